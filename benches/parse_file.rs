@@ -10,14 +10,17 @@
 
 #[path = "../src/artifact_path.rs"]
 #[expect(dead_code)]
+#[cfg_attr(test, expect(unused_imports))]
 mod artifact_path;
 #[path = "../tests/common/mod.rs"]
-#[expect(dead_code)]
+#[expect(dead_code, unused_imports)]
 mod common;
 #[path = "../src/config.rs"]
+#[cfg_attr(test, expect(dead_code))]
 mod config;
 #[path = "../src/digest.rs"]
 #[expect(dead_code)]
+#[cfg_attr(test, expect(unused_imports))]
 mod digest;
 #[path = "../src/fetch_method.rs"]
 #[expect(dead_code)]
@@ -161,8 +164,8 @@ fn large_config() -> String {
     }
 
     config.push_str(
-        r#"    }
-}"#,
+        "    }
+}",
     );
 
     config
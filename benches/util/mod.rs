@@ -9,6 +9,7 @@
  */
 
 #[path = "../../src/util/fs_ctx.rs"]
+#[cfg_attr(test, expect(unused_imports))]
 pub mod fs_ctx;
 #[path = "../../src/util/unarchive.rs"]
 pub mod unarchive;
@@ -0,0 +1,300 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! Checking the size and digest of an artifact, either from a file or while
+//! it is being fetched.
+
+use std::fmt;
+use std::io;
+use std::io::Read;
+
+use sha2::Digest as _;
+use sha2::Sha256;
+use sha2::Sha512;
+
+use crate::config::ArtifactEntry;
+use crate::config::HashAlgorithm;
+use crate::digest::Digest;
+
+/// Accumulates the size and digests of everything written to it.
+pub struct ArtifactHasher {
+    size_in_bytes: u64,
+    /// One for each of the entry's `digests()`, in the same order.
+    states: Vec<HasherState>,
+}
+
+enum HasherState {
+    Blake3(Box<blake3::Hasher>),
+    Sha256(Sha256),
+    Sha512(Sha512),
+}
+
+impl HasherState {
+    fn new(hash: HashAlgorithm) -> Self {
+        match hash {
+            HashAlgorithm::Blake3 => Self::Blake3(Box::new(blake3::Hasher::new())),
+            HashAlgorithm::Sha256 => Self::Sha256(Sha256::new()),
+            HashAlgorithm::Sha512 => Self::Sha512(Sha512::new()),
+        }
+    }
+
+    fn update(&mut self, buf: &[u8]) {
+        match self {
+            Self::Blake3(hasher) => {
+                hasher.update(buf);
+            }
+            Self::Sha256(hasher) => hasher.update(buf),
+            Self::Sha512(hasher) => hasher.update(buf),
+        }
+    }
+
+    fn finalize(self) -> String {
+        match self {
+            Self::Blake3(hasher) => format!("{}", hasher.finalize().to_hex()),
+            Self::Sha256(hasher) => format!("{:x}", hasher.finalize()),
+            Self::Sha512(hasher) => format!("{:x}", hasher.finalize()),
+        }
+    }
+}
+
+impl ArtifactHasher {
+    /// Computes every digest that `artifact_entry` lists.
+    pub fn new(artifact_entry: &ArtifactEntry) -> Self {
+        Self {
+            size_in_bytes: 0,
+            states: artifact_entry
+                .digests()
+                .map(|(hash, _)| HasherState::new(hash))
+                .collect(),
+        }
+    }
+
+    pub fn update(&mut self, buf: &[u8]) {
+        self.size_in_bytes += buf.len() as u64;
+        for state in &mut self.states {
+            state.update(buf);
+        }
+    }
+
+    /// The size of the bytes seen so far and their digests, in the order of
+    /// the entry's `digests()`.
+    pub fn finish(self) -> (u64, Vec<String>) {
+        let digests = self.states.into_iter().map(HasherState::finalize).collect();
+        (self.size_in_bytes, digests)
+    }
+
+    /// Fails unless the bytes seen so far are exactly the artifact described
+    /// by `artifact_entry`, which must be the entry the hasher was created
+    /// for. `artifact` identifies the artifact in errors.
+    pub fn verify(
+        self,
+        artifact_entry: &ArtifactEntry,
+        artifact: &dyn fmt::Display,
+    ) -> anyhow::Result<()> {
+        if self.size_in_bytes != artifact_entry.size {
+            return Err(anyhow::format_err!(
+                "fetched artifact `{}` has incorrect size: {} bytes vs expected {} bytes",
+                artifact,
+                self.size_in_bytes,
+                artifact_entry.size
+            ));
+        }
+
+        for (state, (hash, expected)) in self.states.into_iter().zip(artifact_entry.digests()) {
+            let digest = Digest::try_from(state.finalize())?;
+            if digest != *expected {
+                return Err(anyhow::format_err!(
+                    "fetched artifact `{}` has incorrect {} digest: {} vs expected {}",
+                    artifact,
+                    hash.as_str(),
+                    digest,
+                    expected
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl io::Write for ArtifactHasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Hashes everything that is read through it. Reading fails as soon as
+/// there is more than the artifact's `size`, so that a response that never
+/// ends (or decompresses to far more than expected) cannot fill the disk.
+pub struct HashingReader<R> {
+    inner: R,
+    hasher: ArtifactHasher,
+    max_size: u64,
+}
+
+impl<R: Read> HashingReader<R> {
+    pub fn new(inner: R, artifact_entry: &ArtifactEntry) -> Self {
+        Self {
+            inner,
+            hasher: ArtifactHasher::new(artifact_entry),
+            max_size: artifact_entry.size,
+        }
+    }
+
+    /// Reads (and hashes) whatever is left, e.g., the padding after the end
+    /// of a tar archive, and returns the hasher.
+    pub fn finish(mut self) -> io::Result<ArtifactHasher> {
+        io::copy(&mut self, &mut io::sink())?;
+        Ok(self.hasher)
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if self.hasher.size_in_bytes + n as u64 > self.max_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "fetched artifact is larger than the expected {} bytes",
+                    self.max_size
+                ),
+            ));
+        }
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(size: u64, hash: &str, digest: &str) -> ArtifactEntry {
+        serde_json::from_value(serde_json::json!({
+            "size": size,
+            "hash": hash,
+            "digest": digest,
+            "path": "hello",
+            "providers": [],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn hashing_reader_verifies_stream() -> anyhow::Result<()> {
+        let contents: &[u8] = b"hello world\n";
+        for (hash, digest) in [
+            (
+                "blake3",
+                "dc5a4edb8240b018124052c330270696f96771a63b45250a5c17d3000e823355",
+            ),
+            (
+                "sha256",
+                "a948904f2f0f479b8f8197694b30184b0d2ed1c1cd2a1ec0fb85d299a192a447",
+            ),
+            (
+                "sha512",
+                "db3974a97f2407b7cae1ae637c0030687a11913274d578492558e39c16c017de\
+                 84eacdc8c62fe34ee4e12b4b1428817f09b6a2760c3f8a664ceae94d2434a593",
+            ),
+        ] {
+            let entry = entry(contents.len() as u64, hash, digest);
+            let mut reader = HashingReader::new(contents, &entry);
+            // Only consume part of the stream: `finish` must read the rest.
+            let mut buf = [0; 5];
+            reader.read_exact(&mut buf)?;
+            reader.finish()?.verify(&entry, &"stream")?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn hashing_reader_stops_at_size() {
+        let entry = entry(
+            3,
+            "blake3",
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+        );
+        let mut reader = HashingReader::new(io::repeat(b'a'), &entry);
+        let err = io::copy(&mut reader, &mut io::sink()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            err.to_string(),
+            "fetched artifact is larger than the expected 3 bytes",
+        );
+
+        let reader = HashingReader::new(&b"abcd"[..], &entry);
+        assert!(reader.finish().is_err());
+    }
+
+    #[test]
+    fn verify_reports_size_and_digest() {
+        let entry = entry(
+            3,
+            "blake3",
+            "dc5a4edb8240b018124052c330270696f96771a63b45250a5c17d3000e823355",
+        );
+
+        let mut hasher = ArtifactHasher::new(&entry);
+        hasher.update(b"ab");
+        assert_eq!(
+            hasher.verify(&entry, &"a").unwrap_err().to_string(),
+            "fetched artifact `a` has incorrect size: 2 bytes vs expected 3 bytes",
+        );
+
+        let mut hasher = ArtifactHasher::new(&entry);
+        hasher.update(b"abc");
+        assert!(
+            hasher
+                .verify(&entry, &"a")
+                .unwrap_err()
+                .to_string()
+                .starts_with("fetched artifact `a` has incorrect blake3 digest: "),
+        );
+    }
+
+    #[test]
+    fn verify_checks_every_digest() {
+        let sha512 = "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+                      2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f";
+        let mut entry = entry(
+            3,
+            "blake3",
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+        );
+        entry.additional_digests = serde_json::from_value(serde_json::json!([
+            {"hash": "sha512", "digest": sha512},
+            {"hash": "sha256", "digest": "ab".repeat(32)},
+        ]))
+        .unwrap();
+
+        let mut hasher = ArtifactHasher::new(&entry);
+        hasher.update(b"abc");
+        assert_eq!(
+            hasher.verify(&entry, &"a").unwrap_err().to_string(),
+            format!(
+                "fetched artifact `a` has incorrect sha256 digest: \
+                 ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad vs expected {}",
+                "ab".repeat(32),
+            ),
+        );
+
+        entry.additional_digests.pop();
+        let mut hasher = ArtifactHasher::new(&entry);
+        hasher.update(b"abc");
+        assert!(hasher.verify(&entry, &"a").is_ok());
+    }
+}
@@ -36,11 +36,16 @@ pub struct ArtifactLocation {
     /// The path to use for advisory locking while downloading the specified
     /// artifact.
     pub lock_path: PathBuf,
+    /// Sidecar file describing the contents of `artifact_directory`. It is a
+    /// sibling of `artifact_directory` so that it is not part of the tree it
+    /// describes.
+    pub manifest_path: PathBuf,
     /// Determines what arg0 (`argv[0]`) gets set to.
-    #[cfg_attr(windows, expect(dead_code))]
     pub arg0: Arg0,
 }
 
+/// Returns where the artifact for `artifact_entry` lives in `dotslash_cache`.
+///
 /// In terms of the computing the path within the artifact_directory, it is a
 /// hash of the artifact's content (size, hash algorithm, digest) as well as how
 /// it was decompressed (fetch_method). By design, it is *independent* of the
@@ -52,6 +57,7 @@ pub struct ArtifactLocation {
 ///   (Note the archive might contain only a single file.)
 /// - uncompressed single file: artifact_directory contains a single file whose
 ///   name matches the specified `filename` property in the DotSlash file.
+#[must_use]
 pub fn determine_location(
     artifact_entry: &ArtifactEntry,
     dotslash_cache: &DotslashCache,
@@ -66,6 +72,9 @@ pub fn determine_location(
         arg0,
         readonly,
         providers_order: _,
+        // Any one digest identifies the contents, and leaving these out keeps
+        // the key of existing entries stable when they are added.
+        additional_digests: _,
     } = artifact_entry;
 
     let artifact_hash = blake3::Hasher::new()
@@ -98,11 +107,13 @@ pub fn determine_location(
     let mut executable = artifact_directory.clone();
     executable.extend(Path::new(path.as_str()));
     let lock_path = dotslash_cache.locks_dir(key_prefix).join(key_rest);
+    let manifest_path = dotslash_cache.manifest_path(key_prefix, key_rest);
 
     ArtifactLocation {
         artifact_directory,
         executable,
         lock_path,
+        manifest_path,
         arg0: *arg0,
     }
 }
@@ -111,6 +122,7 @@ fn create_key_for_hash_algorithm(hash: HashAlgorithm) -> &'static [u8] {
     match hash {
         HashAlgorithm::Blake3 => b"blake3",
         HashAlgorithm::Sha256 => b"sha256",
+        HashAlgorithm::Sha512 => b"sha512",
     }
 }
 
@@ -139,6 +151,7 @@ fn create_key_for_format(format: ArtifactFormat, path: &ArtifactPath) -> Cow<'st
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::HashDigest;
     use crate::config::ProvidersOrder;
     use crate::digest::Digest;
 
@@ -157,6 +170,7 @@ mod tests {
             arg0: Arg0::DotslashFile,
             readonly: true,
             providers_order: ProvidersOrder::Sequential,
+            additional_digests: vec![],
         };
         let dotslash_cache = DotslashCache::default();
         let location = determine_location(&artifact_entry, &dotslash_cache);
@@ -182,6 +196,13 @@ mod tests {
                 .locks_dir("0c")
                 .join("7cc25be015e0ab6855aaa7bfea49d5dffe5e4c")
         );
+        assert_eq!(
+            location.manifest_path,
+            dotslash_cache
+                .artifacts_dir()
+                .join("0c")
+                .join("7cc25be015e0ab6855aaa7bfea49d5dffe5e4c.json")
+        );
     }
 
     #[test]
@@ -198,6 +219,7 @@ mod tests {
             providers: vec![],
             arg0: Arg0::DotslashFile,
             providers_order: ProvidersOrder::Sequential,
+            additional_digests: vec![],
             readonly: true,
         };
         let dotslash_cache = DotslashCache::default();
@@ -225,4 +247,37 @@ mod tests {
                 .join("fd21d5ac7f30378d523758d64d902698559d72")
         );
     }
+    #[test]
+    fn additional_digests_do_not_change_location() {
+        let mut artifact_entry = ArtifactEntry {
+            size: 381654729,
+            hash: HashAlgorithm::Sha256,
+            digest: Digest::try_from(
+                "7f83b1657ff1fc53b92dc18148a1d65dfc2d4b1fa3d677284addd200126d9069".to_owned(),
+            )
+            .unwrap(),
+            format: ArtifactFormat::Plain,
+            path: "minesweeper.exe".parse().unwrap(),
+            providers: vec![],
+            arg0: Arg0::DotslashFile,
+            providers_order: ProvidersOrder::Sequential,
+            additional_digests: vec![],
+            readonly: true,
+        };
+        artifact_entry.additional_digests.push(HashDigest {
+            hash: HashAlgorithm::Sha512,
+            digest: Digest::try_from("ab".repeat(64)).unwrap(),
+        });
+        let dotslash_cache = DotslashCache::default();
+        let location = determine_location(&artifact_entry, &dotslash_cache);
+
+        // Same as `paths_for_rename_case`.
+        assert_eq!(
+            location.artifact_directory,
+            dotslash_cache
+                .artifacts_dir()
+                .join("0d")
+                .join("fd21d5ac7f30378d523758d64d902698559d72")
+        );
+    }
 }
@@ -0,0 +1,514 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! A record of what was unpacked into an artifact directory so that drift
+//! (corruption or tampering) in the cache can be detected after the fact.
+//!
+//! Digest verification in `download_artifact` only covers the fetched
+//! archive. Once it has been unpacked, the manifest is the only record of
+//! what the tree is supposed to look like.
+//!
+//! Hashing every unpacked file would make each download noticeably slower,
+//! so `download_artifact` only records metadata (paths, modes and sizes).
+//! `dotslash -- verify` fills in the BLAKE3 hashes the first time it checks
+//! the artifact against a fresh fetch.
+//!
+//! The manifest also records where the artifact came from (the DotSlash
+//! `name`, digest, format, provider, and so on), which is the only way to map
+//! an opaque cache key back to a human-readable tool.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::BufWriter;
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context as _;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::artifact_path::ArtifactPath;
+use crate::config::HashAlgorithm;
+use crate::digest::Digest;
+use crate::fetch_method::ArtifactFormat;
+use crate::inspect_artifact::Magic;
+use crate::util;
+use crate::util::fs_ctx;
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct ArtifactManifest {
+    /// Absent for manifests that were not produced by `download_artifact`,
+    /// e.g., the expected manifest computed by `dotslash -- verify --refetch`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<ArtifactOrigin>,
+    /// Every entry in the artifact directory (excluding the directory
+    /// itself), sorted by path.
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// What produced an artifact directory.
+#[derive(Deserialize, Serialize, Debug)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct ArtifactOrigin {
+    /// The `name` field of the DotSlash file.
+    pub name: String,
+    /// The DotSlash file that triggered the download. Other DotSlash files
+    /// may well share the same artifact.
+    pub dotslash_file: PathBuf,
+    pub size: u64,
+    pub hash: HashAlgorithm,
+    pub digest: Digest,
+    /// `None` for plain (uncompressed, single file) artifacts, mirroring how
+    /// `format` is omitted from the DotSlash file in that case.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<ArtifactFormat>,
+    pub path: ArtifactPath,
+    /// What the start of the artifact, as fetched, looked like, so that
+    /// `lint` can report a `format` that disagrees with it. Absent for
+    /// manifests recorded before this was.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub magic: Option<Magic>,
+    /// The configuration of the provider that successfully fetched the
+    /// artifact.
+    pub provider: Value,
+    /// When the artifact was unpacked, in seconds since the Unix epoch.
+    pub unpacked_at: u64,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ManifestEntry {
+    File {
+        path: String,
+        mode: u32,
+        size: u64,
+        /// `None` if the manifest only records metadata.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        blake3: Option<String>,
+    },
+    Dir {
+        path: String,
+        mode: u32,
+    },
+    Symlink {
+        path: String,
+        target: String,
+    },
+}
+
+impl ManifestEntry {
+    pub fn path(&self) -> &str {
+        match self {
+            Self::File { path, .. } | Self::Dir { path, .. } | Self::Symlink { path, .. } => path,
+        }
+    }
+}
+
+/// A way in which an artifact directory differs from its manifest.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ManifestDifference {
+    Missing(String),
+    Unexpected(String),
+    TypeChanged(String),
+    ContentChanged(String),
+    ModeChanged {
+        path: String,
+        expected: u32,
+        actual: u32,
+    },
+    TargetChanged(String),
+}
+
+impl fmt::Display for ManifestDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing(path) => write!(f, "missing `{path}`"),
+            Self::Unexpected(path) => write!(f, "unexpected `{path}`"),
+            Self::TypeChanged(path) => write!(f, "`{path}` changed type"),
+            Self::ContentChanged(path) => write!(f, "`{path}` has different contents"),
+            Self::ModeChanged {
+                path,
+                expected,
+                actual,
+            } => write!(f, "`{path}` has mode {actual:o}, expected {expected:o}"),
+            Self::TargetChanged(path) => write!(f, "`{path}` points to a different target"),
+        }
+    }
+}
+
+impl ArtifactManifest {
+    /// Builds a manifest by walking `root`. Symlinks are recorded, not
+    /// followed.
+    pub fn from_tree(root: &Path) -> io::Result<Self> {
+        Self::walk(root, true)
+    }
+
+    /// Like `from_tree`, but without hashing the contents of each file.
+    pub fn from_tree_metadata(root: &Path) -> io::Result<Self> {
+        Self::walk(root, false)
+    }
+
+    fn walk(root: &Path, hash_contents: bool) -> io::Result<Self> {
+        let mut entries = vec![];
+        add_entries(root, "", hash_contents, &mut entries)?;
+        Ok(Self {
+            origin: None,
+            entries,
+        })
+    }
+
+    /// Whether every file in the manifest has its BLAKE3 hash recorded.
+    pub fn has_hashes(&self) -> bool {
+        self.entries.iter().all(|entry| match entry {
+            ManifestEntry::File { blake3, .. } => blake3.is_some(),
+            ManifestEntry::Dir { .. } | ManifestEntry::Symlink { .. } => true,
+        })
+    }
+
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let data = fs_ctx::read_to_string(path)?;
+        serde_json::from_str(&data)
+            .with_context(|| format!("failed to parse manifest `{}`", path.display()))
+    }
+
+    /// Like `read`, but only the `origin`, which is much smaller than the
+    /// full manifest of a large artifact. Returns `Ok(None)` if there is no
+    /// manifest at `path`.
+    pub fn read_origin(path: &Path) -> anyhow::Result<Option<ArtifactOrigin>> {
+        #[derive(Deserialize)]
+        struct OriginOnly {
+            #[serde(default)]
+            origin: Option<ArtifactOrigin>,
+        }
+
+        let data = match fs_ctx::read_to_string(path) {
+            Ok(data) => data,
+            Err(err) if util::is_not_found_error(&err) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let manifest: OriginOnly = serde_json::from_str(&data)
+            .with_context(|| format!("failed to parse manifest `{}`", path.display()))?;
+        Ok(manifest.origin)
+    }
+
+    /// Writes the manifest to `path` atomically, i.e., readers see either the
+    /// previous manifest (if any) or the complete new one.
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        let parent = path
+            .parent()
+            .with_context(|| format!("no parent for manifest `{}`", path.display()))?;
+        let temp_file = fs_ctx::namedtempfile_new_in(parent)?;
+        {
+            let mut writer = BufWriter::new(temp_file.as_file());
+            serde_json::to_writer_pretty(&mut writer, self)?;
+            writer.write_all(b"\n")?;
+            writer.flush()?;
+        }
+        temp_file
+            .persist(path)
+            .with_context(|| format!("failed to write manifest `{}`", path.display()))?;
+        Ok(())
+    }
+
+    /// Lists the ways in which `actual` differs from `self`.
+    pub fn diff(&self, actual: &ArtifactManifest) -> Vec<ManifestDifference> {
+        let expected = self
+            .entries
+            .iter()
+            .map(|entry| (entry.path(), entry))
+            .collect::<BTreeMap<_, _>>();
+        let actual = actual
+            .entries
+            .iter()
+            .map(|entry| (entry.path(), entry))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut differences = vec![];
+        for (path, expected_entry) in &expected {
+            let Some(actual_entry) = actual.get(path) else {
+                differences.push(ManifestDifference::Missing((*path).to_owned()));
+                continue;
+            };
+            let path = (*path).to_owned();
+            match (expected_entry, actual_entry) {
+                (
+                    ManifestEntry::File {
+                        mode: expected_mode,
+                        size: expected_size,
+                        blake3: expected_blake3,
+                        ..
+                    },
+                    ManifestEntry::File {
+                        mode: actual_mode,
+                        size: actual_size,
+                        blake3: actual_blake3,
+                        ..
+                    },
+                ) => {
+                    // Without both hashes, only the size can be compared.
+                    let hashes_differ = match (expected_blake3, actual_blake3) {
+                        (Some(expected), Some(actual)) => expected != actual,
+                        _ => false,
+                    };
+                    if expected_size != actual_size || hashes_differ {
+                        differences.push(ManifestDifference::ContentChanged(path.clone()));
+                    }
+                    if expected_mode != actual_mode {
+                        differences.push(ManifestDifference::ModeChanged {
+                            path,
+                            expected: *expected_mode,
+                            actual: *actual_mode,
+                        });
+                    }
+                }
+                (
+                    ManifestEntry::Dir {
+                        mode: expected_mode,
+                        ..
+                    },
+                    ManifestEntry::Dir {
+                        mode: actual_mode, ..
+                    },
+                ) => {
+                    if expected_mode != actual_mode {
+                        differences.push(ManifestDifference::ModeChanged {
+                            path,
+                            expected: *expected_mode,
+                            actual: *actual_mode,
+                        });
+                    }
+                }
+                (
+                    ManifestEntry::Symlink {
+                        target: expected_target,
+                        ..
+                    },
+                    ManifestEntry::Symlink {
+                        target: actual_target,
+                        ..
+                    },
+                ) => {
+                    if expected_target != actual_target {
+                        differences.push(ManifestDifference::TargetChanged(path));
+                    }
+                }
+                _ => differences.push(ManifestDifference::TypeChanged(path)),
+            }
+        }
+        for path in actual.keys() {
+            if !expected.contains_key(path) {
+                differences.push(ManifestDifference::Unexpected((*path).to_owned()));
+            }
+        }
+        differences
+    }
+}
+
+fn add_entries(
+    folder: &Path,
+    prefix: &str,
+    hash_contents: bool,
+    entries: &mut Vec<ManifestEntry>,
+) -> io::Result<()> {
+    let mut dir_entries = fs_ctx::read_dir(folder)?.collect::<io::Result<Vec<_>>>()?;
+    dir_entries.sort_by_key(fs::DirEntry::file_name);
+
+    for dir_entry in dir_entries {
+        let entry_path = dir_entry.path();
+        let path = format!("{prefix}{}", dir_entry.file_name().to_string_lossy());
+        let metadata = fs_ctx::symlink_metadata(&entry_path)?;
+
+        if metadata.is_symlink() {
+            let target = fs::read_link(&entry_path)?;
+            entries.push(ManifestEntry::Symlink {
+                path,
+                target: target.to_string_lossy().into_owned(),
+            });
+        } else if metadata.is_dir() {
+            entries.push(ManifestEntry::Dir {
+                path: path.clone(),
+                mode: mode(&metadata),
+            });
+            add_entries(&entry_path, &format!("{path}/"), hash_contents, entries)?;
+        } else {
+            let blake3 = if hash_contents {
+                let mut hasher = blake3::Hasher::new();
+                hasher.update_mmap(&entry_path)?;
+                Some(hasher.finalize().to_hex().to_string())
+            } else {
+                None
+            };
+            entries.push(ManifestEntry::File {
+                path,
+                mode: mode(&metadata),
+                size: metadata.len(),
+                blake3,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn mode(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    metadata.permissions().mode() & 0o7777
+}
+
+/// Windows only has a read-only bit, so synthesize something that looks
+/// like a Unix mode from it.
+#[cfg(not(unix))]
+fn mode(metadata: &fs::Metadata) -> u32 {
+    if metadata.permissions().readonly() {
+        0o444
+    } else {
+        0o644
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_tree(root: &Path) -> io::Result<()> {
+        fs::create_dir(root.join("bin"))?;
+        fs::write(root.join("bin/tool"), b"#!/bin/sh\n")?;
+        fs::write(root.join("README"), b"hello")?;
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("bin/tool", root.join("tool"))?;
+        Ok(())
+    }
+
+    #[test]
+    fn from_tree_is_sorted_and_relative() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        make_tree(temp_dir.path())?;
+        let manifest = ArtifactManifest::from_tree(temp_dir.path())?;
+
+        let paths = manifest
+            .entries
+            .iter()
+            .map(ManifestEntry::path)
+            .collect::<Vec<_>>();
+        if cfg!(unix) {
+            assert_eq!(paths, vec!["README", "bin", "bin/tool", "tool"]);
+        } else {
+            assert_eq!(paths, vec!["README", "bin", "bin/tool"]);
+        }
+
+        assert_matches::assert_matches!(
+            &manifest.entries[0],
+            ManifestEntry::File { size: 5, blake3: Some(blake3), .. }
+            if *blake3 == blake3::hash(b"hello").to_hex().as_str()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn write_then_read() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let tree = temp_dir.path().join("tree");
+        fs::create_dir(&tree)?;
+        make_tree(&tree)?;
+        let mut manifest = ArtifactManifest::from_tree(&tree)?;
+        manifest.origin = Some(ArtifactOrigin {
+            name: "my_tool".to_owned(),
+            dotslash_file: PathBuf::from("/repo/bin/my_tool"),
+            size: 123,
+            hash: HashAlgorithm::Blake3,
+            digest: Digest::try_from(
+                "d8c1a6a04b0e7ef7d8b8ea6d8e5b2e0ee2ed1f3c6ed3d0a3bba2d6ee54b96c5a".to_owned(),
+            )?,
+            format: Some(ArtifactFormat::TarGz),
+            path: "bin/tool".parse()?,
+            magic: Some(Magic::Gzip),
+            provider: serde_json::json!({"url": "https://example.com/my_tool.tar.gz"}),
+            unpacked_at: 1_700_000_000,
+        });
+        let manifest_path = temp_dir.path().join("tree.json");
+        manifest.write(&manifest_path)?;
+        assert_eq!(ArtifactManifest::read(&manifest_path)?, manifest);
+        Ok(())
+    }
+
+    #[test]
+    fn diff_detects_drift() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        make_tree(temp_dir.path())?;
+        let expected = ArtifactManifest::from_tree(temp_dir.path())?;
+        assert_eq!(expected.diff(&expected), vec![]);
+
+        fs::write(temp_dir.path().join("bin/tool"), b"#!/bin/evil\n")?;
+        fs::remove_file(temp_dir.path().join("README"))?;
+        fs::write(temp_dir.path().join("extra"), b"")?;
+        let actual = ArtifactManifest::from_tree(temp_dir.path())?;
+
+        assert_eq!(
+            expected.diff(&actual),
+            vec![
+                ManifestDifference::Missing("README".to_owned()),
+                ManifestDifference::ContentChanged("bin/tool".to_owned()),
+                ManifestDifference::Unexpected("extra".to_owned()),
+            ],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn metadata_manifest_only_compares_sizes() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        make_tree(temp_dir.path())?;
+        let expected = ArtifactManifest::from_tree_metadata(temp_dir.path())?;
+        assert!(!expected.has_hashes());
+        assert!(ArtifactManifest::from_tree(temp_dir.path())?.has_hashes());
+
+        fs::write(temp_dir.path().join("README"), b"HELLO")?;
+        fs::write(temp_dir.path().join("bin/tool"), b"#!/bin/evil\n")?;
+        let actual = ArtifactManifest::from_tree(temp_dir.path())?;
+
+        assert_eq!(
+            expected.diff(&actual),
+            vec![ManifestDifference::ContentChanged("bin/tool".to_owned())],
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn diff_detects_mode_change() -> anyhow::Result<()> {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let temp_dir = tempfile::tempdir()?;
+        make_tree(temp_dir.path())?;
+        let tool = temp_dir.path().join("bin/tool");
+        fs::set_permissions(&tool, fs::Permissions::from_mode(0o755))?;
+        let expected = ArtifactManifest::from_tree(temp_dir.path())?;
+
+        fs::set_permissions(&tool, fs::Permissions::from_mode(0o777))?;
+        let actual = ArtifactManifest::from_tree(temp_dir.path())?;
+
+        assert_eq!(
+            expected.diff(&actual),
+            vec![ManifestDifference::ModeChanged {
+                path: "bin/tool".to_owned(),
+                expected: 0o755,
+                actual: 0o777,
+            }],
+        );
+        Ok(())
+    }
+}
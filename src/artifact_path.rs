@@ -20,7 +20,7 @@ use thiserror::Error;
 /// `ArtifactPath` is a newtype type for `String` rather than `PathBuf` because
 /// we want it to be unambiguously represented with forward slashes on all
 /// platforms.
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Clone, Debug)]
 #[cfg_attr(test, derive(PartialEq))]
 #[serde(try_from = "String")]
 pub struct ArtifactPath(String);
@@ -48,6 +48,7 @@ impl FromStr for ArtifactPath {
 }
 
 impl ArtifactPath {
+    #[must_use]
     pub fn as_str(&self) -> &str {
         &self.0
     }
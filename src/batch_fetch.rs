@@ -0,0 +1,397 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! Fetching the artifacts for many DotSlash files at once, e.g., to warm the
+//! cache of a fresh CI machine.
+
+use std::collections::HashMap;
+use std::fs::DirEntry;
+use std::io;
+use std::io::Read as _;
+use std::num::NonZeroUsize;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+
+use anyhow::Context as _;
+
+use crate::artifact_location::ArtifactLocation;
+use crate::config::ArtifactEntry;
+use crate::config::REQUIRED_HEADER;
+use crate::dotslash_cache::DotslashCache;
+use crate::download::DotslashFileInfo;
+use crate::download::acquire_download_lock_for_artifact;
+use crate::download::download_artifact;
+use crate::download::ensure_online;
+use crate::download::fetch_verified_artifact;
+use crate::download::verify_artifact;
+use crate::locate::LocatedEntry;
+use crate::locate::PlatformSelection;
+use crate::locate::locate_artifacts;
+use crate::provider::ProviderFactory;
+use crate::util::fs_ctx;
+
+/// Where `fetch_all` puts the artifacts it fetches.
+#[derive(Clone, Debug, Default)]
+pub enum FetchDestination {
+    /// Unpack into the DotSlash cache, as if the DotSlash file were run.
+    #[default]
+    Cache,
+    /// Store the verified artifact, as fetched (i.e., not unpacked), in
+    /// `DIR/PLATFORM/NAME.EXT`, e.g., to populate a mirror.
+    OutputDir(PathBuf),
+    /// Like `OutputDir`, but in `DIR/HASH-DIGEST`, so that DotSlash files
+    /// with the same name never collide and identical artifacts are only
+    /// fetched once, e.g., for a bundle.
+    DigestDir(PathBuf),
+}
+
+#[derive(Debug)]
+pub struct FetchedArtifact {
+    /// The executable in the cache, or the artifact in the output directory.
+    pub path: PathBuf,
+    /// Whether the artifact was already present, i.e., nothing had to be
+    /// downloaded.
+    pub was_cached: bool,
+}
+
+#[derive(Debug)]
+pub struct BatchFetchResult {
+    pub dotslash_file: PathBuf,
+    /// `None` if the DotSlash file could not be read or parsed, so the
+    /// platforms are unknown.
+    pub platform: Option<String>,
+    /// Several DotSlash files may share an artifact, and therefore its
+    /// fetch error, hence the `Arc`.
+    pub result: Result<FetchedArtifact, Arc<anyhow::Error>>,
+}
+
+/// A unique artifact to fetch along with the first DotSlash file that
+/// references it.
+struct FetchJob {
+    name: String,
+    dotslash_file: PathBuf,
+    artifact_entry: ArtifactEntry,
+    artifact_location: ArtifactLocation,
+    /// `None` when unpacking into the cache.
+    output_path: Option<PathBuf>,
+}
+
+/// Fetches the artifacts for the selected `platforms` of `dotslash_files`
+/// using up to `jobs` threads. Each artifact is only fetched once, even if
+/// several DotSlash files (or platforms) share it. Results are returned in
+/// the same order as `dotslash_files`, and then by platform.
+pub fn fetch_all<P: ProviderFactory + Sync>(
+    dotslash_files: &[PathBuf],
+    platforms: &PlatformSelection,
+    destination: &FetchDestination,
+    dotslash_cache: &DotslashCache,
+    jobs: usize,
+    provider_factory: &P,
+) -> Vec<BatchFetchResult> {
+    let mut fetch_jobs = Vec::<FetchJob>::new();
+    // Keyed by where the artifact ends up: the artifact directory in the
+    // cache, or the file in the output directory.
+    let mut job_index_by_destination = HashMap::<PathBuf, usize>::new();
+    // For each (DotSlash file, platform), the index of its job and where
+    // its artifact ends up.
+    let mut located = vec![];
+
+    for dotslash_file in dotslash_files {
+        let result = fs_ctx::read_to_string(dotslash_file)
+            .map_err(anyhow::Error::from)
+            .and_then(|dotslash_data| locate_artifacts(&dotslash_data, dotslash_cache, platforms));
+        let (name, entries) = match result {
+            Ok(result) => result,
+            Err(err) => {
+                located.push((dotslash_file, None, Err(Arc::new(err))));
+                continue;
+            }
+        };
+
+        for entry in entries {
+            let LocatedEntry {
+                platform,
+                artifact_entry,
+                artifact_location,
+            } = entry;
+            let (key, path, output_path) = match destination {
+                FetchDestination::Cache => (
+                    artifact_location.artifact_directory.clone(),
+                    artifact_location.executable.clone(),
+                    None,
+                ),
+                // The name and platform come from the DotSlash file, so they
+                // must not be able to point outside of `dir`.
+                FetchDestination::OutputDir(_)
+                    if !is_path_component(&name) || !is_path_component(&platform) =>
+                {
+                    let err = anyhow::format_err!(
+                        "cannot store `{name}` in the output directory: \
+                         the name and the platform must each be a single path component",
+                    );
+                    located.push((dotslash_file, Some(platform), Err(Arc::new(err))));
+                    continue;
+                }
+                FetchDestination::OutputDir(dir) => {
+                    let output_path = dir
+                        .join(&platform)
+                        .join(format!("{name}{}", artifact_entry.format.extension()));
+                    (output_path.clone(), output_path.clone(), Some(output_path))
+                }
+                FetchDestination::DigestDir(dir) => {
+                    let output_path = dir.join(format!(
+                        "{}-{}",
+                        artifact_entry.hash.as_str(),
+                        artifact_entry.digest,
+                    ));
+                    (output_path.clone(), output_path.clone(), Some(output_path))
+                }
+            };
+
+            let result = match job_index_by_destination.get(&key) {
+                // The same digest is the same artifact as fetched, even if
+                // it is unpacked differently.
+                Some(&index)
+                    if !matches!(destination, FetchDestination::DigestDir(_))
+                        && fetch_jobs[index].artifact_location.artifact_directory
+                            != artifact_location.artifact_directory =>
+                {
+                    Err(Arc::new(anyhow::format_err!(
+                        "`{}` is also the destination for a different artifact in `{}`",
+                        path.display(),
+                        fetch_jobs[index].dotslash_file.display(),
+                    )))
+                }
+                Some(&index) => Ok((index, path)),
+                None => {
+                    fetch_jobs.push(FetchJob {
+                        name: name.clone(),
+                        dotslash_file: dotslash_file.clone(),
+                        artifact_entry,
+                        artifact_location,
+                        output_path,
+                    });
+                    job_index_by_destination.insert(key, fetch_jobs.len() - 1);
+                    Ok((fetch_jobs.len() - 1, path))
+                }
+            };
+            located.push((dotslash_file, Some(platform), result));
+        }
+    }
+
+    let job_results = run_jobs(&fetch_jobs, jobs, provider_factory);
+
+    located
+        .into_iter()
+        .map(|(dotslash_file, platform, located)| {
+            let result = located.and_then(|(index, path)| {
+                job_results[index]
+                    .clone()
+                    .map(|was_cached| FetchedArtifact { path, was_cached })
+            });
+            BatchFetchResult {
+                dotslash_file: dotslash_file.clone(),
+                platform,
+                result,
+            }
+        })
+        .collect()
+}
+
+/// Runs each job on a pool of `jobs` threads. The result for each job is
+/// whether the artifact was already present.
+fn run_jobs<P: ProviderFactory + Sync>(
+    fetch_jobs: &[FetchJob],
+    jobs: usize,
+    provider_factory: &P,
+) -> Vec<Result<bool, Arc<anyhow::Error>>> {
+    let next_job = AtomicUsize::new(0);
+    let results = Mutex::new(
+        (0..fetch_jobs.len())
+            .map(|_| Ok(false))
+            .collect::<Vec<Result<bool, Arc<anyhow::Error>>>>(),
+    );
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, fetch_jobs.len().max(1)) {
+            scope.spawn(|| {
+                loop {
+                    let index = next_job.fetch_add(1, Ordering::Relaxed);
+                    let Some(fetch_job) = fetch_jobs.get(index) else {
+                        break;
+                    };
+                    let result = run_job(fetch_job, provider_factory).map_err(Arc::new);
+                    results.lock().unwrap()[index] = result;
+                }
+            });
+        }
+    });
+
+    results.into_inner().unwrap()
+}
+
+fn run_job<P: ProviderFactory>(fetch_job: &FetchJob, provider_factory: &P) -> anyhow::Result<bool> {
+    let dotslash_file = DotslashFileInfo {
+        name: &fetch_job.name,
+        path: &fetch_job.dotslash_file,
+    };
+    match &fetch_job.output_path {
+        None => {
+            if fetch_job.artifact_location.executable.exists() {
+                return Ok(true);
+            }
+            download_artifact(
+                &fetch_job.artifact_entry,
+                &fetch_job.artifact_location,
+                dotslash_file,
+                provider_factory,
+            )?;
+        }
+        Some(output_path) => {
+            if output_path.exists()
+                && verify_artifact(output_path, &fetch_job.artifact_entry).is_ok()
+            {
+                return Ok(true);
+            }
+            ensure_online(&fetch_job.artifact_location, dotslash_file)?;
+            let output_dir = output_path.parent().context("output path has no parent")?;
+            fs_ctx::create_dir_all(output_dir)?;
+            let file_lock = acquire_download_lock_for_artifact(&fetch_job.artifact_location)
+                .context("failed to get artifact lock")?;
+            let (fetched_artifact, _provider_config) = fetch_verified_artifact(
+                &fetch_job.artifact_entry,
+                output_dir,
+                &file_lock,
+                provider_factory,
+            )?;
+            fs_ctx::rename(&fetched_artifact, output_path)?;
+        }
+    }
+    Ok(false)
+}
+
+/// Directories that are never searched for DotSlash files, in addition to
+/// hidden ones (which include `.git`, `.hg` and `.svn`), because they are
+/// large and only contain copies of (or things built from) the source.
+const SKIPPED_DIRS: [&str; 2] = ["node_modules", "target"];
+
+/// Recursively finds the DotSlash files under `dir`, i.e., the files that
+/// start with `REQUIRED_HEADER`. Symlinks are not followed, and hidden
+/// directories and `SKIPPED_DIRS` are not searched. Entries under `dir` that
+/// cannot be read are skipped with a warning, rather than failing the whole
+/// search. The result is sorted.
+pub fn find_dotslash_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut dotslash_files = vec![];
+    find_dotslash_files_impl(dir, &mut dotslash_files)?;
+    dotslash_files.sort();
+    Ok(dotslash_files)
+}
+
+/// Only fails if `dir` itself cannot be read.
+fn find_dotslash_files_impl(dir: &Path, dotslash_files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs_ctx::read_dir(dir)? {
+        if let Err(err) = find_dotslash_files_in_entry(entry, dotslash_files) {
+            eprintln!(
+                "dotslash warning: skipping part of `{}` while searching for DotSlash files: {err}",
+                dir.display(),
+            );
+        }
+    }
+    Ok(())
+}
+
+fn find_dotslash_files_in_entry(
+    entry: io::Result<DirEntry>,
+    dotslash_files: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    let entry = entry?;
+    let file_type = entry.file_type()?;
+    if file_type.is_dir() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_ref()) {
+            find_dotslash_files_impl(&entry.path(), dotslash_files)?;
+        }
+    } else if file_type.is_file() && has_dotslash_header(&entry.path())? {
+        dotslash_files.push(entry.path());
+    }
+    Ok(())
+}
+
+fn has_dotslash_header(path: &Path) -> io::Result<bool> {
+    // The header must be followed by a newline (`\n` or `\r\n`).
+    let mut buf = Vec::with_capacity(REQUIRED_HEADER.len() + 1);
+    fs_ctx::file_open(path)?
+        .take(REQUIRED_HEADER.len() as u64 + 1)
+        .read_to_end(&mut buf)?;
+    Ok(buf
+        .strip_prefix(REQUIRED_HEADER.as_bytes())
+        .is_some_and(|rest| matches!(rest, b"\n" | b"\r")))
+}
+
+/// Whether `s` is a single, normal path component, i.e., not empty, `.`,
+/// `..` or anything with a separator or a root.
+fn is_path_component(s: &str) -> bool {
+    let mut components = Path::new(s).components();
+    matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(component)), None) if component == s
+    )
+}
+
+/// The default for `fetch --jobs`.
+pub fn default_jobs() -> usize {
+    thread::available_parallelism().map_or(4, NonZeroUsize::get)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_find_dotslash_files() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("bin/nested"))?;
+        fs::write(root.join("bin/a"), "#!/usr/bin/env dotslash\n{}")?;
+        fs::write(root.join("bin/nested/b"), "#!/usr/bin/env dotslash\r\n{}")?;
+        fs::write(root.join("bin/script.sh"), "#!/bin/sh\n")?;
+        fs::write(root.join("bin/dotslashy"), "#!/usr/bin/env dotslashy\n")?;
+        fs::write(root.join("bin/empty"), "")?;
+        for skipped in [".git", "node_modules", "target"] {
+            fs::create_dir_all(root.join(skipped))?;
+            fs::write(root.join(skipped).join("c"), "#!/usr/bin/env dotslash\n{}")?;
+        }
+
+        assert_eq!(
+            find_dotslash_files(root)?,
+            vec![root.join("bin/a"), root.join("bin/nested/b")],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_path_component() {
+        assert!(is_path_component("my_tool"));
+        assert!(is_path_component("linux-x86_64"));
+        assert!(is_path_component(".hidden"));
+        for s in ["", ".", "..", "a/b", "../a", "/a", "a/"] {
+            assert!(!is_path_component(s), "{s:?}");
+        }
+    }
+}
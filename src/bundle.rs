@@ -0,0 +1,273 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! Portable bundles of artifacts for machines that cannot reach any
+//! provider, e.g., air-gapped machines.
+//!
+//! A bundle is a tar file whose first member is `dotslash-bundle.json`
+//! (a `BundleManifest`), followed by the raw artifacts, as fetched, under
+//! `blobs/`. Because the artifacts are not unpacked, they can be verified
+//! against the `size` and `digest` of their DotSlash entries again on import.
+
+use std::collections::HashMap;
+use std::io;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context as _;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::artifact_location::determine_location;
+use crate::batch_fetch;
+use crate::batch_fetch::FetchDestination;
+use crate::config;
+use crate::config::ArtifactEntry;
+use crate::dotslash_cache::DotslashCache;
+use crate::download::DotslashFileInfo;
+use crate::download::acquire_download_lock_for_artifact;
+use crate::download::artifact_origin;
+use crate::download::install_verified_artifact;
+use crate::download::verify_artifact;
+use crate::locate::PlatformSelection;
+use crate::locate::locate_artifacts;
+use crate::provider::ProviderFactory;
+use crate::util::fs_ctx;
+
+const BUNDLE_MANIFEST_NAME: &str = "dotslash-bundle.json";
+
+#[derive(Deserialize, Serialize, Debug)]
+struct BundleManifest {
+    artifacts: Vec<BundleRecord>,
+}
+
+/// One platform entry of one DotSlash file.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct BundleRecord {
+    /// The `name` field of the DotSlash file.
+    pub name: String,
+    /// The DotSlash file on the machine that exported the bundle.
+    pub dotslash_file: PathBuf,
+    pub platform: String,
+    /// Path of the raw artifact within the bundle. Records for identical
+    /// artifacts share a blob.
+    pub blob: String,
+    /// The entry exactly as it appears in the DotSlash file.
+    pub entry: Value,
+}
+
+#[derive(Debug)]
+pub enum ImportStatus {
+    Imported,
+    AlreadyCached,
+}
+
+/// Fetches and verifies the artifacts for the selected `platforms` of
+/// `dotslash_files` and writes them to a bundle at `output`. Nothing is
+/// written unless every artifact could be fetched. Returns the number of
+/// records in the bundle.
+pub fn export_bundle<P: ProviderFactory + Sync>(
+    dotslash_files: &[PathBuf],
+    platforms: &PlatformSelection,
+    output: &Path,
+    dotslash_cache: &DotslashCache,
+    jobs: usize,
+    provider_factory: &P,
+) -> anyhow::Result<usize> {
+    let output_dir = match output.parent() {
+        Some(parent) if parent != Path::new("") => parent,
+        _ => Path::new("."),
+    };
+    let scratch_dir = fs_ctx::tempdir_in(output_dir)?;
+    let results = batch_fetch::fetch_all(
+        dotslash_files,
+        platforms,
+        &FetchDestination::DigestDir(scratch_dir.path().to_owned()),
+        dotslash_cache,
+        jobs,
+        provider_factory,
+    );
+
+    let mut failures = vec![];
+    let mut fetched = HashMap::new();
+    for result in results {
+        match result.result {
+            Ok(artifact) => {
+                let platform = result.platform.unwrap_or_default();
+                fetched.insert((result.dotslash_file, platform), artifact.path);
+            }
+            Err(err) => failures.push(format!(
+                "`{}`: {}{:#}",
+                result.dotslash_file.display(),
+                result
+                    .platform
+                    .map(|platform| format!("{platform}: "))
+                    .unwrap_or_default(),
+                err,
+            )),
+        }
+    }
+    if !failures.is_empty() {
+        return Err(anyhow::format_err!(
+            "failed to fetch {} artifact(s):\n{}",
+            failures.len(),
+            failures.join("\n"),
+        ));
+    }
+
+    let mut records = vec![];
+    // Blob name to the fetched artifact.
+    let mut blobs = HashMap::new();
+    for dotslash_file in dotslash_files {
+        let dotslash_data = fs_ctx::read_to_string(dotslash_file)?;
+        let (original_json, _config_file) = config::parse_file(&dotslash_data)
+            .with_context(|| format!("failed to parse `{}`", dotslash_file.display()))?;
+        let (name, entries) = locate_artifacts(&dotslash_data, dotslash_cache, platforms)?;
+        for entry in entries {
+            let fetched_artifact = fetched
+                .get(&(dotslash_file.clone(), entry.platform.clone()))
+                .context("artifact was not fetched")?;
+            let blob = format!("blobs/{}", entry.artifact_entry.digest);
+            blobs.entry(blob.clone()).or_insert(fetched_artifact);
+            records.push(BundleRecord {
+                name: name.clone(),
+                dotslash_file: dunce::canonicalize(dotslash_file)
+                    .unwrap_or_else(|_| dotslash_file.clone()),
+                entry: original_json["platforms"][&entry.platform].clone(),
+                platform: entry.platform,
+                blob,
+            });
+        }
+    }
+
+    let temp_file = fs_ctx::namedtempfile_new_in(output_dir)?;
+    {
+        let mut builder = tar::Builder::new(BufWriter::new(temp_file.as_file()));
+        let manifest = serde_json::to_vec_pretty(&BundleManifest { artifacts: records })?;
+        let mut header = tar::Header::new_gnu();
+        header.set_size(manifest.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, BUNDLE_MANIFEST_NAME, manifest.as_slice())?;
+
+        let mut blobs = blobs.into_iter().collect::<Vec<_>>();
+        blobs.sort();
+        for (blob, fetched_artifact) in blobs {
+            builder.append_path_with_name(fetched_artifact, blob)?;
+        }
+        builder.into_inner()?;
+    }
+    let num_records = fetched.len();
+    temp_file
+        .persist(output)
+        .with_context(|| format!("failed to write bundle `{}`", output.display()))?;
+
+    Ok(num_records)
+}
+
+/// Verifies each artifact in the bundle at `bundle_path` against its entry
+/// and installs it into `dotslash_cache`, exactly as if it had been fetched.
+/// Returns the result for each record in the bundle.
+pub fn import_bundle(
+    bundle_path: &Path,
+    dotslash_cache: &DotslashCache,
+) -> anyhow::Result<Vec<(BundleRecord, anyhow::Result<ImportStatus>)>> {
+    let not_a_bundle =
+        || anyhow::format_err!("`{}` is not a DotSlash bundle", bundle_path.display());
+    let mut archive = tar::Archive::new(BufReader::new(fs_ctx::file_open(bundle_path)?));
+    let mut entries = archive.entries()?;
+
+    let mut first = entries.next().ok_or_else(not_a_bundle)??;
+    if first.path()?.as_ref() != Path::new(BUNDLE_MANIFEST_NAME) {
+        return Err(not_a_bundle());
+    }
+    let manifest: BundleManifest = serde_json::from_reader(&mut first)
+        .with_context(|| format!("invalid manifest in `{}`", bundle_path.display()))?;
+
+    let mut results = manifest
+        .artifacts
+        .into_iter()
+        .map(|record| {
+            let result = Err(anyhow::format_err!(
+                "`{}` is missing from bundle",
+                record.blob
+            ));
+            (record, result)
+        })
+        .collect::<Vec<_>>();
+
+    fs_ctx::create_dir_all(dotslash_cache.artifacts_dir())?;
+    let provider_config = serde_json::json!({
+        "type": "bundle",
+        "path": dunce::canonicalize(bundle_path).unwrap_or_else(|_| bundle_path.to_owned()),
+    });
+    for entry in entries {
+        let mut entry = entry?;
+        let blob = entry.path()?.to_string_lossy().into_owned();
+        if !results.iter().any(|(record, _)| record.blob == blob) {
+            continue;
+        }
+
+        // Extracted within the cache so that it is on the same filesystem.
+        let blob_file = fs_ctx::namedtempfile_new_in(dotslash_cache.artifacts_dir())?;
+        io::copy(&mut entry, &mut blob_file.as_file())?;
+
+        for (record, result) in &mut results {
+            if record.blob == blob {
+                *result = import_record(record, blob_file.path(), dotslash_cache, &provider_config);
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+fn import_record(
+    record: &BundleRecord,
+    blob_path: &Path,
+    dotslash_cache: &DotslashCache,
+    provider_config: &Value,
+) -> anyhow::Result<ImportStatus> {
+    let artifact_entry =
+        ArtifactEntry::deserialize(&record.entry).context("invalid entry in bundle")?;
+    verify_artifact(blob_path, &artifact_entry)?;
+
+    let artifact_location = determine_location(&artifact_entry, dotslash_cache);
+    if artifact_location.executable.exists() {
+        return Ok(ImportStatus::AlreadyCached);
+    }
+    let artifact_parent_dir = artifact_location
+        .artifact_directory
+        .parent()
+        .context("artifact directory has no parent")?;
+    fs_ctx::create_dir_all(artifact_parent_dir)?;
+    let _file_lock = acquire_download_lock_for_artifact(&artifact_location)
+        .context("failed to get artifact lock")?;
+
+    // Installing may consume the file it is given, and several records may
+    // share a blob, so install a copy.
+    let fetched_artifact = fs_ctx::namedtempfile_new_in(artifact_parent_dir)?.into_temp_path();
+    fs_ctx::copy(blob_path, &fetched_artifact)?;
+    let dotslash_file = DotslashFileInfo {
+        name: &record.name,
+        path: &record.dotslash_file,
+    };
+    install_verified_artifact(
+        &artifact_entry,
+        &artifact_location,
+        &fetched_artifact,
+        artifact_origin(&artifact_entry, dotslash_file, provider_config),
+    )?;
+
+    Ok(ImportStatus::Imported)
+}
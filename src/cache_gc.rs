@@ -0,0 +1,471 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! Least-recently-used eviction for the DotSlash cache.
+
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use anyhow::Context as _;
+
+use crate::dotslash_cache;
+use crate::dotslash_cache::CachedArtifact;
+use crate::dotslash_cache::DotslashCache;
+use crate::util;
+use crate::util::TreeUsage;
+use crate::util::fs_ctx;
+
+/// Artifacts used more recently than this are never evicted, as they may be
+/// running, or about to be, e.g., between `locate_artifact` and `exec`.
+pub const GRACE_PERIOD: Duration = Duration::from_secs(10 * 60);
+
+/// Temp dirs and files in the cache that have not been modified for this
+/// long were left behind by a DotSlash process that was killed, e.g., in the
+/// middle of a fetch, so they are deleted regardless of the budget.
+pub const STALE_TEMP_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Default)]
+pub struct GcOptions {
+    /// Evict least-recently-used artifacts until the cache is at most this
+    /// many bytes.
+    pub max_size: Option<u64>,
+    /// Evict artifacts that have not been used for longer than this.
+    pub max_age: Option<Duration>,
+    /// Report what would be evicted without deleting anything.
+    pub dry_run: bool,
+}
+
+#[derive(Debug)]
+pub struct GcArtifact {
+    pub artifact_directory: PathBuf,
+    pub size_in_bytes: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct GcReport {
+    /// Artifacts that were evicted (or would be, for a dry run).
+    pub removed: Vec<GcArtifact>,
+    /// Artifacts that should have been evicted but were locked by another
+    /// DotSlash process or used within the `GRACE_PERIOD`.
+    pub in_use: Vec<GcArtifact>,
+    /// Temp dirs and files older than `STALE_TEMP_AGE` that were deleted (or
+    /// would be, for a dry run). Not included in the cache sizes.
+    pub removed_temp: Vec<GcArtifact>,
+    /// Artifacts that should have been evicted but could not be deleted.
+    pub failed: Vec<(GcArtifact, anyhow::Error)>,
+    /// Total number of bytes in the cache before eviction.
+    pub size_before: u64,
+    /// Total number of bytes in the cache after eviction.
+    pub size_after: u64,
+    /// Number of artifacts left in the cache after eviction.
+    pub num_remaining: usize,
+}
+
+/// Evicts artifacts from the cache, least-recently-used first, until the
+/// cache satisfies the budget in `options`.
+///
+/// "Last used" is the most recent mtime within the artifact directory, which
+/// `locate_artifact` bumps every time the artifact is run (on Linux; on other
+/// platforms, only fetching it counts). Each artifact is only deleted while
+/// holding its download lock, so an artifact that is in the middle of being
+/// fetched is never touched. Running an artifact does not take the lock, so
+/// artifacts used within the `GRACE_PERIOD` are kept as well, even if that
+/// leaves the cache over budget.
+///
+/// Stale temp dirs and files (see `STALE_TEMP_AGE`) are deleted first.
+pub fn collect_garbage(
+    dotslash_cache: &DotslashCache,
+    options: &GcOptions,
+    now: SystemTime,
+) -> anyhow::Result<GcReport> {
+    let removed_temp = collect_stale_temp_entries(dotslash_cache, options, now)?;
+
+    let mut artifacts = dotslash_cache
+        .read_artifacts()
+        .context("failed to list artifacts in cache")?
+        .into_iter()
+        .filter_map(|artifact| {
+            // An artifact that disappears while we are walking the cache was
+            // most likely removed by a concurrent `gc` or `clean`.
+            let usage = util::tree_usage(&artifact.artifact_directory).ok()?;
+            Some((artifact, usage))
+        })
+        .collect::<Vec<(CachedArtifact, TreeUsage)>>();
+
+    // Oldest first.
+    artifacts.sort_by_key(|(_, usage)| usage.last_modified);
+
+    let size_before = artifacts.iter().map(|(_, usage)| usage.size_in_bytes).sum();
+    let mut report = GcReport {
+        size_before,
+        size_after: size_before,
+        num_remaining: artifacts.len(),
+        removed_temp,
+        ..Default::default()
+    };
+
+    for (artifact, usage) in artifacts {
+        let is_expired = options.max_age.is_some_and(|max_age| {
+            now.duration_since(usage.last_modified)
+                .is_ok_and(|age| age > max_age)
+        });
+        let is_over_budget = options
+            .max_size
+            .is_some_and(|max_size| report.size_after > max_size);
+        if !is_expired && !is_over_budget {
+            // Everything after this artifact was used more recently, so it
+            // is not expired either, and the size budget is already met.
+            break;
+        }
+
+        let gc_artifact = GcArtifact {
+            artifact_directory: artifact.artifact_directory.clone(),
+            size_in_bytes: usage.size_in_bytes,
+        };
+        // An mtime in the future counts as recent, too.
+        let is_recent = !now
+            .duration_since(usage.last_modified)
+            .is_ok_and(|age| age >= GRACE_PERIOD);
+        if is_recent {
+            report.in_use.push(gc_artifact);
+            continue;
+        }
+
+        let result = if options.dry_run {
+            Ok(true)
+        } else {
+            dotslash_cache::remove_artifact(
+                &artifact.artifact_directory,
+                &artifact.lock_path,
+                &artifact.manifest_path,
+            )
+        };
+        match result {
+            Ok(true) => {
+                report.size_after -= usage.size_in_bytes;
+                report.num_remaining -= 1;
+                report.removed.push(gc_artifact);
+            }
+            Ok(false) => report.in_use.push(gc_artifact),
+            Err(err) => report.failed.push((gc_artifact, err)),
+        }
+    }
+
+    Ok(report)
+}
+
+/// Deletes the temp dirs and files in the cache that are older than
+/// `STALE_TEMP_AGE`. Ones that cannot be deleted, or that disappear in the
+/// meantime, are skipped: they are tried again by the next `gc`.
+fn collect_stale_temp_entries(
+    dotslash_cache: &DotslashCache,
+    options: &GcOptions,
+    now: SystemTime,
+) -> anyhow::Result<Vec<GcArtifact>> {
+    let mut removed = vec![];
+    for path in dotslash_cache
+        .read_temp_entries()
+        .context("failed to list temp entries in cache")?
+    {
+        let Ok(usage) = temp_entry_usage(&path) else {
+            continue;
+        };
+        let is_stale = now
+            .duration_since(usage.last_modified)
+            .is_ok_and(|age| age > STALE_TEMP_AGE);
+        if !is_stale {
+            continue;
+        }
+        if !options.dry_run && remove_temp_entry(&path).is_err() {
+            continue;
+        }
+        removed.push(GcArtifact {
+            artifact_directory: path,
+            size_in_bytes: usage.size_in_bytes,
+        });
+    }
+    Ok(removed)
+}
+
+fn temp_entry_usage(path: &Path) -> anyhow::Result<TreeUsage> {
+    let metadata = fs_ctx::symlink_metadata(path)?;
+    if metadata.is_dir() {
+        return Ok(util::tree_usage(path)?);
+    }
+    Ok(TreeUsage {
+        size_in_bytes: metadata.len(),
+        last_modified: metadata.modified()?,
+    })
+}
+
+fn remove_temp_entry(path: &Path) -> anyhow::Result<()> {
+    if fs_ctx::symlink_metadata(path)?.is_dir() {
+        // The artifact inside may already have been made read-only.
+        util::make_tree_entries_writable(path)?;
+        fs_ctx::remove_dir_all(path)?;
+    } else {
+        fs_ctx::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Parses durations such as `90s`, `30m`, `12h`, `7d` or `2w`. A bare number
+/// is interpreted as seconds.
+pub fn parse_duration(s: &str) -> anyhow::Result<Duration> {
+    let err = || anyhow::format_err!("invalid duration `{s}` (expected e.g. `12h`, `7d`, `2w`)");
+    let digits_end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (digits, unit) = s.split_at(digits_end);
+    let number = digits.parse::<u64>().map_err(|_| err())?;
+    let multiplier = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(err()),
+    };
+    number
+        .checked_mul(multiplier)
+        .map(Duration::from_secs)
+        .ok_or_else(err)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use filetime::FileTime;
+
+    use super::*;
+    use crate::util::FileLock;
+
+    const NOW_SECS: i64 = 1_700_000_000;
+    const DAY_SECS: i64 = 24 * 60 * 60;
+
+    fn now() -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(NOW_SECS as u64)
+    }
+
+    /// Creates a fake artifact of `size` bytes last used `days_ago`.
+    fn add_artifact(cache: &DotslashCache, key: &str, size: usize, days_ago: i64) -> PathBuf {
+        let (prefix, rest) = key.split_at(2);
+        let dir = cache.artifacts_dir().join(prefix).join(rest);
+        fs::create_dir_all(&dir).unwrap();
+        let exe = dir.join("tool");
+        fs::write(&exe, vec![0u8; size]).unwrap();
+        let mtime = FileTime::from_unix_time(NOW_SECS - days_ago * DAY_SECS, 0);
+        filetime::set_file_mtime(&exe, mtime).unwrap();
+        filetime::set_file_mtime(&dir, mtime).unwrap();
+        dir
+    }
+
+    fn removed(report: &GcReport) -> Vec<&Path> {
+        report
+            .removed
+            .iter()
+            .map(|a| a.artifact_directory.as_path())
+            .collect()
+    }
+
+    #[test]
+    fn evicts_by_age() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let cache = DotslashCache::new_in(temp_dir.path());
+        let old = add_artifact(&cache, "aa0001", 10, 40);
+        let recent = add_artifact(&cache, "bb0002", 10, 1);
+
+        let options = GcOptions {
+            max_age: Some(Duration::from_secs(30 * DAY_SECS as u64)),
+            ..Default::default()
+        };
+        let report = collect_garbage(&cache, &options, now())?;
+
+        assert_eq!(removed(&report), vec![old.as_path()]);
+        assert!(!old.exists());
+        assert!(recent.exists());
+        assert_eq!(report.size_before, 20);
+        assert_eq!(report.size_after, 10);
+        assert_eq!(report.num_remaining, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn evicts_least_recently_used_until_under_budget() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let cache = DotslashCache::new_in(temp_dir.path());
+        let a = add_artifact(&cache, "aa0001", 100, 3);
+        let b = add_artifact(&cache, "bb0002", 100, 5);
+        let c = add_artifact(&cache, "cc0003", 100, 1);
+
+        let options = GcOptions {
+            max_size: Some(150),
+            ..Default::default()
+        };
+        let report = collect_garbage(&cache, &options, now())?;
+
+        assert_eq!(removed(&report), vec![b.as_path(), a.as_path()]);
+        assert!(!a.exists());
+        assert!(!b.exists());
+        assert!(c.exists());
+        assert_eq!(report.size_after, 100);
+        Ok(())
+    }
+
+    #[test]
+    fn dry_run_does_not_delete() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let cache = DotslashCache::new_in(temp_dir.path());
+        let a = add_artifact(&cache, "aa0001", 100, 3);
+
+        let options = GcOptions {
+            max_size: Some(0),
+            dry_run: true,
+            ..Default::default()
+        };
+        let report = collect_garbage(&cache, &options, now())?;
+
+        assert_eq!(removed(&report), vec![a.as_path()]);
+        assert!(a.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn skips_locked_artifacts() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let cache = DotslashCache::new_in(temp_dir.path());
+        let a = add_artifact(&cache, "aa0001", 100, 5);
+        let b = add_artifact(&cache, "bb0002", 100, 3);
+
+        let lock_path = cache.locks_dir("aa").join("0001");
+        fs::create_dir_all(lock_path.parent().unwrap())?;
+        let _lock = FileLock::acquire(&lock_path)?;
+
+        let options = GcOptions {
+            max_size: Some(100),
+            ..Default::default()
+        };
+        let report = collect_garbage(&cache, &options, now())?;
+
+        assert_eq!(report.in_use.len(), 1);
+        assert_eq!(report.in_use[0].artifact_directory, a);
+        assert_eq!(removed(&report), vec![b.as_path()]);
+        assert!(a.exists());
+        assert!(!b.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn removes_read_only_artifacts() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let cache = DotslashCache::new_in(temp_dir.path());
+        let a = add_artifact(&cache, "aa0001", 100, 5);
+        util::make_tree_entries_read_only(&a)?;
+        let mut perms = fs::metadata(&a)?.permissions();
+        perms.set_readonly(true);
+        fs::set_permissions(&a, perms)?;
+
+        let options = GcOptions {
+            max_size: Some(0),
+            ..Default::default()
+        };
+        let report = collect_garbage(&cache, &options, now())?;
+
+        assert_eq!(removed(&report), vec![a.as_path()]);
+        assert!(!a.exists());
+        // Only the (now empty) prefix dir is left behind.
+        assert_eq!(fs::read_dir(a.parent().unwrap())?.count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_recently_used_artifacts() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let cache = DotslashCache::new_in(temp_dir.path());
+        let a = add_artifact(&cache, "aa0001", 100, 3);
+        let b = add_artifact(&cache, "bb0002", 100, 0);
+
+        let options = GcOptions {
+            max_size: Some(0),
+            ..Default::default()
+        };
+        let report = collect_garbage(&cache, &options, now())?;
+
+        assert_eq!(removed(&report), vec![a.as_path()]);
+        assert_eq!(report.in_use.len(), 1);
+        assert_eq!(report.in_use[0].artifact_directory, b);
+        assert!(b.exists());
+        assert_eq!(report.size_after, 100);
+        Ok(())
+    }
+
+    #[test]
+    fn removes_stale_temp_entries() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let cache = DotslashCache::new_in(temp_dir.path());
+        let a = add_artifact(&cache, "aa0001", 100, 0);
+        let prefix_dir = a.parent().unwrap();
+
+        let stale_dir = prefix_dir.join(".tmpStale");
+        fs::create_dir_all(stale_dir.join("artifact"))?;
+        fs::write(stale_dir.join("artifact/tool"), "partial")?;
+        util::make_tree_entries_read_only(&stale_dir)?;
+        let stale_file = prefix_dir.join(".tmpFile");
+        fs::write(&stale_file, "{}")?;
+        let fresh_dir = prefix_dir.join(".tmpFresh");
+        fs::create_dir(&fresh_dir)?;
+        let old = FileTime::from_unix_time(NOW_SECS - 2 * DAY_SECS, 0);
+        for path in [stale_dir.join("artifact/tool"), stale_dir.join("artifact")] {
+            filetime::set_file_mtime(path, old)?;
+        }
+        filetime::set_file_mtime(&stale_dir, old)?;
+        filetime::set_file_mtime(&stale_file, old)?;
+        filetime::set_file_mtime(&fresh_dir, FileTime::from_unix_time(NOW_SECS, 0))?;
+
+        let options = GcOptions {
+            max_age: Some(Duration::from_secs(30 * DAY_SECS as u64)),
+            ..Default::default()
+        };
+        let report = collect_garbage(&cache, &options, now())?;
+
+        let removed_temp = report
+            .removed_temp
+            .iter()
+            .map(|a| a.artifact_directory.as_path())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            removed_temp,
+            vec![stale_file.as_path(), stale_dir.as_path()]
+        );
+        assert!(!stale_dir.exists());
+        assert!(!stale_file.exists());
+        assert!(fresh_dir.exists());
+        assert!(a.exists());
+        assert!(report.removed.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("0").unwrap(), Duration::from_secs(0));
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("30m").unwrap(), Duration::from_secs(1800));
+        assert_eq!(parse_duration("12h").unwrap(), Duration::from_secs(43200));
+        assert_eq!(parse_duration("7d").unwrap(), Duration::from_secs(604800));
+        assert_eq!(parse_duration("2w").unwrap(), Duration::from_secs(1209600));
+
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("7y").is_err());
+        assert!(parse_duration("-7d").is_err());
+    }
+}
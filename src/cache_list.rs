@@ -0,0 +1,154 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! Inspection of what is in the DotSlash cache.
+
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use anyhow::Context as _;
+use serde::Serialize;
+
+use crate::artifact_manifest::ArtifactManifest;
+use crate::artifact_manifest::ArtifactOrigin;
+use crate::dotslash_cache::DotslashCache;
+use crate::util;
+
+#[derive(Serialize, Debug)]
+pub struct CacheListEntry {
+    /// Same as `CachedArtifact::key`.
+    pub key: String,
+    pub artifact_directory: PathBuf,
+    pub size_in_bytes: u64,
+    /// Seconds since the Unix epoch. See `tree_usage` for why mtime is a
+    /// proxy for "last used".
+    pub last_used: u64,
+    /// `None` if the artifact has no manifest, e.g., because it was fetched
+    /// by an older version of DotSlash.
+    pub origin: Option<ArtifactOrigin>,
+}
+
+/// Lists the artifacts in the cache, largest first.
+pub fn list_artifacts(dotslash_cache: &DotslashCache) -> anyhow::Result<Vec<CacheListEntry>> {
+    let mut entries = dotslash_cache
+        .read_artifacts()
+        .context("failed to list artifacts in cache")?
+        .into_iter()
+        .filter_map(|artifact| {
+            // Skip artifacts that disappear while we are walking the cache.
+            let usage = util::tree_usage(&artifact.artifact_directory).ok()?;
+            // The manifest is advisory, so an unreadable one is treated the
+            // same as a missing one.
+            let origin = ArtifactManifest::read_origin(&artifact.manifest_path)
+                .ok()
+                .flatten();
+            Some(CacheListEntry {
+                key: artifact.key,
+                artifact_directory: artifact.artifact_directory,
+                size_in_bytes: usage.size_in_bytes,
+                last_used: usage
+                    .last_modified
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .map_or(0, |duration| duration.as_secs()),
+                origin,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    entries.sort_by(|a, b| {
+        b.size_in_bytes
+            .cmp(&a.size_in_bytes)
+            .then_with(|| a.key.cmp(&b.key))
+    });
+    Ok(entries)
+}
+
+/// Formats how long ago something happened using the largest whole unit,
+/// e.g., `3d ago`.
+pub fn format_age(age: Duration) -> String {
+    const UNITS: &[(&str, u64)] = &[
+        ("w", 7 * 24 * 60 * 60),
+        ("d", 24 * 60 * 60),
+        ("h", 60 * 60),
+        ("m", 60),
+    ];
+    let secs = age.as_secs();
+    for (unit, unit_secs) in UNITS {
+        if secs >= *unit_secs {
+            return format!("{}{unit} ago", secs / unit_secs);
+        }
+    }
+    "just now".to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn lists_largest_first_with_origin() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let cache = DotslashCache::new_in(temp_dir.path());
+        for (key, size) in [("aa0001", 10), ("bb0002", 100)] {
+            let (prefix, rest) = key.split_at(2);
+            let dir = cache.artifacts_dir().join(prefix).join(rest);
+            fs::create_dir_all(&dir)?;
+            fs::write(dir.join("tool"), vec![0u8; size])?;
+        }
+        fs::write(
+            cache.manifest_path("aa", "0001"),
+            r#"{
+                "origin": {
+                    "name": "my_tool",
+                    "dotslash_file": "/repo/bin/my_tool",
+                    "size": 10,
+                    "hash": "blake3",
+                    "digest": "d8c1a6a04b0e7ef7d8b8ea6d8e5b2e0ee2ed1f3c6ed3d0a3bba2d6ee54b96c5a",
+                    "path": "tool",
+                    "provider": {"url": "https://example.com/tool"},
+                    "unpacked_at": 1700000000
+                },
+                "entries": []
+            }"#,
+        )?;
+        // A corrupt manifest is ignored.
+        fs::write(cache.manifest_path("bb", "0002"), "{")?;
+
+        let entries = list_artifacts(&cache)?;
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| (entry.key.as_str(), entry.size_in_bytes))
+                .collect::<Vec<_>>(),
+            vec![("bb0002", 100), ("aa0001", 10)],
+        );
+        assert!(entries[0].origin.is_none());
+        assert_eq!(
+            entries[1]
+                .origin
+                .as_ref()
+                .map(|origin| origin.name.as_str()),
+            Some("my_tool"),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(Duration::from_secs(59)), "just now");
+        assert_eq!(format_age(Duration::from_secs(60)), "1m ago");
+        assert_eq!(format_age(Duration::from_secs(2 * 60 * 60 + 1)), "2h ago");
+        assert_eq!(format_age(Duration::from_secs(3 * 24 * 60 * 60)), "3d ago");
+        assert_eq!(format_age(Duration::from_secs(15 * 24 * 60 * 60)), "2w ago");
+    }
+}
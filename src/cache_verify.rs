@@ -0,0 +1,204 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! Detects (and optionally repairs) drift between an unpacked artifact in
+//! the cache and what its DotSlash entry says it should contain.
+
+use anyhow::Context as _;
+
+use crate::artifact_location::ArtifactLocation;
+use crate::artifact_manifest::ArtifactManifest;
+use crate::artifact_manifest::ManifestDifference;
+use crate::config::ArtifactEntry;
+use crate::dotslash_cache;
+use crate::download::DotslashFileInfo;
+use crate::download::acquire_download_lock_for_artifact;
+use crate::download::download_artifact;
+use crate::download::ensure_online;
+use crate::download::fetch_verified_artifact;
+use crate::download::is_offline;
+use crate::download::unpack_verified_artifact;
+use crate::provider::ProviderFactory;
+use crate::util;
+use crate::util::fs_ctx;
+
+#[derive(Debug, Default)]
+pub struct VerifyOptions {
+    /// Ignore the recorded manifest and rebuild the expected tree from a
+    /// freshly fetched (and digest-verified) artifact.
+    pub refetch: bool,
+    /// Replace the cached artifact if it has drifted.
+    pub repair: bool,
+    /// Never fetch the artifact: only compare against the recorded manifest,
+    /// as in offline mode, e.g., for a DotSlash file that fails its
+    /// signature checks.
+    pub no_fetch: bool,
+}
+
+#[derive(Debug)]
+pub enum VerifyStatus {
+    /// The artifact is not in the cache, so there is nothing to verify.
+    NotCached,
+    /// The cached artifact matches.
+    Ok,
+    /// The cached artifact matches the paths, modes and sizes in the recorded
+    /// manifest, but the contents of its files could not be checked because
+    /// the manifest has no hashes yet and offline mode is enabled (or
+    /// `options.no_fetch` is set).
+    Unverified,
+    /// The cached artifact differs from what was expected.
+    Drift(Vec<ManifestDifference>),
+    /// The cached artifact differed and was replaced.
+    Repaired(Vec<ManifestDifference>),
+}
+
+/// Compares the cached artifact at `artifact_location` against the manifest
+/// recorded when it was unpacked.
+///
+/// If there is no recorded manifest (e.g., the artifact was unpacked by an
+/// older DotSlash), the recorded manifest has no hashes yet, or
+/// `options.refetch` is set, the expected manifest is instead computed by
+/// fetching the artifact and unpacking it into a scratch directory. In the
+/// second case, the hashes are then recorded for next time, unless offline
+/// mode is enabled or `options.no_fetch` is set: then only the metadata in
+/// the recorded manifest is compared, and matching artifacts are reported as
+/// `Unverified`.
+///
+/// Fetching reads the global config, just like `download_artifact`.
+pub fn verify_cached_artifact<P: ProviderFactory>(
+    artifact_entry: &ArtifactEntry,
+    artifact_location: &ArtifactLocation,
+    dotslash_file: DotslashFileInfo<'_>,
+    options: &VerifyOptions,
+    provider_factory: &P,
+) -> anyhow::Result<VerifyStatus> {
+    if !artifact_location.artifact_directory.exists() {
+        return Ok(VerifyStatus::NotCached);
+    }
+
+    let recorded = if !options.refetch && artifact_location.manifest_path.exists() {
+        Some(ArtifactManifest::read(&artifact_location.manifest_path)?)
+    } else {
+        None
+    };
+    let expected = match recorded {
+        Some(recorded) if recorded.has_hashes() => recorded,
+        // Fetching would fail, but the metadata can still be checked.
+        Some(recorded) if is_offline() || options.no_fetch => recorded,
+        _ if options.no_fetch => {
+            return Err(anyhow::format_err!(
+                "there is no manifest to compare against without fetching the artifact"
+            ));
+        }
+        recorded => {
+            ensure_online(artifact_location, dotslash_file)?;
+            let mut expected = expected_manifest_from_providers(
+                artifact_entry,
+                artifact_location,
+                provider_factory,
+            )?;
+            // Record the hashes so that later runs do not have to fetch the
+            // artifact again. Like in `download_artifact`, the manifest is
+            // advisory, so failing to write it is not an error.
+            if let Some(recorded) = recorded {
+                expected.origin = recorded.origin;
+                let _ = expected.write(&artifact_location.manifest_path);
+            }
+            expected
+        }
+    };
+    // Without hashes to compare against, hashing the files would be wasted.
+    let is_metadata_only = !expected.has_hashes();
+    let actual = if is_metadata_only {
+        ArtifactManifest::from_tree_metadata(&artifact_location.artifact_directory)
+    } else {
+        ArtifactManifest::from_tree(&artifact_location.artifact_directory)
+    }
+    .context("failed to read cached artifact")?;
+
+    let differences = expected.diff(&actual);
+    if differences.is_empty() {
+        return Ok(if is_metadata_only {
+            VerifyStatus::Unverified
+        } else {
+            VerifyStatus::Ok
+        });
+    }
+    if !options.repair {
+        return Ok(VerifyStatus::Drift(differences));
+    }
+
+    // Check before removing anything so that the drifted artifact is kept
+    // rather than leaving nothing in the cache.
+    ensure_online(artifact_location, dotslash_file)?;
+    let removed = dotslash_cache::remove_artifact(
+        &artifact_location.artifact_directory,
+        &artifact_location.lock_path,
+        &artifact_location.manifest_path,
+    )?;
+    if !removed {
+        return Err(anyhow::format_err!(
+            "cannot repair `{}` because it is in use by another DotSlash process",
+            artifact_location.artifact_directory.display(),
+        ));
+    }
+    download_artifact(
+        artifact_entry,
+        artifact_location,
+        dotslash_file,
+        provider_factory,
+    )
+    .context("failed to fetch artifact for repair")?;
+
+    Ok(VerifyStatus::Repaired(differences))
+}
+
+/// Fetches and unpacks the artifact exactly as `download_artifact` would,
+/// but into a scratch directory, and returns the manifest of the result.
+fn expected_manifest_from_providers<P: ProviderFactory>(
+    artifact_entry: &ArtifactEntry,
+    artifact_location: &ArtifactLocation,
+    provider_factory: &P,
+) -> anyhow::Result<ArtifactManifest> {
+    let artifact_parent_dir = artifact_location
+        .artifact_directory
+        .parent()
+        .context("artifact directory has no parent")?;
+    let scratch_dir = fs_ctx::tempdir_in(artifact_parent_dir)?;
+    let unpack_dir = scratch_dir.path().join("unpacked");
+    fs_ctx::create_dir_all(&unpack_dir)?;
+
+    let result = (|| {
+        let file_lock = acquire_download_lock_for_artifact(artifact_location)
+            .context("failed to get artifact lock")?;
+        let (fetched_artifact, _provider_config) = fetch_verified_artifact(
+            artifact_entry,
+            scratch_dir.path(),
+            &file_lock,
+            provider_factory,
+        )?;
+        unpack_verified_artifact(
+            &fetched_artifact,
+            &unpack_dir,
+            artifact_entry.format,
+            artifact_entry.path.as_str(),
+        )?;
+        if artifact_entry.readonly {
+            util::make_tree_entries_read_only(&unpack_dir)?;
+        }
+        Ok(ArtifactManifest::from_tree(&unpack_dir)?)
+    })();
+
+    // The unpacked tree may be read-only.
+    let _ = util::make_tree_entries_writable(scratch_dir.path());
+    let _ = scratch_dir.close();
+
+    result
+}
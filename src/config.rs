@@ -11,6 +11,7 @@
 use std::collections::HashMap;
 
 use anyhow::Context as _;
+use jsonc_parser::ParseOptions;
 use serde::Deserialize;
 use serde::Serialize;
 use serde_json::Value;
@@ -21,26 +22,46 @@ use crate::digest::Digest;
 use crate::fetch_method::ArtifactFormat;
 
 /// A DotSlash file must start with exactly these bytes on the first line
-/// to be considered valid. Because a DotSlash file does not have a
-/// standard extension, this gives us a reliable way to identify
-/// all of the DotSlash files in the repo.
+/// to be considered valid.
+///
+/// Because a DotSlash file does not have a standard extension, this gives us
+/// a reliable way to identify all of the DotSlash files in the repo.
 pub const REQUIRED_HEADER: &str = "#!/usr/bin/env dotslash";
 
 #[derive(Deserialize, Debug)]
 #[cfg_attr(test, derive(PartialEq))]
 pub struct ConfigFile {
-    #[cfg_attr(not(test), expect(dead_code))]
     pub name: String,
     pub platforms: HashMap<String, ArtifactEntry>,
 }
 
+/// The fields of `ArtifactEntry`, in the order they are declared (which is
+/// also the order they are serialized in).
+pub const ARTIFACT_ENTRY_FIELDS: [&str; 10] = [
+    "size",
+    "hash",
+    "digest",
+    "format",
+    "path",
+    "providers",
+    "arg0",
+    "readonly",
+    "providers_order",
+    "additional_digests",
+];
+
+/// Deserializing checks that each digest is the right length for its hash,
+/// which `Digest` cannot do on its own.
 #[derive(Deserialize, Serialize, Debug)]
 #[cfg_attr(test, derive(PartialEq))]
+#[serde(remote = "Self")]
+#[serde(bound(serialize = "Format: Serialize + Default + PartialEq"))]
 pub struct ArtifactEntry<Format = ArtifactFormat> {
     pub size: u64,
     pub hash: HashAlgorithm,
     pub digest: Digest,
-    #[serde(default)]
+    /// Omitted for plain artifacts, which is also the only way to write one.
+    #[serde(default, skip_serializing_if = "is_default")]
     pub format: Format,
     pub path: ArtifactPath,
     pub providers: Vec<Value>,
@@ -50,6 +71,45 @@ pub struct ArtifactEntry<Format = ArtifactFormat> {
     pub readonly: bool,
     #[serde(default, skip_serializing_if = "is_default")]
     pub providers_order: ProvidersOrder,
+    /// Digests that the artifact must match in addition to `digest`. Unlike
+    /// `digest`, they are not part of the artifact's key in the cache, so
+    /// adding them to an existing entry does not move its artifact.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub additional_digests: Vec<HashDigest>,
+}
+
+impl<'de, Format: Deserialize<'de> + Default> Deserialize<'de> for ArtifactEntry<Format> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let artifact_entry = Self::deserialize(deserializer)?;
+        for (hash, digest) in artifact_entry.digests() {
+            digest.check_len(hash).map_err(serde::de::Error::custom)?;
+        }
+        Ok(artifact_entry)
+    }
+}
+
+impl<Format: Serialize + Default + PartialEq> Serialize for ArtifactEntry<Format> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Self::serialize(self, serializer)
+    }
+}
+
+impl<Format> ArtifactEntry<Format> {
+    /// `(hash, digest)` followed by `additional_digests`.
+    pub fn digests(&self) -> impl Iterator<Item = (HashAlgorithm, &Digest)> {
+        std::iter::once((self.hash, &self.digest)).chain(
+            self.additional_digests
+                .iter()
+                .map(|hash_digest| (hash_digest.hash, &hash_digest.digest)),
+        )
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct HashDigest {
+    pub hash: HashAlgorithm,
+    pub digest: Digest,
 }
 
 fn is_default<T>(t: &T) -> bool
@@ -108,6 +168,30 @@ pub enum HashAlgorithm {
     Blake3,
     #[serde(rename = "sha256")]
     Sha256,
+    #[serde(rename = "sha512")]
+    Sha512,
+}
+
+impl HashAlgorithm {
+    pub const ALL: [Self; 3] = [Self::Blake3, Self::Sha256, Self::Sha512];
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Blake3 => "blake3",
+            Self::Sha256 => "sha256",
+            Self::Sha512 => "sha512",
+        }
+    }
+
+    /// The length of a digest as a hex string.
+    #[must_use]
+    pub fn digest_len(self) -> usize {
+        match self {
+            Self::Blake3 | Self::Sha256 => 64,
+            Self::Sha512 => 128,
+        }
+    }
 }
 
 /// Returned when `parse_file` fails to deserialize the JSON into a
@@ -124,6 +208,17 @@ pub struct IncompatibleDotslashBinaryError {
     pub source: serde_json::Error,
 }
 
+/// `data`, which must start with `REQUIRED_HEADER`, with the header replaced
+/// by spaces. Unlike stripping the header, this keeps offsets into the
+/// syntax tree of the result valid for `data`.
+pub fn blank_header(data: &str) -> String {
+    format!(
+        "{}{}",
+        " ".repeat(REQUIRED_HEADER.len()),
+        &data[REQUIRED_HEADER.len()..],
+    )
+}
+
 pub fn parse_file(data: &str) -> anyhow::Result<(Value, ConfigFile)> {
     // Check to see whether the DotSlash file starts with the proper shebang.
     let data = data
@@ -136,7 +231,7 @@ pub fn parse_file(data: &str) -> anyhow::Result<(Value, ConfigFile)> {
             anyhow::format_err!("DotSlash file must start with `{REQUIRED_HEADER}`")
         })?;
 
-    let value = jsonc_parser::parse_to_serde_value(data, &Default::default())?
+    let value = jsonc_parser::parse_to_serde_value(data, &ParseOptions::default())?
         .with_context(|| anyhow::format_err!("Failed to parse JSON"))?;
     let config_file = ConfigFile::deserialize(&value).map_err(|err| {
         if value.get("oncall").is_some() {
@@ -202,6 +297,7 @@ mod tests {
                         arg0: Arg0::DotslashFile,
                         providers_order: ProvidersOrder::Sequential,
                         readonly: true,
+                        additional_digests: vec![],
                     }
                 )]
                 .into(),
@@ -312,6 +408,7 @@ mod tests {
                         arg0: Arg0::DotslashFile,
                         providers_order: ProvidersOrder::Sequential,
                         readonly: true,
+                        additional_digests: vec![],
                     }
                 )]
                 .into(),
@@ -319,6 +416,68 @@ mod tests {
         );
     }
 
+    #[test]
+    fn additional_digests() {
+        let dotslash = r#"#!/usr/bin/env dotslash
+        {
+            "name": "my_tool",
+            "platforms": {
+                "linux-x86_64": {
+                    "size": 123,
+                    "hash": "sha256",
+                    "digest": "7f83b1657ff1fc53b92dc18148a1d65dfc2d4b1fa3d677284addd200126d9069",
+                    "additional_digests": [
+                        {
+                            "hash": "sha512",
+                            "digest": "DIGEST"
+                        }
+                    ],
+                    "path": "my_tool",
+                    "providers": [],
+                },
+            },
+        }
+        "#;
+        let sha512 = "ab".repeat(64);
+        let config_file = parse_file_string(&dotslash.replace("DIGEST", &sha512)).unwrap();
+        let digests = config_file.platforms["linux-x86_64"]
+            .digests()
+            .map(|(hash, digest)| (hash.as_str(), digest.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            digests,
+            [
+                (
+                    "sha256",
+                    "7f83b1657ff1fc53b92dc18148a1d65dfc2d4b1fa3d677284addd200126d9069",
+                ),
+                ("sha512", sha512.as_str()),
+            ],
+        );
+
+        // Each digest must be the right length for its hash.
+        let sha256 = "ab".repeat(32);
+        let err = parse_file_string(&dotslash.replace("DIGEST", &sha256)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("`{sha256}` is not a sha512 digest (expected 128 hex characters)"),
+        );
+
+        // Also when an entry is deserialized on its own.
+        let err = serde_json::from_value::<ArtifactEntry>(serde_json::json!({
+            "size": 123,
+            "hash": "sha512",
+            "digest": sha256,
+            "path": "my_tool",
+            "providers": [],
+        }))
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("`{sha256}` is not a sha512 digest (expected 128 hex characters)"),
+        );
+    }
+
     #[test]
     fn header_must_be_present() {
         let dotslash = r#"
@@ -333,4 +492,31 @@ mod tests {
             Err("DotSlash file must start with `#!/usr/bin/env dotslash`".to_owned()),
         );
     }
+
+    #[test]
+    fn artifact_entry_fields_match_struct() -> anyhow::Result<()> {
+        // Every field is set to a non-default value so that it is serialized.
+        let entry = ArtifactEntry {
+            size: 1,
+            hash: HashAlgorithm::Blake3,
+            digest: "a".repeat(64).try_into()?,
+            format: ArtifactFormat::Tar,
+            path: "tool".parse()?,
+            providers: vec![],
+            arg0: Arg0::UnderlyingExecutable,
+            readonly: false,
+            providers_order: ProvidersOrder::WeightedRandom,
+            additional_digests: vec![HashDigest {
+                hash: HashAlgorithm::Sha256,
+                digest: "b".repeat(64).try_into()?,
+            }],
+        };
+        let Value::Object(fields) = serde_json::to_value(&entry)? else {
+            panic!("not an object");
+        };
+        let mut expected = ARTIFACT_ENTRY_FIELDS.to_vec();
+        expected.sort_unstable();
+        assert_eq!(fields.keys().collect::<Vec<_>>(), expected);
+        Ok(())
+    }
 }
@@ -0,0 +1,389 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! Generates a complete DotSlash file from the assets of a GitHub release:
+//! each asset is matched to a platform by name, then downloaded so that its
+//! size, digest, format and executable can be filled in.
+
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::io;
+use std::io::IsTerminal as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+
+use anyhow::Context as _;
+use regex_lite::Regex;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::config::Arg0;
+use crate::config::ArtifactEntry;
+use crate::config::HashAlgorithm;
+use crate::config::ProvidersOrder;
+use crate::config::REQUIRED_HEADER;
+use crate::curl::CurlCommand;
+use crate::curl::FetchContext;
+use crate::inspect_artifact::guess_path;
+use crate::inspect_artifact::sniff_format;
+use crate::util::CommandDisplay;
+use crate::util::CommandStderrDisplay;
+use crate::util::fs_ctx;
+
+/// For each platform, the spellings that release assets commonly use for
+/// its OS and its architecture.
+const PLATFORM_SPELLINGS: [(&str, &str, &str); 7] = [
+    ("linux-aarch64", "linux", "aarch64|arm64"),
+    ("linux-riscv64", "linux", "riscv64"),
+    ("linux-x86_64", "linux", "x86_64|x86-64|amd64|x64"),
+    ("macos-aarch64", "macos|darwin|apple|osx", "aarch64|arm64"),
+    (
+        "macos-x86_64",
+        "macos|darwin|apple|osx",
+        "x86_64|x86-64|amd64|x64",
+    ),
+    ("windows-aarch64", "windows", "aarch64|arm64"),
+    ("windows-x86_64", "windows", "x86_64|x86-64|amd64|x64"),
+];
+
+/// Assets that accompany the artifacts of a release, e.g., checksums and
+/// installers, rather than being one.
+const IGNORED_SUFFIXES: &[&str] = &[
+    ".asc",
+    ".deb",
+    ".dmg",
+    ".json",
+    ".jsonl",
+    ".md5",
+    ".msi",
+    ".pem",
+    ".pkg",
+    ".rpm",
+    ".sbom",
+    ".sha256",
+    ".sha256sum",
+    ".sha512",
+    ".sha512sum",
+    ".sig",
+    ".txt",
+];
+
+pub struct ReleaseOptions {
+    /// `OWNER/REPO`.
+    pub repo: String,
+    pub tag: String,
+    /// The `name` of the DotSlash file. Defaults to the name of the repo.
+    pub name: Option<String>,
+    /// Read the release from this file rather than asking `gh`.
+    pub release_json: Option<PathBuf>,
+    /// `(platform, regex)` pairs that replace (or add to) the default
+    /// patterns for matching asset names to platforms.
+    pub platform_patterns: Vec<(String, String)>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Release {
+    assets: Vec<ReleaseAsset>,
+}
+
+/// `gh release view --json assets` and the GitHub REST API both list the
+/// assets of a release, but in the REST API, `url` is an API endpoint and the
+/// download URL is `browser_download_url`.
+#[derive(Deserialize, Debug)]
+struct ReleaseAsset {
+    name: String,
+    url: Option<String>,
+    browser_download_url: Option<String>,
+}
+
+impl ReleaseAsset {
+    fn download_url(&self) -> Option<&str> {
+        self.browser_download_url.as_deref().or(self.url.as_deref())
+    }
+}
+
+#[derive(Serialize)]
+struct DotslashFileData<'a> {
+    name: &'a str,
+    platforms: BTreeMap<&'a str, ArtifactEntry>,
+}
+
+/// Returns the contents of the DotSlash file. Platforms without an asset are
+/// reported on stderr and left out.
+pub fn create_from_release(options: &ReleaseOptions) -> anyhow::Result<String> {
+    let release = match &options.release_json {
+        Some(path) => serde_json::from_str::<Release>(&fs_ctx::read_to_string(path)?)
+            .with_context(|| format!("failed to parse `{}`", path.display()))?,
+        None => view_release(&options.repo, &options.tag)?,
+    };
+    let name = match &options.name {
+        Some(name) => name.as_str(),
+        None => options
+            .repo
+            .rsplit('/')
+            .next()
+            .unwrap_or(options.repo.as_str()),
+    };
+
+    let temp_dir = tempfile::tempdir()?;
+    let mut platforms = BTreeMap::new();
+    for (platform, pattern) in platform_patterns(&options.platform_patterns)? {
+        let Some(asset) = match_asset(platform, &pattern, &release.assets)? else {
+            eprintln!("No asset matches `{platform}`");
+            continue;
+        };
+        let url = asset
+            .download_url()
+            .with_context(|| format!("asset `{}` has no download URL", asset.name))?;
+
+        let artifact = temp_dir.path().join(platform);
+        download(url, &artifact)?;
+        let mut file = fs_ctx::file_open(&artifact)?;
+        let mut hasher = blake3::Hasher::new();
+        let size = io::copy(&mut file, &mut hasher)?;
+
+        let exe_name = if platform.starts_with("windows") {
+            format!("{name}.exe")
+        } else {
+            name.to_owned()
+        };
+        let (format, path) = sniff_format(&artifact)
+            .and_then(|format| Ok((format, guess_path(&artifact, format, &exe_name)?)))
+            .with_context(|| format!("failed to inspect `{}`", asset.name))?;
+        eprintln!("{platform}: `{}` ({path})", asset.name);
+
+        platforms.insert(
+            platform,
+            ArtifactEntry {
+                size,
+                hash: HashAlgorithm::Blake3,
+                digest: format!("{}", hasher.finalize().to_hex()).try_into()?,
+                format,
+                path: path.parse()?,
+                providers: vec![
+                    serde_json::json!({"url": url}),
+                    serde_json::json!({
+                        "type": "github-release",
+                        "repo": options.repo,
+                        "tag": options.tag,
+                        "name": asset.name,
+                    }),
+                ],
+                arg0: Arg0::default(),
+                readonly: true,
+                providers_order: ProvidersOrder::Sequential,
+                additional_digests: vec![],
+            },
+        );
+    }
+    if platforms.is_empty() {
+        return Err(anyhow::format_err!(
+            "no asset of `{}` release `{}` matches any platform",
+            options.repo,
+            options.tag,
+        ));
+    }
+
+    let json = serde_json::to_string_pretty(&DotslashFileData { name, platforms })?;
+    Ok(format!("{REQUIRED_HEADER}\n\n{json}\n"))
+}
+
+fn view_release(repo: &str, tag: &str) -> anyhow::Result<Release> {
+    let mut command = Command::new("gh");
+    command
+        .arg("release")
+        .arg("view")
+        .arg(tag)
+        .arg("--repo")
+        .arg(repo)
+        .arg("--json")
+        .arg("assets");
+    let output = command
+        .output()
+        .with_context(|| format!("{}", CommandDisplay::new(&command)))
+        .context("failed to run the GitHub CLI")?;
+    if !output.status.success() {
+        return Err(anyhow::format_err!(
+            "{}",
+            CommandStderrDisplay::new(&output)
+        ))
+        .with_context(|| format!("{}", CommandDisplay::new(&command)))
+        .context("the GitHub CLI failed");
+    }
+    serde_json::from_slice(&output.stdout).context("failed to parse the output of the GitHub CLI")
+}
+
+/// The default patterns with `overrides` applied, in platform order.
+fn platform_patterns(overrides: &[(String, String)]) -> anyhow::Result<Vec<(&str, Regex)>> {
+    let mut patterns = PLATFORM_SPELLINGS
+        .iter()
+        .map(|(platform, os, arch)| {
+            (
+                *platform,
+                format!("(?i)(?:{os}).*(?:{arch})|(?:{arch}).*(?:{os})"),
+            )
+        })
+        .collect::<BTreeMap<_, _>>();
+    for (platform, pattern) in overrides {
+        patterns.insert(platform.as_str(), pattern.clone());
+    }
+    patterns
+        .into_iter()
+        .map(|(platform, pattern)| {
+            let regex = Regex::new(&pattern)
+                .with_context(|| format!("invalid pattern for `{platform}`: `{pattern}`"))?;
+            Ok((platform, regex))
+        })
+        .collect()
+}
+
+fn match_asset<'a>(
+    platform: &str,
+    pattern: &Regex,
+    assets: &'a [ReleaseAsset],
+) -> anyhow::Result<Option<&'a ReleaseAsset>> {
+    let matches = assets
+        .iter()
+        .filter(|asset| {
+            !IGNORED_SUFFIXES
+                .iter()
+                .any(|suffix| asset.name.ends_with(suffix))
+                && pattern.is_match(&asset.name)
+        })
+        .collect::<Vec<_>>();
+    match matches.as_slice() {
+        [] => Ok(None),
+        [asset] => Ok(Some(asset)),
+        _ => Err(anyhow::format_err!(
+            "more than one asset matches `{platform}`: {}\n\
+             pass `--platform {platform}=REGEX` to choose one",
+            matches
+                .iter()
+                .map(|asset| format!("`{}`", asset.name))
+                .collect::<Vec<_>>()
+                .join(", "),
+        )),
+    }
+}
+
+fn download(url: &str, destination: &Path) -> anyhow::Result<()> {
+    let fetch_context = FetchContext {
+        artifact_name: url,
+        content_length: 0,
+        show_progress: io::stderr().is_terminal(),
+    };
+    CurlCommand::new(OsStr::new(url))
+        .get_request(destination, &fetch_context)
+        .with_context(|| format!("failed to fetch `{url}`"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assets(names: &[&str]) -> Vec<ReleaseAsset> {
+        names
+            .iter()
+            .map(|name| ReleaseAsset {
+                name: (*name).to_owned(),
+                url: None,
+                browser_download_url: None,
+            })
+            .collect()
+    }
+
+    fn matches(patterns: &[(&str, Regex)], assets: &[ReleaseAsset]) -> Vec<(String, String)> {
+        patterns
+            .iter()
+            .filter_map(|(platform, pattern)| {
+                match_asset(platform, pattern, assets)
+                    .unwrap()
+                    .map(|asset| ((*platform).to_owned(), asset.name.clone()))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_common_asset_names() -> anyhow::Result<()> {
+        let assets = assets(&[
+            "ripgrep-14.1.1-aarch64-apple-darwin.tar.gz",
+            "ripgrep-14.1.1-aarch64-apple-darwin.tar.gz.sha256",
+            "ripgrep-14.1.1-aarch64-unknown-linux-gnu.tar.gz",
+            "ripgrep-14.1.1-x86_64-apple-darwin.tar.gz",
+            "ripgrep-14.1.1-x86_64-pc-windows-msvc.zip",
+            "ripgrep-14.1.1-x86_64-unknown-linux-musl.tar.gz",
+            "ripgrep_14.1.1-1_amd64.deb",
+            "tool-Linux-arm64",
+        ]);
+        assert_eq!(
+            matches(&platform_patterns(&[])?, &assets[..7]),
+            [
+                (
+                    "linux-aarch64",
+                    "ripgrep-14.1.1-aarch64-unknown-linux-gnu.tar.gz"
+                ),
+                (
+                    "linux-x86_64",
+                    "ripgrep-14.1.1-x86_64-unknown-linux-musl.tar.gz"
+                ),
+                (
+                    "macos-aarch64",
+                    "ripgrep-14.1.1-aarch64-apple-darwin.tar.gz"
+                ),
+                ("macos-x86_64", "ripgrep-14.1.1-x86_64-apple-darwin.tar.gz"),
+                (
+                    "windows-x86_64",
+                    "ripgrep-14.1.1-x86_64-pc-windows-msvc.zip"
+                ),
+            ]
+            .map(|(platform, name)| (platform.to_owned(), name.to_owned())),
+        );
+
+        // Matching ignores case, and more than one match is an error.
+        let patterns = platform_patterns(&[])?;
+        let (platform, pattern) = &patterns[0];
+        assert_eq!(*platform, "linux-aarch64");
+        let err = match_asset(platform, pattern, &assets).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "more than one asset matches `linux-aarch64`: \
+             `ripgrep-14.1.1-aarch64-unknown-linux-gnu.tar.gz`, `tool-Linux-arm64`\n\
+             pass `--platform linux-aarch64=REGEX` to choose one",
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn overrides_replace_default_patterns() -> anyhow::Result<()> {
+        let assets = assets(&["tool-linux-gnu-x64.tar.gz", "tool-linux-musl-x64.tar.gz"]);
+        let overrides = [
+            ("linux-x86_64".to_owned(), "linux-musl-x64".to_owned()),
+            ("freebsd-x86_64".to_owned(), "freebsd".to_owned()),
+        ];
+        let patterns = platform_patterns(&overrides)?;
+        assert_eq!(
+            matches(&patterns, &assets),
+            [(
+                "linux-x86_64".to_owned(),
+                "tool-linux-musl-x64.tar.gz".to_owned()
+            )],
+        );
+        assert!(
+            patterns
+                .iter()
+                .any(|(platform, _)| *platform == "freebsd-x86_64")
+        );
+
+        let err = platform_patterns(&[("linux-x86_64".to_owned(), "(".to_owned())]).unwrap_err();
+        assert_eq!(err.to_string(), "invalid pattern for `linux-x86_64`: `(`");
+        Ok(())
+    }
+}
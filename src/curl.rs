@@ -12,32 +12,43 @@ use std::error::Error as StdError;
 use std::ffi::OsStr;
 use std::fmt;
 use std::io;
+use std::io::Read;
 use std::path::Path;
+use std::process::Child;
+use std::process::ChildStdout;
 use std::process::Command;
 use std::process::Output;
+use std::process::Stdio;
 use std::thread;
 use std::time::Duration;
 
 use thiserror::Error;
 
+use crate::global_config::GlobalConfig;
+#[cfg(feature = "native-http")]
+use crate::native_http;
+#[cfg(feature = "native-http")]
+use crate::native_http::NativeHttpError;
+use crate::policy;
 use crate::util;
 use crate::util::CommandDisplay;
 use crate::util::CommandStderrDisplay;
 use crate::util::HttpStatus;
+use crate::util::fs_ctx;
 
 const NUM_RETRYABLE_CURL_MAX_ATTEMPTS: u8 = 3;
 const NUM_TRANSIENT_ERROR_CURL_MAX_ATTEMPTS: u64 = 3;
 
 /// Environment variable to set the maximum time for curl operations (in seconds).
-const DOTSLASH_CURL_TIMEOUT_SEC_ENV: &str = "DOTSLASH_CURL_TIMEOUT_SEC";
+pub const DOTSLASH_CURL_TIMEOUT_SEC_ENV: &str = "DOTSLASH_CURL_TIMEOUT_SEC";
 
 /// Environment variable to set the connection timeout for curl operations (in seconds).
-const DOTSLASH_CURL_CONNECT_TIMEOUT_SEC_ENV: &str = "DOTSLASH_CURL_CONNECT_TIMEOUT_SEC";
+pub const DOTSLASH_CURL_CONNECT_TIMEOUT_SEC_ENV: &str = "DOTSLASH_CURL_CONNECT_TIMEOUT_SEC";
 
 /// Specify a custom user-agent when making requests. In the unfortunate event
 /// that a site hosting an artifact gets overloaded with requests, hopefully
 /// this will help them identify whether DotSlash is involed.
-const USER_AGENT: &str = concat!(
+pub const USER_AGENT: &str = concat!(
     "Mozilla/5.0 (compatible; DotSlash/",
     env!("CARGO_PKG_VERSION"),
     "; +",
@@ -58,6 +69,11 @@ const CURL_RETRYABLE_EXIT_CODES: &[i32] = &[
     92, // CURLE_HTTP2_STREAM
 ];
 
+// The server does not support (or no longer agrees with) resuming from the
+// partial file, so the download has to start over.
+// https://curl.haxx.se/docs/manpage.html#33
+const CURL_RANGE_ERROR_EXIT_CODE: i32 = 33;
+
 // HTTP page not retrieved. The requested url was not found or returned
 // another error with the HTTP error code being 400 or above.
 // This return code only appears if -f, --fail is used.
@@ -72,6 +88,8 @@ const CURL_HTTP_RETURNED_ERROR_EXIT_CODES: &[i32] = &[
 enum CurlRequestType<'a> {
     /// String is the argument to use with --output.
     Get(&'a str),
+    /// Write the response body to stdout.
+    Stream,
 }
 
 pub struct FetchContext<'a> {
@@ -94,6 +112,16 @@ pub enum CurlError {
     #[error("`{0}`")]
     HttpStatus(DebugCommand, #[source] HttpStatus),
 
+    // The request was redirected to a URL that a policy does not allow. The
+    // string is the reason.
+    #[error("`{0}` was redirected to a URL that is not allowed: {1}")]
+    RedirectRejected(DebugCommand, String),
+
+    // The in-process HTTP client failed. See `native_http`.
+    #[cfg(feature = "native-http")]
+    #[error("`GET {0}`")]
+    Native(String, #[source] NativeHttpError),
+
     // DotSlash failed managing the thread responsible for displaying a progress
     // indicator.
     #[error("progress indicator thread panicked with `{0}`")]
@@ -103,6 +131,14 @@ pub enum CurlError {
 #[derive(Debug)]
 pub struct CurlExit(Output);
 
+/// The response body from `CurlCommand::get_stream`.
+struct CurlStream {
+    command: Command,
+    child: Child,
+    stdout: ChildStdout,
+    finished: bool,
+}
+
 #[derive(Debug)]
 pub struct CurlCommand<'a> {
     url: &'a OsStr,
@@ -110,13 +146,13 @@ pub struct CurlCommand<'a> {
 }
 
 #[derive(Debug)]
-pub struct DebugCommand(Command);
+pub struct DebugCommand(Box<Command>);
 
 impl From<&Command> for DebugCommand {
     fn from(command: &Command) -> DebugCommand {
         let mut clone = Command::new(command.get_program());
         clone.args(command.get_args());
-        DebugCommand(clone)
+        DebugCommand(Box::new(clone))
     }
 }
 
@@ -163,15 +199,34 @@ impl CurlError {
     }
 
     fn is_retryable(&self) -> bool {
+        if self.is_resume_failure() {
+            return true;
+        }
         if let Self::CurlExit(_, source) = self {
             if let Some(exit_code) = source.0.status.code() {
                 return CURL_RETRYABLE_EXIT_CODES.contains(&exit_code);
             }
         }
+        #[cfg(feature = "native-http")]
+        if let Self::Native(_, source) = self {
+            return source.is_retryable();
+        }
         false
     }
 
+    fn is_resume_failure(&self) -> bool {
+        match self {
+            Self::CurlExit(_, source) => source.0.status.code() == Some(CURL_RANGE_ERROR_EXIT_CODE),
+            Self::HttpStatus(_, HttpStatus::RequestedRangeNotSatisfiable) => true,
+            _ => false,
+        }
+    }
+
     fn is_too_many_requests(&self) -> bool {
+        #[cfg(feature = "native-http")]
+        if let Self::Native(_, NativeHttpError::HttpStatus(HttpStatus::TooManyRequests)) = self {
+            return true;
+        }
         matches!(self, Self::HttpStatus(_, HttpStatus::TooManyRequests))
     }
 }
@@ -204,7 +259,28 @@ impl CurlCommand<'_> {
 
         // If the request fails, the `progress_sender` channel is dropped,
         // and the progress thread uses this to finish by itself.
-        self.make_request(&mut self.curl_command(self.url, &CurlRequestType::Get(output_arg)))?;
+        // `target` is kept between attempts so that each one resumes where the
+        // previous one left off.
+        let mut curl_command = self.curl_command(self.url, &CurlRequestType::Get(output_arg));
+        make_request(|| {
+            #[cfg(feature = "native-http")]
+            if let Some(url) = native_http::handled_url(self.url) {
+                return native_http::get(url, target, self.retry)
+                    .map_err(|e| CurlError::Native(url.to_owned(), e));
+            }
+            let result = run_curl(&mut curl_command);
+            if result.as_ref().is_err_and(CurlError::is_resume_failure) {
+                // Start over on the next attempt.
+                let _ = fs_ctx::remove_file(target);
+            }
+            // `--write-out` prints where the artifact was fetched from in the
+            // end, so that a redirect cannot get around the policies.
+            let final_url = String::from_utf8_lossy(&result?.stdout).into_owned();
+            policy::check_redirect(&GlobalConfig::get().policies, &final_url).map_err(|reason| {
+                let _ = fs_ctx::remove_file(target);
+                CurlError::RedirectRejected((&curl_command).into(), reason)
+            })
+        })?;
 
         if let Some((progress_sender, join_handler)) = handler {
             // Let the progress thread know that we're done done.
@@ -219,42 +295,53 @@ impl CurlCommand<'_> {
         Ok(())
     }
 
-    #[expect(clippy::unused_self)]
-    fn make_request(&self, curl_command: &mut Command) -> Result<Vec<u8>, CurlError> {
-        let mut retries = 1..=NUM_RETRYABLE_CURL_MAX_ATTEMPTS;
-        loop {
-            let output = match curl_command.output() {
-                Ok(output) => output,
-                // If curl failed to execute, exit immediately.
-                Err(e) => return Err(CurlError::from_command_error(curl_command, e)),
-            };
-
-            if output.status.success() {
-                // curl completed successfully!
-                return Ok(output.stdout);
-            }
-
-            let curl_error = CurlError::from_command_output(curl_command, output);
-
-            // Sometimes we manually retry ourselves...
-            if let Some(retry_num) = retries.next() {
-                // curl failed, but it satisfies our "retryable" heuristic,
-                // so loop again.
-                if curl_error.is_retryable() {
-                    continue;
-                }
-
-                // curl failed, but we're hitting the server too hard,
-                // so loop again, but wait a little bit.
-                if curl_error.is_too_many_requests() {
-                    // 1^3=1s  ->  2^3=8s  ->  3^3=27s
-                    thread::sleep(Duration::from_secs(retry_num.pow(3) as u64));
-                    continue;
-                }
-            }
+    /// Opens the response body as a stream. Unlike `get_request`, nothing is
+    /// resumed, so callers should keep what they read and resume from there
+    /// with `get_request` if reading the stream fails.
+    ///
+    /// Returns `None` if the stream could not be checked against the
+    /// policies in the global config: `curl` only says where it was
+    /// redirected to once the whole body has been passed along.
+    pub fn get_stream(
+        &self,
+        context: &FetchContext<'_>,
+    ) -> Result<Option<Box<dyn Read>>, CurlError> {
+        let stream = self.open_stream()?;
+        if !context.show_progress {
+            return Ok(stream);
+        }
+        Ok(stream.map(|stream| {
+            eprintln!("Downloading {}...", context.artifact_name);
+            Box::new(util::ProgressReader::new(stream, context.content_length)) as Box<dyn Read>
+        }))
+    }
 
-            return Err(curl_error);
+    fn open_stream(&self) -> Result<Option<Box<dyn Read>>, CurlError> {
+        #[cfg(feature = "native-http")]
+        if let Some(url) = native_http::handled_url(self.url) {
+            return native_http::open(url)
+                .map(Some)
+                .map_err(|e| CurlError::Native(url.to_owned(), e));
         }
+        if policy::restrict_redirects(&GlobalConfig::get().policies) {
+            return Ok(None);
+        }
+
+        let mut command = self.curl_command(self.url, &CurlRequestType::Stream);
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let mut child = command
+            .spawn()
+            .map_err(|e| CurlError::from_command_error(&command, e))?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        Ok(Some(Box::new(CurlStream {
+            command,
+            child,
+            stdout,
+            finished: false,
+        })))
     }
 
     fn curl_command(&self, url: &OsStr, request_type: &CurlRequestType<'_>) -> Command {
@@ -269,20 +356,12 @@ impl CurlCommand<'_> {
         curl_command.arg("--location");
 
         // Set timeouts if specified via environment variables.
-        if let Some(max_time) = std::env::var(DOTSLASH_CURL_TIMEOUT_SEC_ENV)
-            .ok()
-            .and_then(|v| v.parse::<u64>().ok())
-            .filter(|&v| v > 0)
-        {
+        if let Some(max_time) = max_time() {
             curl_command.arg("--max-time");
             curl_command.arg(max_time.to_string());
         }
 
-        if let Some(connect_timeout) = std::env::var(DOTSLASH_CURL_CONNECT_TIMEOUT_SEC_ENV)
-            .ok()
-            .and_then(|v| v.parse::<u64>().ok())
-            .filter(|&v| v > 0)
-        {
+        if let Some(connect_timeout) = connect_timeout() {
             curl_command.arg("--connect-timeout");
             curl_command.arg(connect_timeout.to_string());
         }
@@ -325,13 +404,124 @@ impl CurlCommand<'_> {
         match request_type {
             CurlRequestType::Get(output) => {
                 curl_command.args(["--output", output]);
+                curl_command.args(["--write-out", "%{url_effective}"]);
+                // Resume from the end of `output`, if a previous attempt left
+                // a partial file behind. A missing file starts from zero.
+                curl_command.args(["--continue-at", "-"]);
             }
+            CurlRequestType::Stream => {}
         }
 
         curl_command
     }
 }
 
+impl CurlStream {
+    /// Waits for curl to exit, failing if it did not succeed.
+    fn finish(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        let status = self.child.wait()?;
+        if status.success() {
+            return Ok(());
+        }
+        let mut stderr = vec![];
+        if let Some(mut child_stderr) = self.child.stderr.take() {
+            child_stderr.read_to_end(&mut stderr)?;
+        }
+        let output = Output {
+            status,
+            stdout: vec![],
+            stderr,
+        };
+        Err(io::Error::other(CurlError::from_command_output(
+            &self.command,
+            output,
+        )))
+    }
+}
+
+impl Read for CurlStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.stdout.read(buf)?;
+        if n == 0 && !buf.is_empty() {
+            // The body is only complete if curl says so.
+            self.finish()?;
+        }
+        Ok(n)
+    }
+}
+
+impl Drop for CurlStream {
+    fn drop(&mut self) {
+        // The reader gave up part of the way through.
+        if !self.finished {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+/// Retries `request` when it fails in a way that satisfies our "retryable"
+/// heuristic, on top of any retries the HTTP client makes by itself.
+fn make_request<T>(mut request: impl FnMut() -> Result<T, CurlError>) -> Result<T, CurlError> {
+    let mut retries = 1..=NUM_RETRYABLE_CURL_MAX_ATTEMPTS;
+    loop {
+        let error = match request() {
+            Ok(output) => return Ok(output),
+            Err(error) => error,
+        };
+
+        // Sometimes we manually retry ourselves...
+        if let Some(retry_num) = retries.next() {
+            // The request failed, but it satisfies our "retryable" heuristic,
+            // so loop again.
+            if error.is_retryable() {
+                continue;
+            }
+
+            // The request failed, but we're hitting the server too hard,
+            // so loop again, but wait a little bit.
+            if error.is_too_many_requests() {
+                // 1^3=1s  ->  2^3=8s  ->  3^3=27s
+                thread::sleep(Duration::from_secs(retry_num.pow(3) as u64));
+                continue;
+            }
+        }
+
+        return Err(error);
+    }
+}
+
+fn run_curl(curl_command: &mut Command) -> Result<Output, CurlError> {
+    let output = match curl_command.output() {
+        Ok(output) => output,
+        // If curl failed to execute, exit immediately.
+        Err(e) => return Err(CurlError::from_command_error(curl_command, e)),
+    };
+
+    if output.status.success() {
+        // curl completed successfully!
+        return Ok(output);
+    }
+
+    Err(CurlError::from_command_output(curl_command, output))
+}
+
+/// The maximum time for the whole transfer, from `DOTSLASH_CURL_TIMEOUT_SEC`
+/// or the global config.
+pub fn max_time() -> Option<u64> {
+    GlobalConfig::get().timeout_sec.value
+}
+
+/// The maximum time to connect, from `DOTSLASH_CURL_CONNECT_TIMEOUT_SEC` or
+/// the global config.
+pub fn connect_timeout() -> Option<u64> {
+    GlobalConfig::get().connect_timeout_sec.value
+}
+
 fn parse_http_returned_error(stderr: &[u8]) -> Option<usize> {
     // "The requested URL returned error: %d\n" (or \r\n on Windows)
     // https://github.com/curl/curl/blob/eab2f95c0de9/lib/http.c#L627-L630
@@ -407,4 +597,102 @@ mod tests {
             USER_AGENT
         );
     }
+
+    // With `native-http`, `get_request` does not use curl for HTTP URLs, and
+    // `native_http` has its own tests.
+    #[cfg(not(feature = "native-http"))]
+    mod resume {
+        use std::fs;
+        use std::io::BufRead as _;
+        use std::io::BufReader;
+        use std::io::Write as _;
+        use std::net::TcpListener;
+        use std::sync::Arc;
+        use std::sync::Mutex;
+
+        use super::*;
+
+        /// Serves one canned response per connection, in order, and records the
+        /// `Range` header (if any) of each request.
+        fn serve(
+            responses: Vec<&'static str>,
+        ) -> anyhow::Result<(String, Arc<Mutex<Vec<String>>>)> {
+            let listener = TcpListener::bind("127.0.0.1:0")?;
+            let url = format!("http://{}/artifact", listener.local_addr()?);
+            let ranges = Arc::new(Mutex::new(vec![]));
+            let recorded_ranges = Arc::clone(&ranges);
+            thread::spawn(move || {
+                for response in responses {
+                    let (stream, _) = listener.accept().unwrap();
+                    let mut reader = BufReader::new(&stream);
+                    let mut range = String::new();
+                    let mut line = String::new();
+                    while reader.read_line(&mut line).unwrap() > 2 {
+                        if let Some((name, value)) = line.split_once(':') {
+                            if name.eq_ignore_ascii_case("range") {
+                                value.trim().clone_into(&mut range);
+                            }
+                        }
+                        line.clear();
+                    }
+                    recorded_ranges.lock().unwrap().push(range);
+                    (&stream).write_all(response.as_bytes()).unwrap();
+                }
+            });
+            Ok((url, ranges))
+        }
+
+        fn get(url: &str, target: &Path) -> Result<(), CurlError> {
+            let context = FetchContext {
+                artifact_name: "artifact",
+                content_length: 5,
+                show_progress: false,
+            };
+            CurlCommand::new(OsStr::new(url)).get_request(target, &context)
+        }
+
+        #[test]
+        fn resumes_partial_download() -> anyhow::Result<()> {
+            let temp_dir = tempfile::tempdir()?;
+            let target = temp_dir.path().join("artifact");
+            fs::write(&target, "hel")?;
+
+            let (url, ranges) = serve(vec![
+                "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 3-4/5\r\n\
+                 Content-Length: 2\r\nConnection: close\r\n\r\nlo",
+            ])?;
+            get(&url, &target)?;
+            assert_eq!(fs::read_to_string(&target)?, "hello");
+            assert_eq!(*ranges.lock().unwrap(), ["bytes=3-"]);
+            Ok(())
+        }
+
+        #[test]
+        fn starts_over_when_resume_fails() -> anyhow::Result<()> {
+            let temp_dir = tempfile::tempdir()?;
+            let target = temp_dir.path().join("artifact");
+            let full_response = "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\
+                                 Connection: close\r\n\r\nhello";
+
+            // The server ignores the range, so curl exits with 33.
+            fs::write(&target, "hel")?;
+            let (url, ranges) = serve(vec![full_response, full_response])?;
+            get(&url, &target)?;
+            assert_eq!(fs::read_to_string(&target)?, "hello");
+            assert_eq!(*ranges.lock().unwrap(), ["bytes=3-", ""]);
+
+            // The partial file is longer than the artifact, so the server
+            // responds with 416.
+            fs::write(&target, "hello!!")?;
+            let (url, ranges) = serve(vec![
+                "HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */5\r\n\
+                 Content-Length: 0\r\nConnection: close\r\n\r\n",
+                full_response,
+            ])?;
+            get(&url, &target)?;
+            assert_eq!(fs::read_to_string(&target)?, "hello");
+            assert_eq!(*ranges.lock().unwrap(), ["bytes=7-", ""]);
+            Ok(())
+        }
+    }
 }
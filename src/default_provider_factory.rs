@@ -8,6 +8,7 @@
  * above-listed licenses.
  */
 
+use crate::exec_provider::ExecProvider;
 use crate::gcs_provider::GcsProvider;
 use crate::github_release_provider::GitHubReleaseProvider;
 use crate::http_provider::HttpProvider;
@@ -21,6 +22,7 @@ impl ProviderFactory for DefaultProviderFactory {
     fn get_provider(&self, provider_type: &str) -> anyhow::Result<Box<dyn Provider>> {
         match provider_type {
             "http" => Ok(Box::new(HttpProvider {})),
+            "exec" => Ok(Box::new(ExecProvider {})),
             "gcs" => Ok(Box::new(GcsProvider {})),
             "github-release" => Ok(Box::new(GitHubReleaseProvider {})),
             "s3" => Ok(Box::new(S3Provider {})),
@@ -14,6 +14,8 @@ use serde::Deserialize;
 use serde::Serialize;
 use thiserror::Error;
 
+use crate::config::HashAlgorithm;
+
 #[derive(Debug, Error)]
 pub enum DigestError {
     #[error("invalid hash characters `{0}`")]
@@ -21,6 +23,9 @@ pub enum DigestError {
 
     #[error("invalid hash length `{0}`")]
     InvalidHashLength(String),
+
+    #[error("`{digest}` is not a {} digest (expected {} hex characters)", hash.as_str(), hash.digest_len())]
+    WrongHashLength { hash: HashAlgorithm, digest: String },
 }
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone)]
@@ -39,7 +44,10 @@ impl TryFrom<String> for Digest {
     fn try_from(hash: String) -> Result<Self, Self::Error> {
         if !hash.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f')) {
             Err(DigestError::InvalidHashCharacters(hash))
-        } else if hash.len() != 64 {
+        } else if !HashAlgorithm::ALL
+            .iter()
+            .any(|algorithm| algorithm.digest_len() == hash.len())
+        {
             Err(DigestError::InvalidHashLength(hash))
         } else {
             Ok(Digest(hash))
@@ -48,9 +56,23 @@ impl TryFrom<String> for Digest {
 }
 
 impl Digest {
+    #[must_use]
     pub fn as_str(&self) -> &str {
         &self.0
     }
+
+    /// A `Digest` on its own can be for any `HashAlgorithm`: fails unless it
+    /// is the right length for `hash`.
+    pub fn check_len(&self, hash: HashAlgorithm) -> Result<(), DigestError> {
+        if self.0.len() == hash.digest_len() {
+            Ok(())
+        } else {
+            Err(DigestError::WrongHashLength {
+                hash,
+                digest: self.0.clone(),
+            })
+        }
+    }
 }
 
 #[cfg(test)]
@@ -95,4 +117,27 @@ mod tests {
             "7f83b1657ff1fc53b92dc18148a1d65dfc2d4b1fa3d677284addd200126d9069",
         );
     }
+
+    #[test]
+    fn test_digest_check_len() {
+        let sha256 = Digest::try_from("ab".repeat(32)).unwrap();
+        let sha512 = Digest::try_from("ab".repeat(64)).unwrap();
+        assert!(sha256.check_len(HashAlgorithm::Blake3).is_ok());
+        assert!(sha256.check_len(HashAlgorithm::Sha256).is_ok());
+        assert!(sha512.check_len(HashAlgorithm::Sha512).is_ok());
+        assert_eq!(
+            sha256
+                .check_len(HashAlgorithm::Sha512)
+                .unwrap_err()
+                .to_string(),
+            format!(
+                "`{}` is not a sha512 digest (expected 128 hex characters)",
+                "ab".repeat(32),
+            ),
+        );
+        assert_matches!(
+            sha512.check_len(HashAlgorithm::Sha256),
+            Err(DigestError::WrongHashLength { .. })
+        );
+    }
 }
@@ -10,14 +10,18 @@
 
 use std::env;
 use std::ffi::OsString;
+use std::io;
 use std::path::Path;
 use std::path::PathBuf;
 
+use anyhow::Context as _;
 #[cfg(unix)]
 use nix::unistd;
 
-#[cfg(unix)]
+use crate::global_config::GlobalConfig;
 use crate::util;
+use crate::util::FileLock;
+use crate::util::fs_ctx;
 
 pub const DOTSLASH_CACHE_ENV: &str = "DOTSLASH_CACHE";
 
@@ -26,10 +30,26 @@ pub struct DotslashCache {
     cache_dir: PathBuf,
 }
 
+/// An artifact directory that was found on disk in the DotSlash cache.
+#[derive(Debug)]
+pub struct CachedArtifact {
+    /// The artifact hash as hex, i.e., the two hex digit parent folder name
+    /// followed by the artifact folder name.
+    pub key: String,
+    /// Same as `ArtifactLocation::artifact_directory`.
+    pub artifact_directory: PathBuf,
+    /// Same as `ArtifactLocation::lock_path`.
+    pub lock_path: PathBuf,
+    /// Same as `ArtifactLocation::manifest_path`.
+    pub manifest_path: PathBuf,
+}
+
 /// The DotSlash cache is organized as follows:
 /// - Any subfolder that starts with two lowercase hex digits is the parent
 ///   folder for artifacts whose *artifact hash* starts with those two hex
 ///   digits (see `ArtifactLocation::artifact_directory`).
+/// - Next to each artifact folder is a `.json` file with the same name that
+///   describes the folder's contents (see `ArtifactManifest`).
 /// - The only other subfolder is `locks/`, which internally is organized
 ///   to the root of the cache folder.
 ///
@@ -38,6 +58,10 @@ pub struct DotslashCache {
 /// The `locks/` folder is kept separate so it can be blown away independent of
 /// the artifacts.
 impl DotslashCache {
+    /// The cache that the `dotslash` binary uses, i.e., the one named by
+    /// `DOTSLASH_CACHE` or the global config, or else the platform's default.
+    /// Use `new_in` to pick the location explicitly.
+    #[must_use]
     pub fn new() -> Self {
         Self::new_in(get_dotslash_cache())
     }
@@ -48,18 +72,167 @@ impl DotslashCache {
         }
     }
 
+    #[must_use]
     pub fn cache_dir(&self) -> &Path {
         &self.cache_dir
     }
 
+    #[must_use]
     pub fn artifacts_dir(&self) -> &Path {
         &self.cache_dir
     }
 
     /// artifact_hash_prefix should be two lowercase hex digits.
+    #[must_use]
     pub fn locks_dir(&self, artifact_hash_prefix: &str) -> PathBuf {
         self.cache_dir.join("locks").join(artifact_hash_prefix)
     }
+
+    /// The sidecar manifest for an artifact lives next to its directory:
+    /// `ab/cdef...` is described by `ab/cdef....json`.
+    #[must_use]
+    pub fn manifest_path(&self, artifact_hash_prefix: &str, artifact_hash_rest: &str) -> PathBuf {
+        self.artifacts_dir()
+            .join(artifact_hash_prefix)
+            .join(format!("{artifact_hash_rest}.json"))
+    }
+
+    /// Lists the artifact directories currently in the cache. Entries that
+    /// do not look like artifact directories (e.g., the temp dirs used while
+    /// an artifact is being fetched) are skipped. A missing cache directory
+    /// is treated as an empty cache.
+    pub fn read_artifacts(&self) -> io::Result<Vec<CachedArtifact>> {
+        let mut artifacts = vec![];
+        for (prefix, prefix_dir) in self.read_prefix_dirs()? {
+            for entry in fs_ctx::read_dir(prefix_dir)? {
+                let entry = entry?;
+                let Some(rest) = entry
+                    .file_name()
+                    .to_str()
+                    .filter(|name| !name.is_empty() && is_lower_hex(name))
+                    .map(str::to_owned)
+                else {
+                    continue;
+                };
+                if !entry.file_type()?.is_dir() {
+                    continue;
+                }
+
+                artifacts.push(CachedArtifact {
+                    key: format!("{prefix}{rest}"),
+                    artifact_directory: entry.path(),
+                    lock_path: self.locks_dir(&prefix).join(&rest),
+                    manifest_path: self.manifest_path(&prefix, &rest),
+                });
+            }
+        }
+
+        artifacts.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(artifacts)
+    }
+
+    /// Lists the temp dirs and files next to the artifact directories, i.e.,
+    /// those of fetches and manifest updates that are in progress, or that
+    /// were left behind when a DotSlash process was killed.
+    pub fn read_temp_entries(&self) -> io::Result<Vec<PathBuf>> {
+        let mut temp_entries = vec![];
+        for (_prefix, prefix_dir) in self.read_prefix_dirs()? {
+            for entry in fs_ctx::read_dir(prefix_dir)? {
+                let entry = entry?;
+                if entry.file_name().to_string_lossy().starts_with(".tmp") {
+                    temp_entries.push(entry.path());
+                }
+            }
+        }
+
+        temp_entries.sort();
+        Ok(temp_entries)
+    }
+
+    /// The two hex digit folders that contain the artifact directories.
+    fn read_prefix_dirs(&self) -> io::Result<Vec<(String, PathBuf)>> {
+        let prefix_entries = match fs_ctx::read_dir(self.artifacts_dir()) {
+            Ok(entries) => entries,
+            Err(err) if util::is_not_found_error(&err) => return Ok(vec![]),
+            Err(err) => return Err(err),
+        };
+
+        let mut prefix_dirs = vec![];
+        for prefix_entry in prefix_entries {
+            let prefix_entry = prefix_entry?;
+            let Some(prefix) = prefix_entry
+                .file_name()
+                .to_str()
+                .filter(|name| name.len() == 2 && is_lower_hex(name))
+                .map(str::to_owned)
+            else {
+                continue;
+            };
+            if !prefix_entry.file_type()?.is_dir() {
+                continue;
+            }
+            prefix_dirs.push((prefix, prefix_entry.path()));
+        }
+
+        Ok(prefix_dirs)
+    }
+}
+
+/// Deletes an artifact directory, and its manifest, while holding the
+/// artifact's download lock. Returns `Ok(false)` without deleting anything if
+/// another process holds the lock.
+pub fn remove_artifact(
+    artifact_directory: &Path,
+    lock_path: &Path,
+    manifest_path: &Path,
+) -> anyhow::Result<bool> {
+    if let Some(lock_dir) = lock_path.parent() {
+        fs_ctx::create_dir_all(lock_dir)?;
+    }
+    let Some(_file_lock) = FileLock::try_acquire(lock_path)? else {
+        return Ok(false);
+    };
+
+    let parent = artifact_directory
+        .parent()
+        .context("artifact directory has no parent")?;
+
+    match fs_ctx::remove_file(manifest_path) {
+        Ok(()) => {}
+        Err(err) if util::is_not_found_error(&err) => {}
+        Err(err) => return Err(err.into()),
+    }
+
+    // The artifact directory itself is made read-only after it is moved into
+    // place, so it must be made writable before it can be moved again.
+    let mut perms = match fs_ctx::symlink_metadata(artifact_directory) {
+        Ok(metadata) => metadata.permissions(),
+        // Already removed, e.g., by a concurrent `gc`.
+        Err(err) if util::is_not_found_error(&err) => return Ok(true),
+        Err(err) => return Err(err.into()),
+    };
+    if perms.readonly() {
+        #[expect(clippy::permissions_set_readonly_false)]
+        perms.set_readonly(false);
+        fs_ctx::set_permissions(artifact_directory, perms)?;
+    }
+
+    // Move the artifact out of the way before deleting it so that a
+    // concurrent DotSlash invocation sees either the complete artifact or
+    // nothing at all (in which case it fetches it again), but never a
+    // partially deleted tree.
+    let trash_dir = fs_ctx::tempdir_in(parent)?;
+    fs_ctx::rename(artifact_directory, trash_dir.path().join("artifact"))?;
+    util::make_tree_entries_writable(trash_dir.path())?;
+    trash_dir
+        .close()
+        .context("failed to delete removed artifact")?;
+
+    Ok(true)
+}
+
+fn is_lower_hex(s: &str) -> bool {
+    s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
 }
 
 impl Default for DotslashCache {
@@ -69,13 +242,12 @@ impl Default for DotslashCache {
 }
 
 /// Return the directory where DotSlash should write its cached artifacts.
-/// Although DotSlash does not currently have any global config files,
-/// if it did, most platforms would prefer config files to be stored in
-/// a separate directory that is backed up and should not be blown away
-/// when the user is low on space like /tmp.
+/// Note that most platforms prefer config files (see `global_config`) to be
+/// stored in a separate directory that is backed up and should not be blown
+/// away when the user is low on space like /tmp.
 fn get_dotslash_cache() -> PathBuf {
-    if let Some(val) = env::var_os(DOTSLASH_CACHE_ENV) {
-        return PathBuf::from(val);
+    if let Some(cache_dir) = &GlobalConfig::get().cache_dir.value {
+        return cache_dir.clone();
     }
 
     // `dirs` returns the preferred cache directory for the user and the
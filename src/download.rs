@@ -9,24 +9,40 @@
  */
 
 use std::borrow::Cow;
+use std::fmt;
 use std::fs::File;
 use std::io;
+use std::io::BufRead;
 use std::io::BufReader;
+use std::io::Read;
+use std::io::Write as _;
 use std::path::Path;
 use std::path::PathBuf;
+use std::time::SystemTime;
 
 use anyhow::Context as _;
 use rand::distr::Distribution;
 use serde_json::Value;
-use sha2::Digest as _;
-use sha2::Sha256;
+use tempfile::TempDir;
+use thiserror::Error;
 
+use crate::artifact_hasher::ArtifactHasher;
+use crate::artifact_hasher::HashingReader;
 use crate::artifact_location::ArtifactLocation;
+use crate::artifact_manifest::ArtifactManifest;
+use crate::artifact_manifest::ArtifactOrigin;
 use crate::config::ArtifactEntry;
-use crate::config::HashAlgorithm;
 use crate::config::ProvidersOrder;
-use crate::digest::Digest;
 use crate::fetch_method::ArtifactFormat;
+use crate::global_config::GlobalConfig;
+use crate::global_config::Setting;
+use crate::global_config::SettingSource;
+use crate::inspect_artifact;
+use crate::inspect_artifact::Magic;
+use crate::mirror;
+use crate::mirror::MirrorRule;
+use crate::policy::Policy;
+use crate::provider::Provider;
 use crate::provider::ProviderFactory;
 use crate::util;
 use crate::util::FileLock;
@@ -36,17 +52,280 @@ use crate::util::unarchive;
 
 pub const DEFAULT_PROVDIER_TYPE: &str = "http";
 
+/// When set (to anything other than `0` or `false`), DotSlash never fetches
+/// artifacts: anything that is not already in the cache is an error. This is
+/// intended for hermetic builds where the cache is seeded ahead of time.
+/// Offline mode can also be enabled with `offline` in the global config.
+pub const DOTSLASH_OFFLINE_ENV: &str = "DOTSLASH_OFFLINE";
+
+/// Returned instead of fetching an artifact while offline mode is enabled.
+#[derive(Debug, Error)]
+#[error(
+    "offline mode is enabled by {enabled_by} but the artifact for `{name}` (`{}`) is not in the cache at `{}`",
+    dotslash_file.display(),
+    artifact_directory.display()
+)]
+pub struct OfflineError {
+    pub enabled_by: SettingSource,
+    pub name: String,
+    pub dotslash_file: PathBuf,
+    pub artifact_directory: PathBuf,
+}
+
+pub fn is_offline() -> bool {
+    GlobalConfig::get().offline.value
+}
+
+/// Fails with an `OfflineError` if providers must not be used.
+pub fn ensure_online(
+    artifact_location: &ArtifactLocation,
+    dotslash_file: DotslashFileInfo<'_>,
+) -> Result<(), OfflineError> {
+    if !is_offline() {
+        return Ok(());
+    }
+    Err(OfflineError {
+        enabled_by: GlobalConfig::get().offline.source.clone(),
+        name: dotslash_file.name.to_owned(),
+        dotslash_file: dunce::canonicalize(dotslash_file.path)
+            .unwrap_or_else(|_| dotslash_file.path.to_owned()),
+        artifact_directory: artifact_location.artifact_directory.clone(),
+    })
+}
+
+/// The DotSlash file on whose behalf an artifact is downloaded. This does not
+/// affect the download: it is only recorded in the artifact's manifest.
+#[derive(Copy, Clone, Debug)]
+pub struct DotslashFileInfo<'a> {
+    /// The `name` field of the DotSlash file.
+    pub name: &'a str,
+    pub path: &'a Path,
+}
+
 /// This function is responsible for:
 /// 1. Downloading an artifact into a temp location within $DOTSLASH_CACHE.
 /// 2. Verifying that the size and digest match the ArtifactEntry.
 /// 3. Decompressing the artifact, as appropriate.
 /// 4. Atomically moving it from its temp location to its final location.
+/// 5. Recording a manifest of the unpacked tree next to the final location.
+///
+/// When the first provider supports it, steps 1-3 happen concurrently (see
+/// `stream_artifact`).
+///
+/// In offline mode, it fails with an `OfflineError` instead.
+///
+/// Besides offline mode, the global config decides which mirrors are tried,
+/// which provider URLs are allowed, and the HTTP timeouts.
 pub fn download_artifact<P: ProviderFactory>(
     artifact_entry: &ArtifactEntry,
     artifact_location: &ArtifactLocation,
+    dotslash_file: DotslashFileInfo<'_>,
     provider_factory: &P,
 ) -> anyhow::Result<()> {
-    let artifact_parent_dir = artifact_location
+    ensure_online(artifact_location, dotslash_file)?;
+
+    let artifact_parent_dir = artifact_parent_dir(artifact_location)?;
+    fs_ctx::create_dir_all(artifact_parent_dir)?;
+
+    // We must maintain a reference to the FileLock until the download is complete.
+    let file_lock = acquire_download_lock_for_artifact(artifact_location)
+        .context("failed to get artifact lock")?;
+
+    let stream_to = artifact_entry.format.is_streamable().then_some(StreamTo {
+        artifact_location,
+        dotslash_file,
+    });
+    let (fetched_artifact, provider_config) = match fetch_from_providers(
+        artifact_entry,
+        &artifact_entry.providers,
+        artifact_parent_dir,
+        &file_lock,
+        provider_factory,
+        stream_to,
+        true,
+    )? {
+        Fetched::Installed => return Ok(()),
+        Fetched::File(fetched_artifact, provider_config) => (fetched_artifact, provider_config),
+    };
+
+    install_verified_artifact(
+        artifact_entry,
+        artifact_location,
+        &fetched_artifact,
+        artifact_origin(artifact_entry, dotslash_file, &provider_config),
+    )
+}
+
+/// Where `fetch_from_providers` installs an artifact that it streams.
+#[derive(Copy, Clone)]
+struct StreamTo<'a> {
+    artifact_location: &'a ArtifactLocation,
+    dotslash_file: DotslashFileInfo<'a>,
+}
+
+enum Fetched<'a> {
+    /// The artifact was streamed and installed (see `stream_artifact`).
+    Installed,
+    /// The verified artifact, as fetched, and the provider that fetched it.
+    File(PathBuf, Cow<'a, Value>),
+}
+
+/// Steps 1-4 of `download_artifact`, pipelined: the artifact is hashed and
+/// unpacked into a staging directory as it is fetched, so it is never read
+/// back from disk. The staging directory is only moved into place once the
+/// size and digest are confirmed.
+///
+/// Everything that is read from the stream is also written to
+/// `fetch_destination`, so that if the stream fails part of the way
+/// through, the caller can resume from there with `fetch_artifact`.
+/// Returns `false` if `provider` does not support streaming.
+fn stream_artifact(
+    provider: &dyn Provider,
+    provider_config: &Value,
+    fetch_destination: &Path,
+    file_lock: &FileLock,
+    artifact_entry: &ArtifactEntry,
+    stream_to: StreamTo<'_>,
+) -> anyhow::Result<bool> {
+    let Some(stream) = provider.open_artifact_stream(provider_config, file_lock, artifact_entry)?
+    else {
+        return Ok(false);
+    };
+    let StreamTo {
+        artifact_location,
+        dotslash_file,
+    } = stream_to;
+    let mut origin = artifact_origin(artifact_entry, dotslash_file, provider_config);
+
+    let temp_dir_to_mv = fs_ctx::tempdir_in(artifact_parent_dir(artifact_location)?)?;
+    let tee = TeeReader {
+        inner: stream,
+        copy: fs_ctx::file_create(fetch_destination)?,
+    };
+    // Everything that passes through the `BufReader` is hashed, including
+    // whatever it has buffered but the unpacker did not consume.
+    let mut reader = BufReader::new(HashingReader::new(tee, artifact_entry));
+    origin.magic = Some(reader.fill_buf().map_or(Magic::Unknown, Magic::of));
+    let result = unpack_artifact_stream(
+        &mut reader,
+        temp_dir_to_mv.path(),
+        artifact_entry.format,
+        artifact_entry.path.as_str(),
+    )
+    .and_then(|()| {
+        let hasher = reader.into_inner().finish()?;
+        hasher.verify(artifact_entry, &origin.provider)
+    });
+    if let Err(err) = result {
+        // Unpacking may have created read-only entries, which would keep the
+        // staging directory from being cleaned up.
+        let _ = util::make_tree_entries_writable(temp_dir_to_mv.path());
+        return Err(err);
+    }
+
+    let _ = fs_ctx::remove_file(fetch_destination);
+    move_into_place(artifact_entry, artifact_location, &temp_dir_to_mv, origin)?;
+    Ok(true)
+}
+
+/// Copies everything that is read from `inner` to `copy`.
+struct TeeReader<R> {
+    inner: R,
+    copy: File,
+}
+
+impl<R: Read> Read for TeeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.copy.write_all(&buf[..n])?;
+        Ok(n)
+    }
+}
+
+/// Steps 3-5 of `download_artifact`: unpacks `fetched_artifact`, which must
+/// already have been verified against `artifact_entry` and must be on the
+/// same filesystem as the cache, into its final location. The caller is
+/// responsible for holding the download lock for `artifact_location`.
+pub fn install_verified_artifact(
+    artifact_entry: &ArtifactEntry,
+    artifact_location: &ArtifactLocation,
+    fetched_artifact: &Path,
+    mut origin: ArtifactOrigin,
+) -> anyhow::Result<()> {
+    // This must be a sibling to the final artifact_location so that we can
+    // atomically move it into place.
+    let temp_dir_to_mv = fs_ctx::tempdir_in(artifact_parent_dir(artifact_location)?)?;
+    let magic = inspect_artifact::sniff_magic(fetched_artifact).unwrap_or(Magic::Unknown);
+    origin.magic = Some(magic);
+    let result = unpack_verified_artifact(
+        fetched_artifact,
+        temp_dir_to_mv.path(),
+        artifact_entry.format,
+        artifact_entry.path.as_str(),
+    );
+    // Mistakes in `format` are easy to make by hand, and the errors from
+    // unpacking do not point at the cause. (A mismatch that does not make
+    // unpacking fail is reported by `lint` once the artifact is cached.)
+    match (result, magic.mismatch_with(artifact_entry.format)) {
+        (Err(err), Some(mismatch)) => return Err(err.context(format!("the artifact {mismatch}"))),
+        (result, _) => result?,
+    }
+    move_into_place(artifact_entry, artifact_location, &temp_dir_to_mv, origin)
+}
+
+/// Steps 4-5 of `download_artifact` for an artifact that has been verified
+/// and unpacked into `temp_dir_to_mv`, which must be a sibling of the final
+/// location.
+fn move_into_place(
+    artifact_entry: &ArtifactEntry,
+    artifact_location: &ArtifactLocation,
+    temp_dir_to_mv: &TempDir,
+    origin: ArtifactOrigin,
+) -> anyhow::Result<()> {
+    if artifact_entry.readonly {
+        util::make_tree_entries_read_only(temp_dir_to_mv.path())?;
+    }
+    // Computed before the move so the manifest describes exactly what was
+    // unpacked, including the effect of `readonly`. Hashing the contents is
+    // left to `dotslash -- verify` so as not to slow down every download.
+    let manifest =
+        ArtifactManifest::from_tree_metadata(temp_dir_to_mv.path()).map(|mut manifest| {
+            manifest.origin = Some(origin);
+            manifest
+        });
+    util::mv_no_clobber(temp_dir_to_mv, &artifact_location.artifact_directory)?;
+    if artifact_entry.readonly {
+        // Note the following appears to work on Linux but not
+        // macOS:
+        //
+        // ```
+        // /tmp$ mkdir foo
+        // /tmp$ chmod -w foo
+        // /tmp$ mv foo bar
+        // ```
+        //
+        // so we have to do the final `chmod -w` after the `mv`.
+        // While we could also do the full `chmod -R -w` after
+        // the `mv`, that is a bit riskier because a
+        // simultaneous invocation of the DotSlash file would be
+        // able to use the artifact before `chmod -R -w`
+        // finishes.
+        let metadata = fs_ctx::symlink_metadata(&artifact_location.artifact_directory)?;
+        let mut perms = metadata.permissions();
+        perms.set_readonly(true);
+        fs_ctx::set_permissions(&artifact_location.artifact_directory, perms)?;
+    }
+    // The manifest is advisory (it is only used for inspecting the cache),
+    // so failing to record it must not fail the download.
+    if let Ok(manifest) = manifest {
+        let _ = manifest.write(&artifact_location.manifest_path);
+    }
+
+    Ok(())
+}
+
+fn artifact_parent_dir(artifact_location: &ArtifactLocation) -> anyhow::Result<&Path> {
+    artifact_location
         .artifact_directory
         .parent()
         .with_context(|| {
@@ -54,32 +333,116 @@ pub fn download_artifact<P: ProviderFactory>(
                 "no parent for artifact_directory `{}`",
                 artifact_location.artifact_directory.display()
             )
-        })?;
+        })
+}
 
-    fs_ctx::create_dir_all(artifact_parent_dir)?;
+pub fn artifact_origin(
+    artifact_entry: &ArtifactEntry,
+    dotslash_file: DotslashFileInfo<'_>,
+    provider_config: &Value,
+) -> ArtifactOrigin {
+    let unpacked_at = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    ArtifactOrigin {
+        name: dotslash_file.name.to_owned(),
+        dotslash_file: std::path::absolute(dotslash_file.path)
+            .unwrap_or_else(|_| dotslash_file.path.to_owned()),
+        size: artifact_entry.size,
+        hash: artifact_entry.hash,
+        digest: artifact_entry.digest.clone(),
+        format: artifact_entry
+            .format
+            .as_archive_type()
+            .map(|_| artifact_entry.format),
+        path: artifact_entry.path.clone(),
+        magic: None,
+        provider: provider_config.clone(),
+        unpacked_at,
+    }
+}
 
-    // We must maintain a reference to the FileLock until the download is complete.
-    let file_lock = acquire_download_lock_for_artifact(artifact_location)
-        .context("failed to get artifact lock")?;
+/// Tries each provider in turn until one fetches an artifact whose size and
+/// digest match `artifact_entry`. The verified artifact is left, as fetched
+/// (i.e., not yet unpacked), in a uniquely named file in `fetch_dir`. Returns
+/// its path along with the configuration of the provider that fetched it.
+/// The caller is responsible for holding `file_lock`.
+pub fn fetch_verified_artifact<'a, P: ProviderFactory>(
+    artifact_entry: &'a ArtifactEntry,
+    fetch_dir: &Path,
+    file_lock: &FileLock,
+    provider_factory: &P,
+) -> anyhow::Result<(PathBuf, Cow<'a, Value>)> {
+    match fetch_from_providers(
+        artifact_entry,
+        &artifact_entry.providers,
+        fetch_dir,
+        file_lock,
+        provider_factory,
+        None,
+        true,
+    )? {
+        Fetched::File(fetched_artifact, provider_config) => Ok((fetched_artifact, provider_config)),
+        Fetched::Installed => unreachable!("nothing is streamed without `stream_to`"),
+    }
+}
 
+/// Like `fetch_verified_artifact`, but from `providers` instead of those of
+/// `artifact_entry`, and without checking the size and digest of what is
+/// fetched, for when those are what is being determined (see `update`).
+/// Mirrors and policies apply all the same. Like for
+/// `fetch_verified_artifact`, the caller is responsible for offline mode.
+pub fn fetch_unverified_artifact<P: ProviderFactory>(
+    artifact_entry: &ArtifactEntry,
+    providers: &[Value],
+    fetch_dir: &Path,
+    provider_factory: &P,
+) -> anyhow::Result<PathBuf> {
+    match fetch_from_providers(
+        artifact_entry,
+        providers,
+        fetch_dir,
+        &FileLock::default(),
+        provider_factory,
+        None,
+        false,
+    )? {
+        Fetched::File(fetched_artifact, _provider_config) => Ok(fetched_artifact),
+        Fetched::Installed => unreachable!("nothing is streamed without `stream_to`"),
+    }
+}
+
+/// Like `fetch_verified_artifact`, but with `stream_to`, the first provider
+/// that is tried streams the artifact straight into place if it can (see
+/// `stream_artifact`). Only the first one, so that the order of the
+/// providers is kept. Without `verify`, whatever the first provider to
+/// succeed fetches is accepted.
+fn fetch_from_providers<'a, P: ProviderFactory>(
+    artifact_entry: &ArtifactEntry,
+    providers: &'a [Value],
+    fetch_dir: &Path,
+    file_lock: &FileLock,
+    provider_factory: &P,
+    mut stream_to: Option<StreamTo<'_>>,
+    verify: bool,
+) -> anyhow::Result<Fetched<'a>> {
     // Record warnings: only reported if no provider succeeds.
     let mut warnings = vec![];
+    let mut rejected = vec![];
 
     // Build a list of provider references,
     // and if randomization is enabled, shuffle them.
     let mut rng = rand::rng();
-    let providers = providers_in_order(
-        &mut rng,
-        &artifact_entry.providers,
-        artifact_entry.providers_order,
-    )?;
+    let providers = providers_in_order(&mut rng, providers, artifact_entry.providers_order)?;
+    let providers = with_mirrors(
+        providers,
+        &GlobalConfig::get().mirrors.value,
+        provider_factory,
+    );
 
     for provider_config in providers {
-        // This must be a sibling to the final artifact_location so that we can
-        // atomically move it into place.
-        let temp_dir_to_mv = fs_ctx::tempdir_in(artifact_parent_dir)?;
         let fetch_destination: PathBuf = {
-            let fetch_destination = fs_ctx::namedtempfile_new_in(artifact_parent_dir)
+            let fetch_destination = fs_ctx::namedtempfile_new_in(fetch_dir)
                 .context("failed to create fetch temp path")?
                 .into_temp_path();
             // fetch_destination is dropped after this and is removed from
@@ -88,60 +451,158 @@ pub fn download_artifact<P: ProviderFactory>(
             fetch_destination.to_path_buf()
         };
 
-        let provider_type = get_provider_type(provider_config)?;
-        let provider = provider_factory.get_provider(provider_type)?;
-        match provider.fetch_artifact(
-            provider_config,
-            &fetch_destination,
-            &file_lock,
-            artifact_entry,
-        ) {
-            Ok(()) => match verify_artifact(&fetch_destination, artifact_entry) {
-                Ok(()) => {
-                    unpack_verified_artifact(
-                        &fetch_destination,
-                        temp_dir_to_mv.path(),
-                        artifact_entry.format,
-                        artifact_entry.path.as_str(),
-                    )?;
-                    if artifact_entry.readonly {
-                        util::make_tree_entries_read_only(temp_dir_to_mv.path())?;
-                    }
-                    util::mv_no_clobber(&temp_dir_to_mv, &artifact_location.artifact_directory)?;
-                    if artifact_entry.readonly {
-                        // Note the following appears to work on Linux but not
-                        // macOS:
-                        //
-                        // ```
-                        // /tmp$ mkdir foo
-                        // /tmp$ chmod -w foo
-                        // /tmp$ mv foo bar
-                        // ```
-                        //
-                        // so we have to do the final `chmod -w` after the `mv`.
-                        // While we could also do the full `chmod -R -w` after
-                        // the `mv`, that is a bit riskier because a
-                        // simultaneous invocation of the DotSlash file would be
-                        // able to use the artifact before `chmod -R -w`
-                        // finishes.
-                        let metadata =
-                            fs_ctx::symlink_metadata(&artifact_location.artifact_directory)?;
-                        let mut perms = metadata.permissions();
-                        perms.set_readonly(true);
-                        fs_ctx::set_permissions(&artifact_location.artifact_directory, perms)?;
+        let provider = match allowed_provider(&provider_config, provider_factory)? {
+            PolicyCheck::Allowed(provider) => provider,
+            PolicyCheck::Rejected(rejection) => {
+                rejected.push(rejection);
+                continue;
+            }
+        };
+
+        let mut fetch = true;
+        if let Some(stream_to) = stream_to.take() {
+            match stream_artifact(
+                provider.as_ref(),
+                &provider_config,
+                &fetch_destination,
+                file_lock,
+                artifact_entry,
+                stream_to,
+            ) {
+                Ok(true) => return Ok(Fetched::Installed),
+                Ok(false) => {}
+                Err(e) => {
+                    let streamed = fs_ctx::metadata(&fetch_destination).map_or(0, |m| m.len());
+                    if streamed == artifact_entry.size {
+                        // Everything arrived, so `verify_artifact` reports
+                        // a mismatch and `install_verified_artifact` any
+                        // other problem.
+                        fetch = false;
+                    } else if streamed == 0 || streamed > artifact_entry.size {
+                        warnings.push(format!("failed to fetch artifact: {:?}", e));
+                        let _ = fs_ctx::remove_file(&fetch_destination);
+                        continue;
                     }
-                    return Ok(());
+                    // Otherwise, `fetch_artifact` resumes from what was
+                    // streamed rather than starting over.
                 }
-                Err(e) => warnings.push(format!("warning: failed to verify artifact {:?}", e)),
-            },
-            Err(e) => warnings.push(format!("failed to fetch artifact: {:?}", e)),
+            }
+        }
+
+        if fetch {
+            if let Err(e) = provider.fetch_artifact(
+                &provider_config,
+                &fetch_destination,
+                file_lock,
+                artifact_entry,
+            ) {
+                warnings.push(format!("failed to fetch artifact: {:?}", e));
+                continue;
+            }
+        }
+        if !verify {
+            return Ok(Fetched::File(fetch_destination, provider_config));
+        }
+        match verify_artifact(&fetch_destination, artifact_entry) {
+            Ok(()) => return Ok(Fetched::File(fetch_destination, provider_config)),
+            Err(e) => warnings.push(format!("warning: failed to verify artifact {:?}", e)),
+        }
+    }
+
+    if rejected.is_empty() {
+        return Err(anyhow::format_err!(
+            "no providers succeeded. warnings:\n{}",
+            warnings.join("\n")
+        ));
+    }
+    let mut message = format!(
+        "no providers succeeded. rejected by policy:\n{}",
+        rejected.join("\n")
+    );
+    if !warnings.is_empty() {
+        message.push_str("\nwarnings:\n");
+        message.push_str(&warnings.join("\n"));
+    }
+    Err(anyhow::Error::msg(message))
+}
+
+enum PolicyCheck {
+    Allowed(Box<dyn Provider>),
+    /// Why the provider was rejected.
+    Rejected(String),
+}
+
+/// Looks up the provider for `provider_config`, unless the policies in the
+/// global config reject it (see `policy`). The provider type is checked
+/// before the provider is looked up. For providers that run another tool,
+/// e.g., `gh`, `artifact_url` is a stand-in: the hosts that the tool actually
+/// contacts cannot be checked.
+fn allowed_provider<P: ProviderFactory>(
+    provider_config: &Value,
+    provider_factory: &P,
+) -> anyhow::Result<PolicyCheck> {
+    let policies = &GlobalConfig::try_get()?.policies;
+    let rejected = |reason: &dyn fmt::Display, policy: &Setting<Policy>| {
+        PolicyCheck::Rejected(format!("{reason} (policy in {})", policy.source))
+    };
+
+    let provider_type = get_provider_type(provider_config)?;
+    for policy in policies {
+        if let Err(violation) = policy.value.check_provider_type(provider_type) {
+            return Ok(rejected(&violation, policy));
         }
     }
 
-    Err(anyhow::format_err!(
-        "no providers succeeded. warnings:\n{}",
-        warnings.join("\n")
-    ))
+    let provider = provider_factory.get_provider(provider_type)?;
+    for policy in policies
+        .iter()
+        .filter(|policy| policy.value.restricts_urls())
+    {
+        let url = match provider.artifact_url(provider_config) {
+            Ok(url) => url,
+            Err(err) => {
+                return Ok(rejected(
+                    &format_args!(
+                        "cannot determine the URL of provider {provider_config}: {err:#}"
+                    ),
+                    policy,
+                ));
+            }
+        };
+        if let Err(violation) = policy.value.check_url(provider_type, url.as_deref()) {
+            return Ok(rejected(&violation, policy));
+        }
+    }
+
+    Ok(PolicyCheck::Allowed(provider))
+}
+
+/// `providers`, each preceded by an `http` provider for its mirror if one of
+/// `rules` matches its URL (see `mirror`). If the mirror fails, or serves
+/// the wrong bytes, the original provider is tried next.
+fn with_mirrors<'b, P: ProviderFactory>(
+    providers: Vec<&'b Value>,
+    rules: &[MirrorRule],
+    provider_factory: &P,
+) -> Vec<Cow<'b, Value>> {
+    let mut with_mirrors = Vec::with_capacity(providers.len());
+    for provider_config in providers {
+        // A bad provider config is reported when the provider itself is tried.
+        let mirror_url = get_provider_type(provider_config)
+            .and_then(|provider_type| provider_factory.get_provider(provider_type))
+            .and_then(|provider| provider.artifact_url(provider_config))
+            .ok()
+            .flatten()
+            .and_then(|url| mirror::rewrite_url(rules, &url));
+        if let Some(mirror_url) = mirror_url {
+            with_mirrors.push(Cow::Owned(serde_json::json!({
+                "type": DEFAULT_PROVDIER_TYPE,
+                "url": mirror_url,
+            })));
+        }
+        with_mirrors.push(Cow::Borrowed(provider_config));
+    }
+    with_mirrors
 }
 
 fn providers_in_order<'b>(
@@ -199,14 +660,16 @@ fn providers_in_order<'b>(
     Ok(ordered_providers)
 }
 
-fn get_provider_type(provider_config: &Value) -> anyhow::Result<&str> {
+pub fn get_provider_type(provider_config: &Value) -> anyhow::Result<&str> {
     match provider_config.get("type") {
         Some(v) => v.as_str().context("type must map to a string"),
         None => Ok(DEFAULT_PROVDIER_TYPE),
     }
 }
 
-fn verify_artifact(
+/// Checks that the file at `artifact_temp_location` has the size and digest
+/// specified by `artifact_entry`.
+pub fn verify_artifact(
     artifact_temp_location: &Path,
     artifact_entry: &ArtifactEntry,
 ) -> anyhow::Result<()> {
@@ -220,23 +683,8 @@ fn verify_artifact(
                 artifact_temp_location.display()
             )
         })?;
-    let (size_in_bytes, digest) = match artifact_entry.hash {
-        HashAlgorithm::Blake3 => {
-            let mut hasher = blake3::Hasher::new();
-            io::copy(&mut file, &mut hasher).map(|size_in_bytes| {
-                let digest = format!("{}", hasher.finalize().to_hex());
-                (size_in_bytes, digest)
-            })
-        }
-        HashAlgorithm::Sha256 => {
-            let mut hasher = Sha256::new();
-            io::copy(&mut file, &mut hasher).map(|size_in_bytes| {
-                let digest = format!("{:x}", hasher.finalize());
-                (size_in_bytes, digest)
-            })
-        }
-    }
-    .with_context(|| {
+    let mut hasher = ArtifactHasher::new(artifact_entry);
+    io::copy(&mut file, &mut hasher).with_context(|| {
         format!(
             "failed to calculate digest for fetched archive `{}`",
             artifact_temp_location.display()
@@ -244,56 +692,71 @@ fn verify_artifact(
     })?;
     drop(file);
 
-    if size_in_bytes != artifact_entry.size {
-        return Err(anyhow::format_err!(
-            "fetched artifact `{}` has incorrect size: {} bytes vs expected {} bytes",
-            artifact_temp_location.display(),
-            size_in_bytes,
-            artifact_entry.size
-        ));
-    }
-
-    let digest = Digest::try_from(digest)?;
-    if digest != artifact_entry.digest {
-        return Err(anyhow::format_err!(
-            "fetched artifact `{}` has incorrect digest: {} vs expected {}",
-            artifact_temp_location.display(),
-            digest,
-            artifact_entry.digest
-        ));
-    }
-
-    Ok(())
+    hasher.verify(artifact_entry, &artifact_temp_location.display())
 }
 
 /// Unpacks the verified artifact. When this function exits, the contents of
 /// `temp_dir_to_mv` should be ready to be moved into the final location.
-fn unpack_verified_artifact(
+pub fn unpack_verified_artifact(
     fetched_artifact: &Path,
     temp_dir_to_mv: &Path,
     format: ArtifactFormat,
     artifact_entry_path: &str,
 ) -> anyhow::Result<()> {
+    let final_artifact_path = final_artifact_path(temp_dir_to_mv, format, artifact_entry_path)?;
+
+    if let Some(archive_type) = format.as_archive_type() {
+        let reader = BufReader::new(fs_ctx::file_open(fetched_artifact)?);
+        unarchive::unarchive(reader, &final_artifact_path, archive_type)?;
+    } else {
+        fs_ctx::rename(fetched_artifact, &final_artifact_path)?;
+    }
+
+    finish_unpack(&final_artifact_path, format)
+}
+
+/// Like `unpack_verified_artifact`, but in a single pass over `reader`,
+/// which must be in a streamable `format`.
+fn unpack_artifact_stream(
+    mut reader: impl BufRead,
+    temp_dir_to_mv: &Path,
+    format: ArtifactFormat,
+    artifact_entry_path: &str,
+) -> anyhow::Result<()> {
+    let final_artifact_path = final_artifact_path(temp_dir_to_mv, format, artifact_entry_path)?;
+
+    if let Some(archive_type) = format.as_archive_type() {
+        unarchive::unarchive_stream(reader, &final_artifact_path, archive_type)?;
+    } else {
+        let mut file = fs_ctx::file_create(&final_artifact_path)?;
+        io::copy(&mut reader, &mut file)?;
+    }
+
+    finish_unpack(&final_artifact_path, format)
+}
+
+/// Where the artifact is unpacked to within `temp_dir_to_mv`.
+fn final_artifact_path<'a>(
+    temp_dir_to_mv: &'a Path,
+    format: ArtifactFormat,
+    artifact_entry_path: &str,
+) -> anyhow::Result<Cow<'a, Path>> {
     // Container artifacts get unarchived into directories.
     // Non-container artifacts get written directly to a file.
-    let final_artifact_path = if format.is_container() {
-        Cow::Borrowed(temp_dir_to_mv)
+    if format.is_container() {
+        Ok(Cow::Borrowed(temp_dir_to_mv))
     } else {
         let final_artifact_path = temp_dir_to_mv.join(artifact_entry_path);
         let parent = final_artifact_path.parent().unwrap();
         if parent != Path::new("") {
             fs_ctx::create_dir_all(parent)?;
         }
-        Cow::Owned(final_artifact_path)
-    };
-
-    if let Some(archive_type) = format.as_archive_type() {
-        let reader = BufReader::new(fs_ctx::file_open(fetched_artifact)?);
-        unarchive::unarchive(reader, &final_artifact_path, archive_type)?;
-    } else {
-        fs_ctx::rename(fetched_artifact, &final_artifact_path)?;
+        Ok(Cow::Owned(final_artifact_path))
     }
+}
 
+#[cfg_attr(not(unix), expect(unused_variables))]
+fn finish_unpack(final_artifact_path: &Path, format: ArtifactFormat) -> anyhow::Result<()> {
     if !format.is_container() {
         #[cfg(unix)]
         util::chmodx(final_artifact_path).context("failed to make path executable")?;
@@ -330,9 +793,215 @@ pub fn acquire_download_lock_for_artifact(
 
 #[cfg(test)]
 mod tests {
+    use std::cell::RefCell;
+    use std::fs;
+    use std::io::Cursor;
+    use std::rc::Rc;
+
     use rand::SeedableRng;
 
     use super::*;
+    use crate::artifact_location::determine_location;
+    use crate::dotslash_cache::DotslashCache;
+    use crate::provider::Provider;
+
+    /// Serves the `contents` of each provider config, which can also set
+    /// `stream` to `false` to not support streaming, or `fail_after` to make
+    /// the stream fail after that many bytes. Fetches resume from whatever
+    /// is already in the destination, like `curl --continue-at -`.
+    #[derive(Clone, Default)]
+    struct FakeProvider {
+        /// What was asked of the provider, in order.
+        log: Rc<RefCell<Vec<String>>>,
+    }
+
+    fn contents(provider_config: &Value) -> Vec<u8> {
+        provider_config["contents"]
+            .as_str()
+            .unwrap()
+            .as_bytes()
+            .to_vec()
+    }
+
+    impl Provider for FakeProvider {
+        fn fetch_artifact(
+            &self,
+            provider_config: &Value,
+            destination: &Path,
+            _fetch_lock: &FileLock,
+            _artifact_entry: &ArtifactEntry,
+        ) -> anyhow::Result<()> {
+            let mut fetched = fs::read(destination).unwrap_or_default();
+            self.log.borrow_mut().push(format!(
+                "fetch {} from {}",
+                provider_config["contents"],
+                fetched.len(),
+            ));
+            fetched.extend_from_slice(&contents(provider_config)[fetched.len()..]);
+            fs::write(destination, fetched)?;
+            Ok(())
+        }
+
+        fn open_artifact_stream(
+            &self,
+            provider_config: &Value,
+            _fetch_lock: &FileLock,
+            _artifact_entry: &ArtifactEntry,
+        ) -> anyhow::Result<Option<Box<dyn Read>>> {
+            if provider_config["stream"] == false {
+                return Ok(None);
+            }
+            self.log
+                .borrow_mut()
+                .push(format!("stream {}", provider_config["contents"]));
+            let contents = contents(provider_config);
+            Ok(Some(match provider_config["fail_after"].as_u64() {
+                Some(n) => {
+                    Box::new(Cursor::new(contents[..n as usize].to_vec()).chain(FailingReader))
+                }
+                None => Box::new(Cursor::new(contents)),
+            }))
+        }
+    }
+
+    struct FailingReader;
+
+    impl Read for FailingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("connection reset"))
+        }
+    }
+
+    impl ProviderFactory for FakeProvider {
+        fn get_provider(&self, _provider_type: &str) -> anyhow::Result<Box<dyn Provider>> {
+            Ok(Box::new(self.clone()))
+        }
+    }
+
+    /// Downloads `contents` from `providers` (see `FakeProvider`), returning
+    /// what ended up in the cache and what was asked of the providers.
+    fn download_with(providers: &Value, contents: &[u8]) -> anyhow::Result<(Vec<u8>, Vec<String>)> {
+        let provider = FakeProvider::default();
+        let temp_dir = tempfile::tempdir()?;
+        let dotslash_cache = DotslashCache::new_in(temp_dir.path());
+        let artifact_entry: ArtifactEntry = serde_json::from_value(serde_json::json!({
+            "size": contents.len(),
+            "hash": "blake3",
+            "digest": blake3::hash(contents).to_hex().as_str(),
+            "path": "tool",
+            "providers": providers,
+        }))?;
+        let artifact_location = determine_location(&artifact_entry, &dotslash_cache);
+        let dotslash_file = DotslashFileInfo {
+            name: "tool",
+            path: Path::new("tool"),
+        };
+        download_artifact(
+            &artifact_entry,
+            &artifact_location,
+            dotslash_file,
+            &provider,
+        )?;
+        let log = provider.log.borrow().clone();
+        Ok((fs::read(&artifact_location.executable)?, log))
+    }
+
+    #[test]
+    fn download_artifact_streams() -> anyhow::Result<()> {
+        let providers = serde_json::json!([
+            {"type": "fake", "contents": "tool\n"},
+            {"type": "fake", "contents": "unused\n"},
+        ]);
+        let (fetched, log) = download_with(&providers, b"tool\n")?;
+        assert_eq!(fetched, b"tool\n");
+        assert_eq!(log, [r#"stream "tool\n""#]);
+        Ok(())
+    }
+
+    #[test]
+    fn download_artifact_only_streams_from_first_provider() -> anyhow::Result<()> {
+        let providers = serde_json::json!([
+            {"type": "fake", "contents": "tool\n", "stream": false},
+            {"type": "fake", "contents": "unused\n"},
+        ]);
+        let (fetched, log) = download_with(&providers, b"tool\n")?;
+        assert_eq!(fetched, b"tool\n");
+        assert_eq!(log, [r#"fetch "tool\n" from 0"#]);
+        Ok(())
+    }
+
+    #[test]
+    fn download_artifact_resumes_failed_stream() -> anyhow::Result<()> {
+        let providers = serde_json::json!([
+            {"type": "fake", "contents": "tool\n", "fail_after": 2},
+        ]);
+        let (fetched, log) = download_with(&providers, b"tool\n")?;
+        assert_eq!(fetched, b"tool\n");
+        assert_eq!(log, [r#"stream "tool\n""#, r#"fetch "tool\n" from 2"#]);
+        Ok(())
+    }
+
+    #[test]
+    fn download_artifact_moves_on_from_corrupt_stream() -> anyhow::Result<()> {
+        let providers = serde_json::json!([
+            {"type": "fake", "contents": "tamp\n"},
+            {"type": "fake", "contents": "tool\n"},
+        ]);
+        let (fetched, log) = download_with(&providers, b"tool\n")?;
+        assert_eq!(fetched, b"tool\n");
+        // The corrupt artifact is not fetched again from the same provider.
+        assert_eq!(log, [r#"stream "tamp\n""#, r#"fetch "tool\n" from 0"#]);
+
+        let providers = serde_json::json!([{"type": "fake", "contents": "tamp\n"}]);
+        let err = download_with(&providers, b"tool\n").unwrap_err();
+        assert!(
+            format!("{err:#}").contains("incorrect blake3 digest"),
+            "{err:#}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn with_mirrors_inserts_mirror_before_original() {
+        let rules: Vec<MirrorRule> = serde_json::from_value(serde_json::json!([
+            {"prefix": "https://github.com/", "replacement": "https://mirror.example.com/gh/"},
+            {"regex": "s3://([^/]+)/(.*)", "replacement": "https://$1.mirror.example.com/$2"},
+        ]))
+        .unwrap();
+        let providers = [
+            serde_json::json!({"url": "https://example.com/tool.tar.gz"}),
+            serde_json::json!({
+                "type": "github-release",
+                "repo": "facebook/dotslash",
+                "tag": "v1",
+                "name": "tool.tar.gz",
+            }),
+            serde_json::json!({"type": "s3", "bucket": "tools", "key": "v1/tool.tar.gz"}),
+        ];
+
+        let with_mirrors = with_mirrors(
+            providers.iter().collect(),
+            &rules,
+            &crate::default_provider_factory::DefaultProviderFactory,
+        );
+
+        assert_eq!(
+            with_mirrors.iter().map(AsRef::as_ref).collect::<Vec<_>>(),
+            vec![
+                &providers[0],
+                &serde_json::json!({
+                    "type": "http",
+                    "url": "https://mirror.example.com/gh/facebook/dotslash/releases/download/v1/tool.tar.gz",
+                }),
+                &providers[1],
+                &serde_json::json!({
+                    "type": "http",
+                    "url": "https://tools.mirror.example.com/v1/tool.tar.gz",
+                }),
+                &providers[2],
+            ],
+        );
+    }
 
     #[test]
     fn providers_in_order_sequential() {
@@ -0,0 +1,348 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! A provider that delegates the fetch to an external program so that new
+//! kinds of artifact stores can be supported without rebuilding DotSlash.
+//!
+//! The DotSlash file only names the provider. For `"name": "foo"`, the
+//! program is `dotslash-provider-foo`, which must be installed in an
+//! absolute directory on `PATH`, so a DotSlash file cannot make DotSlash run
+//! a program of its choosing. The program is sent an `ExecRequest` as JSON
+//! on stdin. It must write the artifact to `destination` and then print an
+//! `ExecResult` as JSON on stdout, e.g., `{}`. To fail with a specific
+//! message, it can print `{"error": "..."}` instead. DotSlash verifies the
+//! size and digest of whatever was written, as it does for every provider.
+
+use std::env;
+use std::ffi::OsStr;
+use std::io;
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+
+use anyhow::Context as _;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::config::ArtifactEntry;
+use crate::config::HashAlgorithm;
+use crate::digest::Digest;
+use crate::provider::Provider;
+use crate::util::CommandDisplay;
+use crate::util::CommandStderrDisplay;
+use crate::util::FileLock;
+
+/// Bumped if `ExecRequest` or `ExecResult` change incompatibly.
+const PROTOCOL_VERSION: u32 = 1;
+
+/// Prepended to the `name` of the provider to get the program to run.
+pub const PROGRAM_PREFIX: &str = "dotslash-provider-";
+
+pub struct ExecProvider {}
+
+#[derive(Deserialize, Debug)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct ExecProviderConfig {
+    /// Must be a valid `NAME_PATTERN`.
+    name: String,
+}
+
+/// Keeps `name` from being anything but the last part of a file name.
+pub const NAME_PATTERN: &str = "^[A-Za-z0-9_-]+$";
+
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+#[derive(Serialize, Debug)]
+struct ExecRequest<'a> {
+    version: u32,
+    /// The provider as it appears in the DotSlash file, so that the program
+    /// can read any fields of its own from it.
+    provider_config: &'a Value,
+    destination: &'a Path,
+    size: u64,
+    hash: HashAlgorithm,
+    digest: &'a Digest,
+}
+
+#[derive(Deserialize, Debug)]
+struct ExecResult {
+    #[serde(default)]
+    error: Option<String>,
+}
+
+impl Provider for ExecProvider {
+    fn fetch_artifact(
+        &self,
+        provider_config: &Value,
+        destination: &Path,
+        _fetch_lock: &FileLock,
+        artifact_entry: &ArtifactEntry,
+    ) -> anyhow::Result<()> {
+        fetch_with_search_path(
+            provider_config,
+            destination,
+            artifact_entry,
+            env::var_os("PATH").as_deref(),
+        )
+    }
+}
+
+/// Finds `program` in the absolute directories of `search_path`. Relative
+/// directories (including the empty one) are skipped because they would
+/// resolve against the current directory, which may be the checkout that
+/// the DotSlash file came from.
+fn find_program(program: &str, search_path: Option<&OsStr>) -> anyhow::Result<PathBuf> {
+    let file_name = format!("{program}{}", env::consts::EXE_SUFFIX);
+    search_path
+        .into_iter()
+        .flat_map(env::split_paths)
+        .filter(|dir| dir.is_absolute())
+        .map(|dir| dir.join(&file_name))
+        .find(|candidate| candidate.is_file())
+        .ok_or_else(|| anyhow::format_err!("`{file_name}` was not found on PATH"))
+}
+
+fn fetch_with_search_path(
+    provider_config: &Value,
+    destination: &Path,
+    artifact_entry: &ArtifactEntry,
+    search_path: Option<&OsStr>,
+) -> anyhow::Result<()> {
+    let ExecProviderConfig { name } = <_>::deserialize(provider_config)?;
+    if !is_valid_name(&name) {
+        return Err(anyhow::format_err!(
+            "exec provider name `{name}` does not match `{NAME_PATTERN}`"
+        ));
+    }
+    let program = find_program(&format!("{PROGRAM_PREFIX}{name}"), search_path)
+        .context("failed to find the exec provider")?;
+
+    let request = ExecRequest {
+        version: PROTOCOL_VERSION,
+        provider_config,
+        destination,
+        size: artifact_entry.size,
+        hash: artifact_entry.hash,
+        digest: &artifact_entry.digest,
+    };
+    let request = serde_json::to_vec(&request).context("failed to serialize request")?;
+
+    let mut command = Command::new(program);
+    command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let output = run_with_stdin(&mut command, &request)
+        .with_context(|| format!("{}", CommandDisplay::new(&command)))
+        .context("failed to run the exec provider")?;
+
+    let result = serde_json::from_slice::<ExecResult>(&output.stdout);
+    if let Ok(ExecResult { error: Some(error) }) = result {
+        return Err(anyhow::format_err!("{error}"))
+            .with_context(|| format!("{}", CommandDisplay::new(&command)))
+            .context("the exec provider failed");
+    }
+    if !output.status.success() {
+        return Err(anyhow::format_err!(
+            "{}",
+            CommandStderrDisplay::new(&output)
+        ))
+        .with_context(|| format!("{}", CommandDisplay::new(&command)))
+        .context("the exec provider failed");
+    }
+    result
+        .with_context(|| {
+            format!(
+                "expected a JSON result on stdout but got: {}",
+                String::from_utf8_lossy(&output.stdout).trim_end(),
+            )
+        })
+        .with_context(|| format!("{}", CommandDisplay::new(&command)))
+        .context("the exec provider failed")?;
+
+    Ok(())
+}
+
+fn run_with_stdin(command: &mut Command, stdin: &[u8]) -> io::Result<std::process::Output> {
+    let mut child = command.spawn()?;
+    let mut child_stdin = child.stdin.take().unwrap();
+    // The program is free to exit without reading its request, e.g., to
+    // report that it is misconfigured, so a broken pipe is not an error.
+    match child_stdin.write_all(stdin) {
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(e);
+        }
+        _ => {}
+    }
+    drop(child_stdin);
+    child.wait_with_output()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt as _;
+
+    use serde_json::json;
+
+    use super::*;
+
+    /// Installs `script` as the exec provider `name` in `bin_dir`.
+    fn install(bin_dir: &Path, name: &str, script: &str) {
+        let program = bin_dir.join(format!("{PROGRAM_PREFIX}{name}"));
+        fs::write(&program, format!("#!/bin/sh\n{script}\n")).unwrap();
+        fs::set_permissions(&program, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    /// Fetches with the exec provider `name`, searching only `search_path`.
+    fn fetch_with(name: &str, search_path: &OsStr, destination: &Path) -> anyhow::Result<()> {
+        let contents = b"hello";
+        let provider_config = json!({
+            "type": "exec",
+            "name": name,
+            "store": "team-artifacts",
+        });
+        let artifact_entry: ArtifactEntry = serde_json::from_value(json!({
+            "size": contents.len(),
+            "hash": "blake3",
+            "digest": blake3::hash(contents).to_hex().as_str(),
+            "path": "tool",
+            "providers": [provider_config],
+        }))?;
+        fetch_with_search_path(
+            &provider_config,
+            destination,
+            &artifact_entry,
+            Some(search_path),
+        )
+    }
+
+    #[test]
+    fn writes_artifact_and_receives_request() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let destination = temp_dir.path().join("artifact");
+        let request_path = temp_dir.path().join("request.json");
+        install(
+            temp_dir.path(),
+            "store",
+            &format!(
+                "cat > '{}' && printf hello > '{}' && echo '{{}}'",
+                request_path.display(),
+                destination.display(),
+            ),
+        );
+        fetch_with("store", temp_dir.path().as_os_str(), &destination)?;
+
+        assert_eq!(fs::read(&destination)?, b"hello");
+        let request: Value = serde_json::from_slice(&fs::read(&request_path)?)?;
+        assert_eq!(request["version"], PROTOCOL_VERSION);
+        assert_eq!(request["provider_config"]["store"], "team-artifacts");
+        assert_eq!(request["destination"], destination.to_str().unwrap());
+        assert_eq!(request["size"], 5);
+        assert_eq!(request["hash"], "blake3");
+        assert_eq!(request["digest"], blake3::hash(b"hello").to_hex().as_str());
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_names_that_are_not_just_a_name() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().join("artifact");
+        for name in ["", "../store", "/bin/sh", "sub/store", "store.sh", "store "] {
+            let error = fetch_with(name, temp_dir.path().as_os_str(), &destination).unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                format!("exec provider name `{name}` does not match `{NAME_PATTERN}`"),
+            );
+        }
+    }
+
+    #[test]
+    fn refuses_programs_that_are_not_installed() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().join("artifact");
+        // `sh` is on the real `PATH`, but is not named like a provider.
+        install(temp_dir.path(), "store", "echo '{}'");
+        let error = fetch_with("sh", temp_dir.path().as_os_str(), &destination).unwrap_err();
+        assert_eq!(
+            format!("{error:#}"),
+            "failed to find the exec provider: `dotslash-provider-sh` was not found on PATH",
+        );
+    }
+
+    #[test]
+    fn skips_relative_path_entries() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().join("artifact");
+        install(temp_dir.path(), "store", "echo '{}'");
+        let relative = temp_dir.path().strip_prefix("/").unwrap();
+        assert!(fetch_with("store", relative.as_os_str(), &destination).is_err());
+    }
+
+    #[test]
+    fn reports_error_from_result() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        install(
+            temp_dir.path(),
+            "store",
+            r#"echo '{"error": "no such artifact"}'; exit 1"#,
+        );
+        let error = fetch_with(
+            "store",
+            temp_dir.path().as_os_str(),
+            &temp_dir.path().join("artifact"),
+        )
+        .unwrap_err();
+        assert_eq!(error.to_string(), "the exec provider failed");
+        assert_eq!(error.root_cause().to_string(), "no such artifact");
+    }
+
+    #[test]
+    fn reports_stderr_on_failure() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        install(temp_dir.path(), "store", "echo oops >&2; exit 3");
+        let error = fetch_with(
+            "store",
+            temp_dir.path().as_os_str(),
+            &temp_dir.path().join("artifact"),
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.root_cause().to_string(),
+            "command failed with exit code 3 and stderr: oops",
+        );
+    }
+
+    #[test]
+    fn requires_result_on_success() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        install(temp_dir.path(), "store", "echo done");
+        let error = fetch_with(
+            "store",
+            temp_dir.path().as_os_str(),
+            &temp_dir.path().join("artifact"),
+        )
+        .unwrap_err();
+        assert!(
+            format!("{error:#}").contains("expected a JSON result on stdout but got: done"),
+            "{error:#}",
+        );
+    }
+}
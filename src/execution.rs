@@ -25,6 +25,7 @@ use anyhow::Context as _;
 use crate::config::Arg0;
 use crate::config::IncompatibleDotslashBinaryError;
 use crate::dotslash_cache::DotslashCache;
+use crate::download::DotslashFileInfo;
 use crate::download::download_artifact;
 use crate::locate::locate_artifact;
 use crate::provider::ProviderFactory;
@@ -79,18 +80,18 @@ fn run_dotslash_file<P: ProviderFactory>(
     };
 
     let dotslash_cache = DotslashCache::new();
-    let (artifact_entry, artifact_location) = match locate_artifact(&dotslash_data, &dotslash_cache)
-    {
-        Ok(result) => result,
-        Err(err)
-            if err
-                .chain()
-                .any(<dyn std::error::Error + 'static>::is::<IncompatibleDotslashBinaryError>) =>
-        {
-            return delegate_to_other_dotslash_binary(file_arg, args, err);
-        }
-        Err(err) => return Err(err),
-    };
+    let (name, artifact_entry, artifact_location) =
+        match locate_artifact(&dotslash_data, &dotslash_cache) {
+            Ok(result) => result,
+            Err(err)
+                if err.chain().any(
+                    <dyn std::error::Error + 'static>::is::<IncompatibleDotslashBinaryError>,
+                ) =>
+            {
+                return delegate_to_other_dotslash_binary(file_arg, args, err);
+            }
+            Err(err) => return Err(err),
+        };
 
     let mut command = Command::new(&artifact_location.executable);
     command.args(args);
@@ -114,15 +115,23 @@ fn run_dotslash_file<P: ProviderFactory>(
         ));
     }
 
-    download_artifact(&artifact_entry, &artifact_location, provider_factory).with_context(
-        || {
-            format!(
-                "failed to download artifact into cache `{}` artifact location `{}`",
-                dotslash_cache.cache_dir().display(),
-                artifact_location.artifact_directory.display()
-            )
-        },
-    )?;
+    let dotslash_file = DotslashFileInfo {
+        name: &name,
+        path: Path::new(file_arg),
+    };
+    download_artifact(
+        &artifact_entry,
+        &artifact_location,
+        dotslash_file,
+        provider_factory,
+    )
+    .with_context(|| {
+        format!(
+            "failed to download artifact into cache `{}` artifact location `{}`",
+            dotslash_cache.cache_dir().display(),
+            artifact_location.artifact_directory.display()
+        )
+    })?;
 
     // Since we just unpacked the executable for the first time, we can
     // afford to pay the macOS cost mentioned above.
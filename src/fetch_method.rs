@@ -13,8 +13,7 @@ use serde::Serialize;
 
 use crate::util::unarchive::ArchiveType;
 
-#[derive(Deserialize, Serialize, Copy, Clone, Default, Debug)]
-#[cfg_attr(test, derive(PartialEq))]
+#[derive(Deserialize, Serialize, Copy, Clone, Default, Debug, PartialEq)]
 pub enum ArtifactFormat {
     /// Artifact is a single file with no compression applied.
     #[default]
@@ -79,4 +78,30 @@ impl ArtifactFormat {
             }
         }
     }
+
+    /// Whether the artifact can be unpacked in a single pass, i.e., while it
+    /// is still being fetched.
+    #[must_use]
+    pub fn is_streamable(self) -> bool {
+        !matches!(self, Self::Zip)
+    }
+
+    /// The conventional file extension for an artifact in this format,
+    /// including the leading dot, or `""` for `Plain`.
+    #[must_use]
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Plain => "",
+            Self::Bzip2 => ".bz2",
+            Self::Gz => ".gz",
+            Self::Tar => ".tar",
+            Self::TarBzip2 => ".tar.bz2",
+            Self::TarGz => ".tar.gz",
+            Self::TarZstd => ".tar.zst",
+            Self::TarXz => ".tar.xz",
+            Self::Xz => ".xz",
+            Self::Zstd => ".zst",
+            Self::Zip => ".zip",
+        }
+    }
 }
@@ -0,0 +1,470 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! Rewrites DotSlash files in a canonical layout so that the same data is
+//! always written the same way, no matter who (or what) wrote the file:
+//!
+//! - Two-space indentation, one field or array element per line, and no
+//!   trailing commas.
+//! - `name` and `platforms` come first, the platforms are sorted, and the
+//!   fields of each entry are in the order `ArtifactEntry` declares them.
+//!   Other fields keep their order.
+//! - Comments are kept with the field or element that they precede, or that
+//!   they follow on the same line.
+//! - Blank lines are kept, though runs of them become one.
+
+use anyhow::Context as _;
+use jsonc_parser::CollectOptions;
+use jsonc_parser::CommentCollectionStrategy;
+use jsonc_parser::ParseOptions;
+use jsonc_parser::ast;
+use jsonc_parser::common::Range;
+use jsonc_parser::common::Ranged;
+use jsonc_parser::parse_to_ast;
+
+use crate::config::ARTIFACT_ENTRY_FIELDS;
+use crate::config::REQUIRED_HEADER;
+use crate::config::blank_header;
+
+const INDENT: &str = "  ";
+
+/// Stands for one or more blank lines in the input, which are kept as a
+/// single blank line. It cannot be confused with a comment, which is never
+/// empty.
+const BLANK_LINE: &str = "";
+
+const TOP_LEVEL_FIELDS: [&str; 2] = ["name", "platforms"];
+
+/// Which part of a DotSlash file an object is, which determines the order of
+/// its fields.
+#[derive(Copy, Clone, PartialEq)]
+enum Level {
+    Root,
+    Platforms,
+    ArtifactEntry,
+    Other,
+}
+
+pub fn format_dotslash_file(dotslash_data: &str) -> anyhow::Result<String> {
+    let has_header = dotslash_data
+        .strip_prefix(REQUIRED_HEADER)
+        .is_some_and(|rest| rest.starts_with('\n') || rest.starts_with("\r\n"));
+    if !has_header {
+        anyhow::bail!("DotSlash file must start with `{REQUIRED_HEADER}`");
+    }
+    let json = blank_header(dotslash_data);
+
+    let collect_options = CollectOptions {
+        comments: CommentCollectionStrategy::Separate,
+        tokens: false,
+    };
+    let ast = parse_to_ast(&json, &collect_options, &ParseOptions::default())?;
+    let root = ast
+        .value
+        .context("DotSlash file must contain a JSON object")?;
+    // Each comment is keyed by both the token before it and the token after.
+    let mut comments = ast
+        .comments
+        .iter()
+        .flat_map(|comments| comments.values())
+        .flat_map(|comments| comments.iter())
+        .map(Ranged::range)
+        .collect::<Vec<_>>();
+    comments.sort_by_key(|range| range.start);
+    comments.dedup_by_key(|range| range.start);
+
+    let mut formatter = Formatter {
+        text: &json,
+        comments,
+        out: format!("{REQUIRED_HEADER}\n"),
+    };
+    let root_range = root.range();
+    for line in formatter.lines_in(REQUIRED_HEADER.len(), root_range.start) {
+        formatter.out.push_str(line);
+        formatter.out.push('\n');
+    }
+    formatter.write_value(&root, Level::Root, 0);
+    formatter.out.push('\n');
+    let mut closing = formatter.lines_in(root_range.end, json.len());
+    if closing.last() == Some(&BLANK_LINE) {
+        closing.pop();
+    }
+    for line in closing {
+        formatter.out.push_str(line);
+        formatter.out.push('\n');
+    }
+
+    // Reformatting must never change what the DotSlash file says.
+    let before = jsonc_parser::parse_to_serde_value(&json, &ParseOptions::default())?;
+    let after = jsonc_parser::parse_to_serde_value(
+        &blank_header(&formatter.out),
+        &ParseOptions::default(),
+    )?;
+    if before != after {
+        anyhow::bail!("formatting would change the contents of the DotSlash file");
+    }
+    Ok(formatter.out)
+}
+
+/// A field of an object or an element of an array.
+enum Item<'b, 'a> {
+    Field(&'b ast::ObjectProp<'a>),
+    Element(&'b ast::Value<'a>),
+}
+
+impl Item<'_, '_> {
+    fn range(&self) -> Range {
+        match self {
+            Self::Field(prop) => prop.range,
+            Self::Element(value) => value.range(),
+        }
+    }
+
+    fn value_start(&self) -> usize {
+        match self {
+            Self::Field(prop) => prop.value.range().start,
+            Self::Element(value) => value.range().start,
+        }
+    }
+}
+
+/// The comments and blank lines in an object or array, by which item they
+/// belong to.
+struct ItemComments<'t> {
+    leading: Vec<Vec<&'t str>>,
+    trailing: Vec<Vec<&'t str>>,
+    /// After the last item, on lines of their own.
+    closing: Vec<&'t str>,
+}
+
+struct Formatter<'t> {
+    text: &'t str,
+    comments: Vec<Range>,
+    out: String,
+}
+
+impl<'t> Formatter<'t> {
+    fn comments_in(&self, start: usize, end: usize) -> Vec<&'t str> {
+        let text = self.text;
+        self.comments
+            .iter()
+            .filter(|range| range.start >= start && range.end <= end)
+            .map(|range| text[range.start..range.end].trim_end())
+            .collect()
+    }
+
+    /// The comments in `start..end`, with `BLANK_LINE` wherever there are
+    /// blank lines before, between or after them.
+    fn lines_in(&self, start: usize, end: usize) -> Vec<&'t str> {
+        let text = self.text;
+        let mut lines = vec![];
+        let mut cursor = start;
+        for range in self
+            .comments
+            .iter()
+            .filter(|range| range.start >= start && range.end <= end)
+        {
+            if has_blank_line(&text[cursor..range.start]) {
+                lines.push(BLANK_LINE);
+            }
+            lines.push(text[range.start..range.end].trim_end());
+            cursor = range.end;
+        }
+        if has_blank_line(&text[cursor..end]) {
+            lines.push(BLANK_LINE);
+        }
+        lines
+    }
+
+    fn is_on_same_line(&self, end: usize, comment: &str) -> bool {
+        // `comment` is a slice of `self.text` that starts after `end`.
+        let comment_start = comment.as_ptr() as usize - self.text.as_ptr() as usize;
+        !self.text[end..comment_start].contains('\n')
+    }
+
+    /// A comment before an item belongs to it, unless it is on the same line
+    /// as the end of the previous item.
+    fn item_comments(&self, container: Range, items: &[Item<'_, '_>]) -> ItemComments<'t> {
+        let mut comments = ItemComments {
+            leading: vec![vec![]; items.len()],
+            trailing: vec![vec![]; items.len()],
+            closing: vec![],
+        };
+        // Skip the opening bracket.
+        let mut region_start = container.start + 1;
+        let mut previous: Option<(usize, usize)> = None;
+        for (i, item) in items.iter().enumerate() {
+            let range = item.range();
+            for line in self.lines_in(region_start, range.start) {
+                match previous {
+                    Some((j, end))
+                        if line != BLANK_LINE
+                            && comments.leading[i].is_empty()
+                            && self.is_on_same_line(end, line) =>
+                    {
+                        comments.trailing[j].push(line);
+                    }
+                    _ => comments.leading[i].push(line),
+                }
+            }
+            // E.g., between the name of a field and its value.
+            comments.leading[i].extend(self.comments_in(range.start, item.value_start()));
+            region_start = range.end;
+            previous = Some((i, range.end));
+        }
+        for line in self.lines_in(region_start, container.end) {
+            match previous {
+                Some((j, end))
+                    if line != BLANK_LINE
+                        && comments.closing.is_empty()
+                        && self.is_on_same_line(end, line) =>
+                {
+                    comments.trailing[j].push(line);
+                }
+                _ => comments.closing.push(line),
+            }
+        }
+        // No blank line before the closing bracket.
+        if comments.closing.last() == Some(&BLANK_LINE) {
+            comments.closing.pop();
+        }
+        comments
+    }
+
+    fn write_indent(&mut self, depth: usize) {
+        for _ in 0..depth {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    fn write_line(&mut self, line: &str, depth: usize) {
+        if line != BLANK_LINE {
+            self.write_indent(depth);
+            self.out.push_str(line);
+        }
+        self.out.push('\n');
+    }
+
+    fn write_value(&mut self, value: &ast::Value<'_>, level: Level, depth: usize) {
+        match value {
+            ast::Value::Object(object) => {
+                let items = object
+                    .properties
+                    .iter()
+                    .map(Item::Field)
+                    .collect::<Vec<_>>();
+                let order = field_order(&object.properties, level);
+                self.write_items(('{', '}'), object.range, &items, &order, level, depth);
+            }
+            ast::Value::Array(array) => {
+                let items = array.elements.iter().map(Item::Element).collect::<Vec<_>>();
+                let order = (0..items.len()).collect::<Vec<_>>();
+                self.write_items(('[', ']'), array.range, &items, &order, level, depth);
+            }
+            ast::Value::StringLit(lit) => {
+                // Single-quoted strings become double-quoted.
+                self.out
+                    .push_str(&serde_json::Value::from(lit.value.as_ref()).to_string());
+            }
+            ast::Value::NumberLit(lit) => self.out.push_str(lit.value),
+            ast::Value::BooleanLit(lit) => {
+                self.out.push_str(if lit.value { "true" } else { "false" });
+            }
+            ast::Value::NullKeyword(_) => self.out.push_str("null"),
+        }
+    }
+
+    fn write_items(
+        &mut self,
+        (open, close): (char, char),
+        container: Range,
+        items: &[Item<'_, '_>],
+        order: &[usize],
+        level: Level,
+        depth: usize,
+    ) {
+        let comments = self.item_comments(container, items);
+        self.out.push(open);
+        if items.is_empty() && comments.closing.is_empty() {
+            self.out.push(close);
+            return;
+        }
+        self.out.push('\n');
+        for (n, &i) in order.iter().enumerate() {
+            for (k, line) in comments.leading[i].iter().enumerate() {
+                // No blank line after the opening bracket.
+                if n == 0 && k == 0 && *line == BLANK_LINE {
+                    continue;
+                }
+                self.write_line(line, depth + 1);
+            }
+            self.write_indent(depth + 1);
+            match &items[i] {
+                Item::Field(prop) => {
+                    let name = prop.name.as_str();
+                    self.out
+                        .push_str(&serde_json::Value::from(name).to_string());
+                    self.out.push_str(": ");
+                    self.write_value(&prop.value, child_level(level, name), depth + 1);
+                }
+                Item::Element(value) => self.write_value(value, Level::Other, depth + 1),
+            }
+            if n + 1 < order.len() {
+                self.out.push(',');
+            }
+            for comment in &comments.trailing[i] {
+                self.out.push(' ');
+                self.out.push_str(comment);
+            }
+            self.out.push('\n');
+        }
+        for (k, line) in comments.closing.iter().enumerate() {
+            if items.is_empty() && k == 0 && *line == BLANK_LINE {
+                continue;
+            }
+            self.write_line(line, depth + 1);
+        }
+        self.write_indent(depth);
+        self.out.push(close);
+    }
+}
+
+/// Whether `gap`, which has no comments, spans a line with nothing on it.
+fn has_blank_line(gap: &str) -> bool {
+    let lines = gap.split('\n').collect::<Vec<_>>();
+    lines.len() > 2
+        && lines[1..lines.len() - 1]
+            .iter()
+            .any(|line| line.trim().is_empty())
+}
+
+fn child_level(level: Level, name: &str) -> Level {
+    match level {
+        Level::Root if name == "platforms" => Level::Platforms,
+        Level::Platforms => Level::ArtifactEntry,
+        Level::Root | Level::ArtifactEntry | Level::Other => Level::Other,
+    }
+}
+
+/// The indexes of `properties` in canonical order.
+fn field_order(properties: &[ast::ObjectProp<'_>], level: Level) -> Vec<usize> {
+    let mut order = (0..properties.len()).collect::<Vec<_>>();
+    let known_fields: &[&str] = match level {
+        Level::Platforms => {
+            order.sort_by_key(|&i| properties[i].name.as_str());
+            return order;
+        }
+        Level::Root => &TOP_LEVEL_FIELDS,
+        Level::ArtifactEntry => &ARTIFACT_ENTRY_FIELDS,
+        Level::Other => return order,
+    };
+    // Unknown fields go last, in their original order.
+    order.sort_by_key(|&i| {
+        let name = properties[i].name.as_str();
+        known_fields
+            .iter()
+            .position(|field| *field == name)
+            .unwrap_or(known_fields.len())
+    });
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMATTED: &str = r#"#!/usr/bin/env dotslash
+// The header comment.
+{
+  "name": "tool",
+  "platforms": {
+    // Apple Silicon.
+    "macos-aarch64": {
+      "size": 5,
+      "hash": "blake3",
+      "digest": "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+      "format": "tar.gz",
+      "path": "tool",
+      "providers": [
+        {
+          "url": "https://example.com/macos.tar.gz"
+        }, // The only provider.
+        /* Another provider. */
+        {
+          "type": "github-release",
+          "repo": "example/tool",
+          "tag": "v1.0",
+          "name": "macos.tar.gz"
+        }
+      ],
+      "readonly": false,
+      "x-note": 1.50
+    },
+    "windows-x86_64": {
+      "size": 5,
+      "hash": "blake3",
+      "digest": "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb",
+      "path": "tool.exe",
+      "providers": []
+    }
+  }
+  // The end.
+}
+"#;
+
+    #[test]
+    fn formats_canonically() -> anyhow::Result<()> {
+        let messy = r#"#!/usr/bin/env dotslash
+// The header comment.
+{
+    platforms: {
+        'windows-x86_64': {"path": "tool.exe", "size": 5, "hash": "blake3",
+          "digest": "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb",
+          "providers": [ ],
+        },
+        // Apple Silicon.
+        "macos-aarch64": {
+            "readonly": false,
+            "x-note": 1.50,
+            "path": "tool",
+            "providers": [{"url": "https://example.com/macos.tar.gz"}, // The only provider.
+                /* Another provider. */
+                {"type": "github-release", "repo": "example/tool", "tag": "v1.0", "name": "macos.tar.gz"},
+            ],
+            "format": "tar.gz", "size": 5, "hash": "blake3",
+            "digest": "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+        },
+    },
+    "name": "tool",
+    // The end.
+}"#;
+        assert_eq!(format_dotslash_file(messy)?, FORMATTED);
+        // Formatting is idempotent.
+        assert_eq!(format_dotslash_file(FORMATTED)?, FORMATTED);
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_single_blank_lines() -> anyhow::Result<()> {
+        let input = "#!/usr/bin/env dotslash\n\n\n// A comment.\n\n{\n\n  \"name\": \"tool\", // Trailing.\n\n\n  // About platforms.\n  \"platforms\": {\n\n  }\n\n}\n\n";
+        let expected = "#!/usr/bin/env dotslash\n\n// A comment.\n\n{\n  \"name\": \"tool\", // Trailing.\n\n  // About platforms.\n  \"platforms\": {}\n}\n";
+        assert_eq!(format_dotslash_file(input)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn requires_header() {
+        let err = format_dotslash_file("{}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "DotSlash file must start with `#!/usr/bin/env dotslash`",
+        );
+    }
+}
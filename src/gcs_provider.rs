@@ -16,6 +16,8 @@ use serde::Deserialize;
 use serde_json::Value;
 
 use crate::config::ArtifactEntry;
+use crate::global_config::GlobalConfig;
+use crate::policy;
 use crate::provider::Provider;
 use crate::util::CommandDisplay;
 use crate::util::CommandStderrDisplay;
@@ -24,7 +26,8 @@ use crate::util::FileLock;
 pub struct GcsProvider {}
 
 #[derive(Deserialize, Debug)]
-struct GcsProviderConfig {
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct GcsProviderConfig {
     bucket: String,
     object: String,
 }
@@ -36,6 +39,10 @@ const TOKEN_ENV_VARS: &[&str] = &[
     "GOOGLE_OAUTH_ACCESS_TOKEN",
 ];
 
+fn object_url(bucket: &str, object: &str) -> String {
+    format!("https://storage.googleapis.com/{}/{}", bucket, object)
+}
+
 fn get_bearer_token() -> Option<String> {
     // First, check environment variables.
     let from_env = TOKEN_ENV_VARS
@@ -68,7 +75,7 @@ impl Provider for GcsProvider {
         _: &ArtifactEntry,
     ) -> anyhow::Result<()> {
         let GcsProviderConfig { bucket, object } = <_>::deserialize(provider_config)?;
-        let url = format!("https://storage.googleapis.com/{}/{}", bucket, object,);
+        let url = object_url(&bucket, &object);
         let output_arg = destination.to_str().unwrap();
 
         let mut command = Command::new("curl");
@@ -78,6 +85,8 @@ impl Provider for GcsProvider {
             command.args(["-H", &format!("Authorization: Bearer {}", token)]);
         }
         command.args(["--output", output_arg]);
+        // So that a redirect cannot get around the policies.
+        command.args(["--write-out", "%{url_effective}"]);
         command.arg(&url);
 
         let output = command
@@ -93,6 +102,17 @@ impl Provider for GcsProvider {
             .with_context(|| format!("{}", CommandDisplay::new(&command)))
             .context("curl failed to download from GCS");
         }
+        let final_url = String::from_utf8_lossy(&output.stdout);
+        policy::check_redirect(&GlobalConfig::get().policies, &final_url)
+            .map_err(|reason| {
+                anyhow::format_err!("redirected to a URL that is not allowed: {reason}")
+            })
+            .with_context(|| format!("{}", CommandDisplay::new(&command)))?;
         Ok(())
     }
+
+    fn artifact_url(&self, provider_config: &Value) -> anyhow::Result<Option<String>> {
+        let GcsProviderConfig { bucket, object } = <_>::deserialize(provider_config)?;
+        Ok(Some(object_url(&bucket, &object)))
+    }
 }
@@ -8,6 +8,7 @@
  * above-listed licenses.
  */
 
+use std::env;
 use std::path::Path;
 use std::process::Command;
 
@@ -24,7 +25,8 @@ use crate::util::FileLock;
 pub struct GitHubReleaseProvider {}
 
 #[derive(Deserialize, Debug)]
-struct GitHubReleaseProviderConfig {
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct GitHubReleaseProviderConfig {
     tag: String,
     repo: String,
     name: String,
@@ -70,6 +72,32 @@ impl Provider for GitHubReleaseProvider {
 
         Ok(())
     }
+
+    /// The URL that `gh` downloads the release asset from, for public
+    /// repositories.
+    fn artifact_url(&self, provider_config: &Value) -> anyhow::Result<Option<String>> {
+        let GitHubReleaseProviderConfig { tag, repo, name } = <_>::deserialize(provider_config)?;
+        let gh_host = env::var("GH_HOST").ok();
+        let (host, repo) = split_repo_host(&repo, gh_host.as_deref());
+        Ok(Some(format!(
+            "https://{host}/{repo}/releases/download/{tag}/{name}"
+        )))
+    }
+}
+
+/// Splits `repo` into the host that `gh` uses for it and `OWNER/REPO`. Like
+/// `gh --repo`, `repo` can be `HOST/OWNER/REPO`; otherwise, the host is
+/// `gh_host` (from `GH_HOST`) or github.com.
+fn split_repo_host<'a>(repo: &'a str, gh_host: Option<&'a str>) -> (&'a str, &'a str) {
+    match repo.split_once('/') {
+        Some((host, owner_repo)) if owner_repo.contains('/') => (host, owner_repo),
+        _ => (
+            gh_host
+                .filter(|host| !host.is_empty())
+                .unwrap_or("github.com"),
+            repo,
+        ),
+    }
 }
 
 /// We want the functionality comparable to regex::escape() without pulling in
@@ -95,6 +123,29 @@ fn regex_escape(s: &str) -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn split_repo_host_like_gh() {
+        assert_eq!(
+            split_repo_host("facebook/dotslash", None),
+            ("github.com", "facebook/dotslash"),
+        );
+        assert_eq!(
+            split_repo_host("facebook/dotslash", Some("ghe.example.com")),
+            ("ghe.example.com", "facebook/dotslash"),
+        );
+        assert_eq!(
+            split_repo_host("facebook/dotslash", Some("")),
+            ("github.com", "facebook/dotslash"),
+        );
+        assert_eq!(
+            split_repo_host(
+                "ghe.example.com/facebook/dotslash",
+                Some("other.example.com")
+            ),
+            ("ghe.example.com", "facebook/dotslash"),
+        );
+    }
+
     #[test]
     fn regex_escape_no_quotable_chars() {
         assert_eq!("foo", regex_escape("foo"));
@@ -0,0 +1,590 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! DotSlash's own settings, as opposed to those in a DotSlash file (see
+//! `config`). Each setting is taken from the first of these that has it:
+//!
+//! 1. Its environment variable, e.g., `DOTSLASH_CACHE`.
+//! 2. The user config file, `$CONFIG_DIR/dotslash/config.json`, or the file
+//!    named by `DOTSLASH_CONFIG`.
+//! 3. The system config file, `/etc/dotslash/config.json` (on Windows,
+//!    `%ProgramData%\dotslash\config.json`).
+//! 4. The built-in default.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::curl::DOTSLASH_CURL_CONNECT_TIMEOUT_SEC_ENV;
+use crate::curl::DOTSLASH_CURL_TIMEOUT_SEC_ENV;
+use crate::dotslash_cache::DOTSLASH_CACHE_ENV;
+use crate::download::DOTSLASH_OFFLINE_ENV;
+use crate::mirror::MirrorRule;
+use crate::policy::Policy;
+use crate::signature::PublicKey;
+use crate::util::ListOf;
+use crate::util::fs_ctx;
+
+/// Overrides the location of the user config file.
+pub const DOTSLASH_CONFIG_ENV: &str = "DOTSLASH_CONFIG";
+
+/// Set to `curl` to always shell out to `curl`, even when DotSlash was built
+/// with the `native-http` feature.
+pub const DOTSLASH_HTTP_CLIENT_ENV: &str = "DOTSLASH_HTTP_CLIENT";
+
+const CONFIG_FILE_NAME: &str = "config.json";
+
+/// The contents of a config file. Every field is optional.
+#[derive(Deserialize, Debug)]
+struct ConfigFileData {
+    cache_dir: Option<PathBuf>,
+    timeout_sec: Option<u64>,
+    connect_timeout_sec: Option<u64>,
+    offline: Option<bool>,
+    http_client: Option<String>,
+    mirrors: Option<Vec<MirrorRule>>,
+    policy: Option<Policy>,
+    trusted_keys: Option<Vec<PublicKey>>,
+    require_signature: Option<bool>,
+    /// Keys that this version of DotSlash does not know, e.g., because they
+    /// are for a newer one. They are ignored.
+    #[serde(flatten)]
+    unknown: BTreeMap<String, Value>,
+}
+
+/// The keys of a config file that fetching must not go ahead without.
+const SAFEGUARD_KEYS: &[&str] = &["policy", "require_signature"];
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SettingSource {
+    Default,
+    File { path: PathBuf },
+    Env { name: &'static str },
+}
+
+impl fmt::Display for SettingSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => f.write_str("default"),
+            Self::File { path } => write!(f, "`{}`", path.display()),
+            Self::Env { name } => write!(f, "`{name}`"),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Setting<T> {
+    pub value: T,
+    pub source: SettingSource,
+}
+
+impl<T> Setting<T> {
+    fn env(name: &'static str, value: T) -> Self {
+        Self {
+            value,
+            source: SettingSource::Env { name },
+        }
+    }
+
+    fn default(value: T) -> Self {
+        Self {
+            value,
+            source: SettingSource::Default,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigFileStatus {
+    Loaded,
+    NotFound,
+    /// The file is ignored, e.g., because it is not valid JSON. If it may
+    /// set a policy or require signatures, nothing is fetched either (see
+    /// `GlobalConfig::try_get`).
+    Invalid(String),
+}
+
+#[derive(Serialize, Debug)]
+pub struct ConfigFile {
+    pub path: PathBuf,
+    pub status: ConfigFileStatus,
+    /// Keys in the file that were ignored (see `ConfigFileData::unknown`).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_keys: Vec<String>,
+    /// Whether the file is `Invalid` and has (or, if it is not even JSON,
+    /// may have) one of `SAFEGUARD_KEYS`.
+    #[serde(skip)]
+    lost_safeguards: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct GlobalConfig {
+    /// `None` to use the platform's cache directory.
+    pub cache_dir: Setting<Option<PathBuf>>,
+    /// Maximum time for a whole transfer. `None` for no limit.
+    pub timeout_sec: Setting<Option<u64>>,
+    /// Maximum time to connect. `None` for no limit.
+    pub connect_timeout_sec: Setting<Option<u64>>,
+    /// See `download::is_offline`.
+    pub offline: Setting<bool>,
+    /// `curl` to always shell out to `curl` (see `native_http`).
+    pub http_client: Setting<Option<String>>,
+    /// See `mirror`. Only set in config files.
+    pub mirrors: Setting<Vec<MirrorRule>>,
+    /// See `policy`. Unlike other settings, the policies in every config
+    /// file apply, so a user config file cannot loosen the system one.
+    pub policies: Vec<Setting<Policy>>,
+    /// See `signature`. Only set in config files. If signatures are
+    /// required, only the file that requires them and the files trusted
+    /// more than it are consulted, so a user config file cannot add keys
+    /// that the system config file does not trust.
+    pub trusted_keys: Setting<Vec<PublicKey>>,
+    /// Like `policies`, any config file can require signatures and none can
+    /// undo that. The source is the most trusted file that requires them.
+    pub require_signature: Setting<bool>,
+    /// The config files that were consulted, highest precedence first.
+    pub files: Vec<ConfigFile>,
+}
+
+impl GlobalConfig {
+    /// The effective config, which is loaded the first time it is needed.
+    /// Config files (and keys in them) that cannot be used are reported once
+    /// on stderr and otherwise ignored: a typo in a config file, or a key for
+    /// a newer DotSlash, should not stop every DotSlash file from running.
+    pub fn get() -> &'static Self {
+        static GLOBAL_CONFIG: OnceLock<GlobalConfig> = OnceLock::new();
+        GLOBAL_CONFIG.get_or_init(|| {
+            let config = Self::load(&config_file_paths(), |name| env::var_os(name));
+            for file in &config.files {
+                if let ConfigFileStatus::Invalid(err) = &file.status {
+                    eprintln!(
+                        "dotslash warning: ignoring config file `{}`: {err}",
+                        file.path.display()
+                    );
+                } else if !file.unknown_keys.is_empty() {
+                    eprintln!(
+                        "dotslash warning: ignoring unknown keys in config file `{}`: {}",
+                        file.path.display(),
+                        ListOf::new(&file.unknown_keys),
+                    );
+                }
+            }
+            config
+        })
+    }
+
+    /// Like `get`, but fails if an ignored config file may have set a policy
+    /// or required signatures, so that fetching cannot go ahead without them.
+    pub fn try_get() -> anyhow::Result<&'static Self> {
+        let config = Self::get();
+        config.check()?;
+        Ok(config)
+    }
+
+    fn check(&self) -> anyhow::Result<()> {
+        match self.files.iter().find_map(|file| match &file.status {
+            ConfigFileStatus::Invalid(err) if file.lost_safeguards => Some((&file.path, err)),
+            _ => None,
+        }) {
+            Some((path, err)) => Err(anyhow::format_err!(
+                "invalid config file `{}`, which may set {}: {err}",
+                path.display(),
+                ListOf::new(SAFEGUARD_KEYS),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// `paths` are the config files, highest precedence first.
+    fn load(paths: &[PathBuf], env_var: impl Fn(&str) -> Option<OsString>) -> Self {
+        let mut files = vec![];
+        let mut layers = vec![];
+        for path in paths {
+            let mut file = ConfigFile {
+                path: path.clone(),
+                status: ConfigFileStatus::NotFound,
+                unknown_keys: vec![],
+                lost_safeguards: false,
+            };
+            match read_config_file(path) {
+                Ok(Some(data)) => {
+                    file.status = ConfigFileStatus::Loaded;
+                    file.unknown_keys = data.unknown.keys().cloned().collect();
+                    layers.push((path, data));
+                }
+                Ok(None) => {}
+                Err((err, lost_safeguards)) => {
+                    file.status = ConfigFileStatus::Invalid(format!("{err:#}"));
+                    file.lost_safeguards = lost_safeguards;
+                }
+            }
+            files.push(file);
+        }
+
+        let cache_dir = match env_var(DOTSLASH_CACHE_ENV) {
+            Some(value) => Setting::env(DOTSLASH_CACHE_ENV, Some(PathBuf::from(value))),
+            None => from_files(&layers, |data| data.cache_dir.clone().map(Some))
+                .unwrap_or_else(|| Setting::default(None)),
+        };
+
+        // Like curl, `0` means no limit. Unparsable values are ignored.
+        let timeout =
+            |name: &'static str, get: fn(&ConfigFileData) -> Option<u64>| match env_var(name)
+                .and_then(|value| value.to_str()?.parse::<u64>().ok())
+            {
+                Some(value) => Setting::env(name, Some(value).filter(|&v| v > 0)),
+                None => from_files(&layers, |data| {
+                    get(data).map(|v| Some(v).filter(|&v| v > 0))
+                })
+                .unwrap_or_else(|| Setting::default(None)),
+            };
+        let timeout_sec = timeout(DOTSLASH_CURL_TIMEOUT_SEC_ENV, |data| data.timeout_sec);
+        let connect_timeout_sec = timeout(DOTSLASH_CURL_CONNECT_TIMEOUT_SEC_ENV, |data| {
+            data.connect_timeout_sec
+        });
+
+        let offline = match env_var(DOTSLASH_OFFLINE_ENV) {
+            Some(value) => Setting::env(
+                DOTSLASH_OFFLINE_ENV,
+                !matches!(value.to_str(), Some("" | "0" | "false")),
+            ),
+            None => {
+                from_files(&layers, |data| data.offline).unwrap_or_else(|| Setting::default(false))
+            }
+        };
+
+        let http_client = match env_var(DOTSLASH_HTTP_CLIENT_ENV) {
+            Some(value) => Setting::env(
+                DOTSLASH_HTTP_CLIENT_ENV,
+                Some(value.to_string_lossy().into_owned()),
+            ),
+            None => from_files(&layers, |data| data.http_client.clone().map(Some))
+                .unwrap_or_else(|| Setting::default(None)),
+        };
+
+        let mirrors = from_files(&layers, |data| data.mirrors.clone())
+            .unwrap_or_else(|| Setting::default(vec![]));
+
+        let policies = layers
+            .iter()
+            .filter_map(|(path, data)| {
+                data.policy.clone().map(|value| Setting {
+                    value,
+                    source: SettingSource::File {
+                        path: (*path).clone(),
+                    },
+                })
+            })
+            .collect();
+
+        // `layers` go from least to most trusted.
+        let requiring_layer = layers
+            .iter()
+            .rposition(|(_, data)| data.require_signature == Some(true));
+        let require_signature = match requiring_layer {
+            Some(index) => Setting {
+                value: true,
+                source: SettingSource::File {
+                    path: layers[index].0.clone(),
+                },
+            },
+            None => Setting::default(false),
+        };
+        let trusted_keys = from_files(&layers[requiring_layer.unwrap_or(0)..], |data| {
+            data.trusted_keys.clone()
+        })
+        .unwrap_or_else(|| Setting::default(vec![]));
+
+        Self {
+            cache_dir,
+            timeout_sec,
+            connect_timeout_sec,
+            offline,
+            http_client,
+            mirrors,
+            policies,
+            trusted_keys,
+            require_signature,
+            files,
+        }
+    }
+}
+
+/// The value from the highest precedence config file that has one.
+fn from_files<T>(
+    layers: &[(&PathBuf, ConfigFileData)],
+    get: impl Fn(&ConfigFileData) -> Option<T>,
+) -> Option<Setting<T>> {
+    layers.iter().find_map(|(path, data)| {
+        get(data).map(|value| Setting {
+            value,
+            source: SettingSource::File {
+                path: (*path).clone(),
+            },
+        })
+    })
+}
+
+/// Returns `Ok(None)` if the file does not exist. On error, also returns
+/// whether the file may have had one of `SAFEGUARD_KEYS`, which is assumed
+/// if it cannot even be read as JSON.
+fn read_config_file(path: &PathBuf) -> Result<Option<ConfigFileData>, (anyhow::Error, bool)> {
+    let contents = match fs_ctx::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err((err.into(), true)),
+    };
+    let value = serde_json::from_str::<Value>(&contents).map_err(|err| (err.into(), true))?;
+    ConfigFileData::deserialize(&value)
+        .map(Some)
+        .map_err(|err| {
+            let lost_safeguards = SAFEGUARD_KEYS.iter().any(|key| value.get(key).is_some());
+            (err.into(), lost_safeguards)
+        })
+}
+
+/// The config files, highest precedence first.
+fn config_file_paths() -> Vec<PathBuf> {
+    let mut paths = vec![];
+
+    match env::var_os(DOTSLASH_CONFIG_ENV) {
+        Some(path) => paths.push(PathBuf::from(path)),
+        None => {
+            if let Some(config_dir) = dirs::config_dir() {
+                paths.push(config_dir.join("dotslash").join(CONFIG_FILE_NAME));
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    paths.push(PathBuf::from("/etc/dotslash").join(CONFIG_FILE_NAME));
+    #[cfg(windows)]
+    if let Some(program_data) = env::var_os("ProgramData") {
+        paths.push(
+            PathBuf::from(program_data)
+                .join("dotslash")
+                .join(CONFIG_FILE_NAME),
+        );
+    }
+
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn layers_env_over_user_over_system() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let user = temp_dir.path().join("user.json");
+        let system = temp_dir.path().join("system.json");
+        let missing = temp_dir.path().join("missing.json");
+        fs::write(&user, r#"{"timeout_sec": 10, "offline": false}"#)?;
+        fs::write(
+            &system,
+            r#"{"timeout_sec": 20, "connect_timeout_sec": 5, "offline": true, "cache_dir": "/c"}"#,
+        )?;
+
+        let config = GlobalConfig::load(&[missing, user.clone(), system.clone()], |name| {
+            (name == DOTSLASH_CURL_CONNECT_TIMEOUT_SEC_ENV).then(|| "0".into())
+        });
+
+        assert_eq!(config.timeout_sec.value, Some(10));
+        assert_eq!(
+            config.timeout_sec.source,
+            SettingSource::File { path: user.clone() },
+        );
+        // `0` disables the timeout from the system config.
+        assert_eq!(config.connect_timeout_sec.value, None);
+        assert_eq!(
+            config.connect_timeout_sec.source,
+            SettingSource::Env {
+                name: DOTSLASH_CURL_CONNECT_TIMEOUT_SEC_ENV,
+            },
+        );
+        assert!(!config.offline.value);
+        assert_eq!(config.offline.source, SettingSource::File { path: user });
+        assert_eq!(config.cache_dir.value, Some(PathBuf::from("/c")));
+        assert_eq!(
+            config.cache_dir.source,
+            SettingSource::File { path: system }
+        );
+        assert_eq!(config.http_client.value, None);
+        assert_eq!(config.http_client.source, SettingSource::Default);
+        assert!(matches!(config.files[0].status, ConfigFileStatus::NotFound));
+        Ok(())
+    }
+
+    #[test]
+    fn policies_from_every_file_apply() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let user = temp_dir.path().join("user.json");
+        let system = temp_dir.path().join("system.json");
+        fs::write(
+            &user,
+            r#"{"policy": {"allow_provider_types": ["http", "s3"]}}"#,
+        )?;
+        fs::write(&system, r#"{"policy": {"deny_provider_types": ["s3"]}}"#)?;
+
+        let config = GlobalConfig::load(&[user.clone(), system.clone()], |_| None);
+
+        let sources = config
+            .policies
+            .iter()
+            .map(|policy| policy.source.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sources,
+            [
+                SettingSource::File { path: user },
+                SettingSource::File { path: system },
+            ],
+        );
+        let allowed = |provider_type| {
+            config
+                .policies
+                .iter()
+                .all(|policy| policy.value.check_provider_type(provider_type).is_ok())
+        };
+        assert!(allowed("http"));
+        assert!(!allowed("s3"));
+        assert!(!allowed("gcs"));
+        Ok(())
+    }
+
+    #[test]
+    fn user_file_cannot_unrequire_signatures() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let user = temp_dir.path().join("user.json");
+        let system = temp_dir.path().join("system.json");
+        fs::write(&user, r#"{"require_signature": false}"#)?;
+        fs::write(&system, r#"{"require_signature": true}"#)?;
+
+        let config = GlobalConfig::load(&[user, system.clone()], |_| None);
+
+        assert!(config.require_signature.value);
+        assert_eq!(
+            config.require_signature.source,
+            SettingSource::File { path: system },
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn required_signatures_only_trust_keys_from_trusted_files() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let user = temp_dir.path().join("user.json");
+        let system = temp_dir.path().join("system.json");
+        let user_key = "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+        let system_key = "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c";
+        fs::write(
+            &user,
+            format!(r#"{{"trusted_keys": ["{user_key}"], "require_signature": true}}"#),
+        )?;
+
+        // Without a requirement in the system file, the user file decides.
+        fs::write(&system, format!(r#"{{"trusted_keys": ["{system_key}"]}}"#))?;
+        let config = GlobalConfig::load(&[user.clone(), system.clone()], |_| None);
+        assert_eq!(config.trusted_keys.value, [user_key.parse::<PublicKey>()?]);
+        assert_eq!(
+            config.require_signature.source,
+            SettingSource::File { path: user.clone() },
+        );
+
+        fs::write(
+            &system,
+            format!(r#"{{"trusted_keys": ["{system_key}"], "require_signature": true}}"#),
+        )?;
+        let config = GlobalConfig::load(&[user.clone(), system.clone()], |_| None);
+        assert_eq!(
+            config.trusted_keys.value,
+            [system_key.parse::<PublicKey>()?]
+        );
+        assert_eq!(
+            config.require_signature.source,
+            SettingSource::File {
+                path: system.clone()
+            },
+        );
+
+        // The user file cannot supply the keys for the system requirement.
+        fs::write(&system, r#"{"require_signature": true}"#)?;
+        let config = GlobalConfig::load(&[user, system], |_| None);
+        assert!(config.require_signature.value);
+        assert!(config.trusted_keys.value.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_keys_are_ignored() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let user = temp_dir.path().join("user.json");
+        fs::write(&user, r#"{"offline": true, "from_the_future": 1}"#)?;
+
+        let config = GlobalConfig::load(&[user], |_| None);
+
+        assert!(config.offline.value);
+        assert!(matches!(config.files[0].status, ConfigFileStatus::Loaded));
+        assert_eq!(config.files[0].unknown_keys, ["from_the_future"]);
+        config.check()?;
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_file_only_fails_check_if_it_has_safeguards() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let user = temp_dir.path().join("user.json");
+        let system = temp_dir.path().join("system.json");
+        fs::write(&user, r#"{"offline": true}"#)?;
+
+        fs::write(&system, r#"{"timeout_sec": "soon"}"#)?;
+        let config = GlobalConfig::load(&[user.clone(), system.clone()], |_| None);
+        assert!(config.offline.value);
+        assert!(matches!(
+            config.files[1].status,
+            ConfigFileStatus::Invalid(_)
+        ));
+        config.check()?;
+
+        for contents in [
+            r#"{"policy": {"deny_provider_types": "s3"}}"#,
+            r#"{"require_signature": true, "timeout_sec": "soon"}"#,
+            // Without valid JSON, there is no telling what the file sets.
+            r#"{"offline": true,}"#,
+        ] {
+            fs::write(&system, contents)?;
+            let config = GlobalConfig::load(&[user.clone(), system.clone()], |_| None);
+            assert!(config.policies.is_empty());
+            assert!(!config.require_signature.value);
+            let err = config.check().unwrap_err().to_string();
+            assert!(
+                err.starts_with(&format!(
+                    "invalid config file `{}`, which may set `policy`, `require_signature`: ",
+                    system.display()
+                )),
+                "{err}",
+            );
+        }
+        Ok(())
+    }
+}
@@ -8,6 +8,7 @@
  * above-listed licenses.
  */
 
+use std::io::Read;
 use std::path::Path;
 
 use anyhow::Context as _;
@@ -23,7 +24,8 @@ use crate::util::FileLock;
 pub struct HttpProvider {}
 
 #[derive(Deserialize, Debug)]
-struct HttpProviderConfig {
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct HttpProviderConfig {
     url: String,
 }
 
@@ -37,17 +39,37 @@ impl Provider for HttpProvider {
     ) -> anyhow::Result<()> {
         let HttpProviderConfig { url } = <_>::deserialize(provider_config)?;
         let curl_cmd = CurlCommand::new(url.as_ref());
-        // Currently, we always disable the progress bar, but we plan to add a
-        // configuration option to enable it.
-        let show_progress = false;
-        let fetch_context = FetchContext {
-            artifact_name: url.as_str(),
-            content_length: artifact_entry.size,
-            show_progress,
-        };
         curl_cmd
-            .get_request(destination, &fetch_context)
+            .get_request(destination, &fetch_context(&url, artifact_entry))
             .with_context(|| format!("failed to fetch `{}`", url))?;
         Ok(())
     }
+
+    fn open_artifact_stream(
+        &self,
+        provider_config: &Value,
+        _fetch_lock: &FileLock,
+        artifact_entry: &ArtifactEntry,
+    ) -> anyhow::Result<Option<Box<dyn Read>>> {
+        let HttpProviderConfig { url } = <_>::deserialize(provider_config)?;
+        CurlCommand::new(url.as_ref())
+            .get_stream(&fetch_context(&url, artifact_entry))
+            .with_context(|| format!("failed to fetch `{}`", url))
+    }
+
+    fn artifact_url(&self, provider_config: &Value) -> anyhow::Result<Option<String>> {
+        let HttpProviderConfig { url } = <_>::deserialize(provider_config)?;
+        Ok(Some(url))
+    }
+}
+
+fn fetch_context<'a>(url: &'a str, artifact_entry: &ArtifactEntry) -> FetchContext<'a> {
+    // Currently, we always disable the progress bar, but we plan to add a
+    // configuration option to enable it.
+    let show_progress = false;
+    FetchContext {
+        artifact_name: url,
+        content_length: artifact_entry.size,
+        show_progress,
+    }
 }
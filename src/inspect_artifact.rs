@@ -0,0 +1,398 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! Inspects a downloaded artifact to fill in the `format` and `path` of its
+//! entry in a DotSlash file, for the commands that generate DotSlash files.
+
+use std::fmt;
+use std::io;
+use std::io::BufReader;
+use std::io::Read;
+use std::path::Path;
+
+use bzip2::read::BzDecoder;
+use flate2::read::GzDecoder;
+#[cfg(not(dotslash_internal))]
+use liblzma::read::XzDecoder;
+use serde::Deserialize;
+use serde::Serialize;
+use zstd::stream::read::Decoder as ZstdDecoder;
+
+use crate::fetch_method::ArtifactFormat;
+use crate::util::ListOf;
+use crate::util::fs_ctx;
+use crate::util::unarchive;
+use crate::util::unarchive::ArchivedFile;
+
+const BZIP2_MAGIC: &[u8] = b"BZh";
+const GZIP_MAGIC: &[u8] = b"\x1f\x8b";
+const XZ_MAGIC: &[u8] = b"\xfd7zXZ\x00";
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const ZIP_EMPTY_MAGIC: &[u8] = b"PK\x05\x06";
+const ZSTD_MAGIC: &[u8] = b"\x28\xb5\x2f\xfd";
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+/// 32- and 64-bit, in either byte order, and universal binaries.
+const MACH_O_MAGICS: [&[u8]; 5] = [
+    b"\xfe\xed\xfa\xce",
+    b"\xfe\xed\xfa\xcf",
+    b"\xce\xfa\xed\xfe",
+    b"\xcf\xfa\xed\xfe",
+    b"\xca\xfe\xba\xbe",
+];
+const PE_MAGIC: &[u8] = b"MZ";
+const SCRIPT_MAGIC: &[u8] = b"#!";
+
+/// Both POSIX (`ustar\0`) and GNU (`ustar  `) tar headers have this magic
+/// at this offset.
+const USTAR_MAGIC: &[u8] = b"ustar";
+const USTAR_MAGIC_OFFSET: usize = 257;
+const TAR_HEADER_LEN: u64 = 512;
+
+/// What the first bytes of a file say about its content. Compressed data is
+/// identified by its compression alone: telling a compressed tar from any
+/// other compressed file requires decompressing it (see `sniff_format`).
+#[derive(Deserialize, Serialize, Copy, Clone, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Magic {
+    Bzip2,
+    Gzip,
+    Xz,
+    Zstd,
+    Zip,
+    Tar,
+    Elf,
+    MachO,
+    Pe,
+    Script,
+    Unknown,
+}
+
+impl Magic {
+    /// `header` should be at least the first 512 bytes of the file, if it
+    /// has that many, or an uncompressed tar is not recognized.
+    pub fn of(header: &[u8]) -> Self {
+        if header.starts_with(GZIP_MAGIC) {
+            Self::Gzip
+        } else if header.starts_with(ZSTD_MAGIC) {
+            Self::Zstd
+        } else if header.starts_with(XZ_MAGIC) {
+            Self::Xz
+        } else if header.starts_with(BZIP2_MAGIC) {
+            Self::Bzip2
+        } else if header.starts_with(ZIP_MAGIC) || header.starts_with(ZIP_EMPTY_MAGIC) {
+            Self::Zip
+        } else if has_ustar_magic(header) {
+            Self::Tar
+        } else if header.starts_with(ELF_MAGIC) {
+            Self::Elf
+        } else if MACH_O_MAGICS.iter().any(|magic| header.starts_with(magic)) {
+            Self::MachO
+        } else if header.starts_with(PE_MAGIC) {
+            Self::Pe
+        } else if header.starts_with(SCRIPT_MAGIC) {
+            Self::Script
+        } else {
+            Self::Unknown
+        }
+    }
+
+    /// The magic at the start of an artifact in `format`, if it has one.
+    pub fn of_format(format: ArtifactFormat) -> Option<Self> {
+        match format {
+            ArtifactFormat::Plain => None,
+            ArtifactFormat::Bzip2 | ArtifactFormat::TarBzip2 => Some(Self::Bzip2),
+            ArtifactFormat::Gz | ArtifactFormat::TarGz => Some(Self::Gzip),
+            ArtifactFormat::Xz | ArtifactFormat::TarXz => Some(Self::Xz),
+            ArtifactFormat::Zstd | ArtifactFormat::TarZstd => Some(Self::Zstd),
+            ArtifactFormat::Tar => Some(Self::Tar),
+            ArtifactFormat::Zip => Some(Self::Zip),
+        }
+    }
+
+    /// Whether the file is compressed or archived, i.e., is not `Plain`.
+    pub fn is_packed(self) -> bool {
+        matches!(
+            self,
+            Self::Bzip2 | Self::Gzip | Self::Xz | Self::Zstd | Self::Zip | Self::Tar
+        )
+    }
+
+    pub fn is_executable(self) -> bool {
+        matches!(self, Self::Elf | Self::MachO | Self::Pe | Self::Script)
+    }
+
+    /// Whether a file that starts with this magic could be in `format`.
+    /// Nothing can be said about content that is not recognized: e.g., old
+    /// tar archives have no magic at all.
+    pub fn agrees_with(self, format: ArtifactFormat) -> bool {
+        match Self::of_format(format) {
+            _ if self == Self::Unknown => true,
+            Some(expected) => self == expected,
+            None => !self.is_packed(),
+        }
+    }
+
+    /// Describes how `format` disagrees with this magic, e.g., "is
+    /// gzip-compressed, but has no `format`", or `None` if it agrees.
+    pub fn mismatch_with(self, format: ArtifactFormat) -> Option<String> {
+        if self.agrees_with(format) {
+            return None;
+        }
+        let declared = match format {
+            ArtifactFormat::Plain => "no `format`".to_owned(),
+            format => format!("`\"format\": \"{}\"`", &format.extension()[1..]),
+        };
+        Some(format!("is {self}, but has {declared}"))
+    }
+}
+
+impl fmt::Display for Magic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Bzip2 => "bzip2-compressed",
+            Self::Gzip => "gzip-compressed",
+            Self::Xz => "xz-compressed",
+            Self::Zstd => "zstd-compressed",
+            Self::Zip => "a zip archive",
+            Self::Tar => "a tar archive",
+            Self::Elf => "an ELF executable",
+            Self::MachO => "a Mach-O executable",
+            Self::Pe => "a PE executable",
+            Self::Script => "a script",
+            Self::Unknown => "unrecognized",
+        })
+    }
+}
+
+/// Detects the format of an artifact from its content rather than from its
+/// name. Content that is not recognized is `Plain`.
+pub fn sniff_format(artifact: &Path) -> io::Result<ArtifactFormat> {
+    let header = read_header(fs_ctx::file_open(artifact)?)?;
+    let open = || fs_ctx::file_open(artifact).map(BufReader::new);
+
+    let format = match Magic::of(&header) {
+        Magic::Gzip if is_tar(GzDecoder::new(open()?))? => ArtifactFormat::TarGz,
+        Magic::Gzip => ArtifactFormat::Gz,
+        Magic::Zstd if is_tar(ZstdDecoder::with_buffer(open()?)?)? => ArtifactFormat::TarZstd,
+        Magic::Zstd => ArtifactFormat::Zstd,
+        Magic::Xz if is_xz_tar(artifact)? => ArtifactFormat::TarXz,
+        Magic::Xz => ArtifactFormat::Xz,
+        Magic::Bzip2 if is_tar(BzDecoder::new(open()?))? => ArtifactFormat::TarBzip2,
+        Magic::Bzip2 => ArtifactFormat::Bzip2,
+        Magic::Zip => ArtifactFormat::Zip,
+        Magic::Tar => ArtifactFormat::Tar,
+        Magic::Elf | Magic::MachO | Magic::Pe | Magic::Script | Magic::Unknown => {
+            ArtifactFormat::Plain
+        }
+    };
+    Ok(format)
+}
+
+/// The magic at the start of the file at `path`.
+pub fn sniff_magic(path: &Path) -> io::Result<Magic> {
+    Ok(Magic::of(&read_header(fs_ctx::file_open(path)?)?))
+}
+
+#[cfg(not(dotslash_internal))]
+fn is_xz_tar(artifact: &Path) -> io::Result<bool> {
+    is_tar(XzDecoder::new(fs_ctx::file_open(artifact)?))
+}
+
+#[cfg(dotslash_internal)]
+fn is_xz_tar(_artifact: &Path) -> io::Result<bool> {
+    Ok(false)
+}
+
+fn read_header<R: Read>(reader: R) -> io::Result<Vec<u8>> {
+    let mut header = Vec::new();
+    reader.take(TAR_HEADER_LEN).read_to_end(&mut header)?;
+    Ok(header)
+}
+
+fn is_tar<R: Read>(decompressed: R) -> io::Result<bool> {
+    Ok(has_ustar_magic(&read_header(decompressed)?))
+}
+
+fn has_ustar_magic(header: &[u8]) -> bool {
+    header
+        .get(USTAR_MAGIC_OFFSET..)
+        .is_some_and(|magic| magic.starts_with(USTAR_MAGIC))
+}
+
+/// Guesses the `path` of the executable in an artifact. Plain and compressed
+/// artifacts are a single file, which is named `exe_name`. For an archive,
+/// this is the file that is most likely the executable, or a `TODO` naming
+/// the candidates.
+pub fn guess_path(artifact: &Path, format: ArtifactFormat, exe_name: &str) -> io::Result<String> {
+    let Some(archive_type) = format.as_archive_type().filter(|_| format.is_container()) else {
+        return Ok(exe_name.to_owned());
+    };
+    let reader = BufReader::new(fs_ctx::file_open(artifact)?);
+    let files = unarchive::list_files(reader, archive_type)?;
+    Ok(pick_executable(&files, exe_name))
+}
+
+/// A file named `exe_name` wins, then the only file that looks executable:
+/// either it has an execute bit or, because zip archives created on Windows
+/// have no mode bits, it is an `.exe`.
+pub fn pick_executable(files: &[ArchivedFile], exe_name: &str) -> String {
+    fn file_name(file: &ArchivedFile) -> &str {
+        file.path.rsplit('/').next().unwrap_or_default()
+    }
+    let named = files
+        .iter()
+        .filter(|file| file_name(file) == exe_name)
+        .collect::<Vec<_>>();
+    let executables = files
+        .iter()
+        .filter(|file| {
+            file.is_executable
+                || Path::new(&file.path)
+                    .extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("exe"))
+        })
+        .collect::<Vec<_>>();
+    let candidates = match (named.as_slice(), executables.as_slice()) {
+        ([file], _) | ([], [file]) => return file.path.clone(),
+        ([], []) => files.iter().collect(),
+        ([], _) => executables,
+        _ => named,
+    };
+    format!(
+        "TODO: choose the executable from {}",
+        ListOf::new(candidates.iter().map(|file| file.path.as_str())),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Write as _;
+
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+
+    use super::*;
+
+    fn tar(files: &[(&str, u32)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, mode) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(0);
+            header.set_mode(*mode);
+            header.set_cksum();
+            builder.append_data(&mut header, path, io::empty()).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn sniffs_format_from_content() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let tar = tar(&[("tool", 0o755)]);
+        for (contents, expected) in [
+            (tar.clone(), ArtifactFormat::Tar),
+            (gzip(&tar), ArtifactFormat::TarGz),
+            (gzip(b"#!/bin/sh\n"), ArtifactFormat::Gz),
+            (zstd::encode_all(&tar[..], 0)?, ArtifactFormat::TarZstd),
+            (zstd::encode_all(&b"data"[..], 0)?, ArtifactFormat::Zstd),
+            (b"PK\x05\x06".to_vec(), ArtifactFormat::Zip),
+            (b"#!/bin/sh\n".to_vec(), ArtifactFormat::Plain),
+            (b"\x7fELF\x02\x01\x01".to_vec(), ArtifactFormat::Plain),
+            (vec![], ArtifactFormat::Plain),
+        ] {
+            // The name of the artifact does not matter.
+            let artifact = temp_dir.path().join("artifact.tar.gz");
+            fs::write(&artifact, &contents)?;
+            assert_eq!(sniff_format(&artifact)?, expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn magic_agrees_with_format() {
+        assert_eq!(Magic::of(b"\x7fELF\x02"), Magic::Elf);
+        assert_eq!(Magic::of(b"\xcf\xfa\xed\xfe\x0c"), Magic::MachO);
+        assert_eq!(Magic::of(b"MZ\x90\x00"), Magic::Pe);
+        assert_eq!(Magic::of(b"#!/bin/sh"), Magic::Script);
+        assert_eq!(Magic::of(&gzip(b"data")), Magic::Gzip);
+        assert_eq!(Magic::of(b"<!DOCTYPE html>"), Magic::Unknown);
+
+        // Compressed data is checked by its compression alone.
+        assert!(Magic::Gzip.agrees_with(ArtifactFormat::TarGz));
+        assert!(Magic::Gzip.agrees_with(ArtifactFormat::Gz));
+        assert!(!Magic::Gzip.agrees_with(ArtifactFormat::TarZstd));
+        assert!(!Magic::Gzip.agrees_with(ArtifactFormat::Plain));
+        assert!(Magic::Elf.agrees_with(ArtifactFormat::Plain));
+        assert!(!Magic::Elf.agrees_with(ArtifactFormat::TarGz));
+        assert!(Magic::Unknown.agrees_with(ArtifactFormat::Tar));
+
+        assert_eq!(Magic::Gzip.mismatch_with(ArtifactFormat::TarGz), None);
+        assert_eq!(
+            Magic::Gzip.mismatch_with(ArtifactFormat::Plain).as_deref(),
+            Some("is gzip-compressed, but has no `format`"),
+        );
+        assert_eq!(
+            Magic::Zstd.mismatch_with(ArtifactFormat::TarGz).as_deref(),
+            Some("is zstd-compressed, but has `\"format\": \"tar.gz\"`"),
+        );
+    }
+
+    #[test]
+    fn guesses_path_from_archive() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let artifact = temp_dir.path().join("artifact");
+        fs::write(
+            &artifact,
+            gzip(&tar(&[
+                ("tool-1.0/README.md", 0o644),
+                ("tool-1.0/tool", 0o755),
+            ])),
+        )?;
+        assert_eq!(
+            guess_path(&artifact, ArtifactFormat::TarGz, "other")?,
+            "tool-1.0/tool",
+        );
+        assert_eq!(guess_path(&artifact, ArtifactFormat::Gz, "tool")?, "tool");
+        Ok(())
+    }
+
+    #[test]
+    fn picks_executable_from_archive() {
+        let file = |path: &str, is_executable| ArchivedFile {
+            path: path.to_owned(),
+            is_executable,
+        };
+        let files = [
+            file("tool-1.0/README.md", false),
+            file("tool-1.0/complete/tool.bash", false),
+            file("tool-1.0/tool", true),
+            file("tool-1.0/tool-helper", true),
+        ];
+        assert_eq!(pick_executable(&files, "tool"), "tool-1.0/tool");
+        assert_eq!(
+            pick_executable(&files, "other"),
+            "TODO: choose the executable from `tool-1.0/tool`, `tool-1.0/tool-helper`",
+        );
+        assert_eq!(pick_executable(&files[..3], "other"), "tool-1.0/tool");
+        // Zip archives from Windows have no mode bits.
+        assert_eq!(
+            pick_executable(&[file("bin/other.exe", false)], "tool.exe"),
+            "bin/other.exe",
+        );
+    }
+}
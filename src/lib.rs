@@ -0,0 +1,141 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! DotSlash as a library.
+//!
+//! The `dotslash` binary is a thin wrapper around [`run`]. Tools that want to
+//! work with DotSlash files without shelling out to it can instead use the
+//! items re-exported here to:
+//!
+//! - parse a DotSlash file ([`parse_file`]),
+//! - select the entry for a platform and find where it lives in the cache
+//!   ([`locate_artifacts`]),
+//! - fetch and verify the artifact, possibly with custom providers
+//!   ([`download_artifact`], [`ProviderFactory`]), and
+//! - inspect and clean up the cache ([`list_artifacts`],
+//!   [`verify_cached_artifact`], [`collect_garbage`]).
+//!
+//! Only the items re-exported from the crate root are considered stable; the
+//! modules behind them are private so that they can be reorganized freely.
+//!
+//! Like the binary, the fetching and cache functions honor DotSlash's global
+//! configuration: the `DOTSLASH_*` environment variables and the config files
+//! described in the docs (offline mode, mirrors, URL policies, timeouts and
+//! the cache location). It is read once, the first time it is needed, and
+//! then kept for the life of the process. If a config file cannot be loaded,
+//! fetching fails rather than ignore it. Functions that depend on the config
+//! say so; the rest of the API does not read it.
+//!
+//! ```
+//! let (_json, config_file) = dotslash::parse_file(
+//!     r#"#!/usr/bin/env dotslash
+//! {
+//!   "name": "hello",
+//!   "platforms": {
+//!     "linux-x86_64": {
+//!       "size": 5,
+//!       "hash": "blake3",
+//!       "digest": "e5fcb8ecbcce8a2c8d3aa26c0e5e2bf0bc5a4f1bf1e5b0e23bc2b2a4e0ae4d7d",
+//!       "path": "hello",
+//!       "providers": [{"url": "https://example.com/hello"}]
+//!     }
+//!   }
+//! }
+//! "#,
+//! )?;
+//! assert_eq!(config_file.name, "hello");
+//! # anyhow::Ok(())
+//! ```
+
+mod artifact_hasher;
+mod artifact_location;
+mod artifact_manifest;
+mod artifact_path;
+mod batch_fetch;
+mod bundle;
+mod cache_gc;
+mod cache_list;
+mod cache_verify;
+mod config;
+mod create_from_release;
+mod curl;
+mod default_provider_factory;
+mod digest;
+mod dotslash_cache;
+mod download;
+mod exec_provider;
+mod execution;
+mod fetch_method;
+mod formatter;
+mod gcs_provider;
+mod github_release_provider;
+mod global_config;
+mod http_provider;
+mod inspect_artifact;
+mod lint;
+mod locate;
+mod mirror;
+#[cfg(feature = "native-http")]
+mod native_http;
+mod platform;
+mod policy;
+mod print_entry_for_url;
+mod provider;
+mod s3_provider;
+mod schema;
+mod signature;
+mod subcommand;
+mod update;
+mod util;
+
+// Parsing.
+pub use crate::artifact_path::ArtifactPath;
+pub use crate::config::Arg0;
+pub use crate::config::ArtifactEntry;
+pub use crate::config::ConfigFile;
+pub use crate::config::HashAlgorithm;
+pub use crate::config::HashDigest;
+pub use crate::config::ProvidersOrder;
+pub use crate::config::REQUIRED_HEADER;
+pub use crate::config::parse_file;
+pub use crate::digest::Digest;
+pub use crate::fetch_method::ArtifactFormat;
+// Locating.
+pub use crate::artifact_location::ArtifactLocation;
+pub use crate::artifact_location::determine_location;
+pub use crate::locate::LocatedEntry;
+pub use crate::locate::PlatformSelection;
+pub use crate::locate::locate_artifact;
+pub use crate::locate::locate_artifacts;
+pub use crate::platform::SUPPORTED_PLATFORM;
+// Fetching.
+pub use crate::default_provider_factory::DefaultProviderFactory;
+pub use crate::download::DotslashFileInfo;
+pub use crate::download::OfflineError;
+pub use crate::download::download_artifact;
+pub use crate::provider::Provider;
+pub use crate::provider::ProviderFactory;
+pub use crate::util::FileLock;
+// Cache management.
+pub use crate::artifact_manifest::ArtifactOrigin;
+pub use crate::artifact_manifest::ManifestDifference;
+pub use crate::cache_gc::GcArtifact;
+pub use crate::cache_gc::GcOptions;
+pub use crate::cache_gc::GcReport;
+pub use crate::cache_gc::collect_garbage;
+pub use crate::cache_list::CacheListEntry;
+pub use crate::cache_list::list_artifacts;
+pub use crate::cache_verify::VerifyOptions;
+pub use crate::cache_verify::VerifyStatus;
+pub use crate::cache_verify::verify_cached_artifact;
+pub use crate::dotslash_cache::CachedArtifact;
+pub use crate::dotslash_cache::DotslashCache;
+// The command line interface.
+pub use crate::execution::run;
@@ -0,0 +1,484 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! Checks DotSlash files for mistakes that `parse_file` lets through, e.g.,
+//! misspelled fields, which serde silently ignores. The checks work on the
+//! JSONC syntax tree so that each diagnostic points at a line and column.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+use std::path::PathBuf;
+
+use jsonc_parser::CollectOptions;
+use jsonc_parser::ParseOptions;
+use jsonc_parser::ast;
+use jsonc_parser::common::Ranged as _;
+use jsonc_parser::parse_to_ast;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::artifact_location::determine_location;
+use crate::artifact_manifest::ArtifactManifest;
+use crate::artifact_path::ArtifactPath;
+use crate::config;
+use crate::config::ARTIFACT_ENTRY_FIELDS;
+use crate::config::ArtifactEntry;
+use crate::config::REQUIRED_HEADER;
+use crate::default_provider_factory::DefaultProviderFactory;
+use crate::dotslash_cache::DotslashCache;
+use crate::download::DEFAULT_PROVDIER_TYPE;
+use crate::provider::ProviderFactory as _;
+use crate::signature::SIGNATURE_FIELD;
+use crate::update::VARS_FIELD;
+use crate::util::ListOf;
+use crate::util::fs_ctx;
+
+const TOP_LEVEL_FIELDS: [&str; 4] = ["name", "platforms", SIGNATURE_FIELD, VARS_FIELD];
+
+/// Every platform that DotSlash supports. This must match `if_platform!`.
+const ALL_PLATFORMS: [&str; 7] = [
+    "linux-aarch64",
+    "linux-riscv64",
+    "linux-x86_64",
+    "macos-aarch64",
+    "macos-x86_64",
+    "windows-aarch64",
+    "windows-x86_64",
+];
+
+/// Platforms that most tools are expected to support. A DotSlash file that
+/// leaves one out is likely an oversight.
+const COMMON_PLATFORMS: [&str; 5] = [
+    "linux-aarch64",
+    "linux-x86_64",
+    "macos-aarch64",
+    "macos-x86_64",
+    "windows-x86_64",
+];
+
+#[derive(Serialize, Copy, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// DotSlash will reject the file or fail to fetch the artifact.
+    Error,
+    /// The file works, but likely not as intended.
+    Warning,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Diagnostic {
+    pub file: PathBuf,
+    /// 1-indexed.
+    pub line: usize,
+    /// 1-indexed, in characters.
+    pub column: usize,
+    pub severity: Severity,
+    /// Identifies the check, e.g., `unknown-field`.
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(
+            f,
+            "{}:{}:{}: {severity}: {} [{}]",
+            self.file.display(),
+            self.line,
+            self.column,
+            self.message,
+            self.code,
+        )
+    }
+}
+
+pub fn lint_file(file: &Path, dotslash_cache: &DotslashCache) -> anyhow::Result<Vec<Diagnostic>> {
+    let dotslash_data = fs_ctx::read_to_string(file)?;
+    Ok(lint(file, &dotslash_data, Some(dotslash_cache)))
+}
+
+/// With `dotslash_cache`, the artifacts that are already in it are also
+/// checked against their entries, e.g., for a `format-mismatch`.
+pub fn lint(
+    file: &Path,
+    dotslash_data: &str,
+    dotslash_cache: Option<&DotslashCache>,
+) -> Vec<Diagnostic> {
+    let mut linter = Linter {
+        file,
+        text: dotslash_data,
+        dotslash_cache,
+        diagnostics: vec![],
+    };
+    linter.lint_text();
+    let mut diagnostics = linter.diagnostics;
+    diagnostics.sort_by_key(|diagnostic| (diagnostic.line, diagnostic.column));
+    diagnostics
+}
+
+struct Linter<'a> {
+    file: &'a Path,
+    text: &'a str,
+    dotslash_cache: Option<&'a DotslashCache>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter<'_> {
+    fn report(&mut self, offset: usize, severity: Severity, code: &'static str, message: String) {
+        let before = &self.text[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        self.diagnostics.push(Diagnostic {
+            file: self.file.to_owned(),
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            severity,
+            code,
+            message,
+        });
+    }
+
+    fn lint_text(&mut self) {
+        let has_header = self
+            .text
+            .strip_prefix(REQUIRED_HEADER)
+            .is_some_and(|rest| rest.starts_with('\n') || rest.starts_with("\r\n"));
+        if !has_header {
+            self.report(
+                0,
+                Severity::Error,
+                "header",
+                format!("DotSlash file must start with `{REQUIRED_HEADER}`"),
+            );
+            return;
+        }
+
+        let json = config::blank_header(self.text);
+        let ast = match parse_to_ast(&json, &CollectOptions::default(), &ParseOptions::default()) {
+            Ok(ast) => ast,
+            Err(err) => {
+                self.report(
+                    err.range().start,
+                    Severity::Error,
+                    "syntax",
+                    err.kind().to_string(),
+                );
+                return;
+            }
+        };
+        let Some(root) = ast.value.as_ref().and_then(|value| value.as_object()) else {
+            self.report(
+                REQUIRED_HEADER.len(),
+                Severity::Error,
+                "invalid",
+                "DotSlash file must contain a JSON object".to_owned(),
+            );
+            return;
+        };
+        // Providers are checked as values, like `download_artifact` does.
+        let value = jsonc_parser::parse_to_serde_value(&json, &ParseOptions::default())
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+
+        self.check_fields(root, &TOP_LEVEL_FIELDS);
+        if let Some(platforms) = root.get("platforms") {
+            if let Some(platforms_object) = platforms.value.as_object() {
+                self.lint_platforms(platforms.range.start, platforms_object, &value["platforms"]);
+            }
+        }
+
+        // Anything else that DotSlash would reject, e.g., a malformed
+        // `digest`, but only if it would not repeat what was reported.
+        if !self
+            .diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.severity == Severity::Error)
+        {
+            if let Err(err) = config::parse_file(self.text) {
+                self.report(
+                    root.range.start,
+                    Severity::Error,
+                    "invalid",
+                    format!("{err:#}"),
+                );
+            }
+        }
+    }
+
+    fn check_fields(&mut self, object: &ast::Object<'_>, fields: &[&str]) {
+        for prop in &object.properties {
+            let name = prop.name.as_str();
+            if !fields.contains(&name) {
+                self.report(
+                    prop.range.start,
+                    Severity::Warning,
+                    "unknown-field",
+                    format!(
+                        "unknown field `{name}` is ignored; expected one of {}",
+                        ListOf::new(fields)
+                    ),
+                );
+            }
+        }
+    }
+
+    fn lint_platforms(&mut self, offset: usize, platforms: &ast::Object<'_>, value: &Value) {
+        for prop in &platforms.properties {
+            let platform = prop.name.as_str();
+            if !ALL_PLATFORMS.contains(&platform) {
+                self.report(
+                    prop.range.start,
+                    Severity::Warning,
+                    "unknown-platform",
+                    format!(
+                        "unknown platform `{platform}` is never used; expected one of {}",
+                        ListOf::new(ALL_PLATFORMS),
+                    ),
+                );
+            }
+            if let Some(entry) = prop.value.as_object() {
+                self.lint_artifact_entry(entry, &value[platform]);
+            }
+        }
+
+        let missing = COMMON_PLATFORMS
+            .into_iter()
+            .filter(|platform| platforms.get(platform).is_none())
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            self.report(
+                offset,
+                Severity::Warning,
+                "missing-platform",
+                format!("no entry for common platform(s) {}", ListOf::new(missing)),
+            );
+        }
+    }
+
+    fn lint_artifact_entry(&mut self, entry: &ast::Object<'_>, value: &Value) {
+        self.check_fields(entry, &ARTIFACT_ENTRY_FIELDS);
+        self.check_cached_format(entry, value);
+
+        if let Some(path) = entry.get_string("path") {
+            if let Err(err) = path.value.parse::<ArtifactPath>() {
+                self.report(
+                    path.range.start,
+                    Severity::Error,
+                    "invalid-path",
+                    format!("invalid `path`: {err}"),
+                );
+            }
+        }
+
+        let weighted = entry
+            .get_string("providers_order")
+            .is_some_and(|order| order.value == "weighted-random");
+        let Some(providers) = entry.get_array("providers") else {
+            return;
+        };
+        let mut urls = HashSet::new();
+        for (index, provider) in providers.elements.iter().enumerate() {
+            let Some(provider) = provider.as_object() else {
+                continue;
+            };
+            if let (Some(weight), false) = (provider.get("weight"), weighted) {
+                self.report(
+                    weight.range.start,
+                    Severity::Warning,
+                    "unused-weight",
+                    "`weight` is ignored unless `providers_order` is `weighted-random`".to_owned(),
+                );
+            }
+            self.lint_provider(provider, &value["providers"][index], &mut urls);
+        }
+    }
+
+    /// The manifest of a cached artifact records what it looked like when it
+    /// was fetched (see `ArtifactOrigin::magic`).
+    fn check_cached_format(&mut self, entry: &ast::Object<'_>, value: &Value) {
+        let Some(dotslash_cache) = self.dotslash_cache else {
+            return;
+        };
+        // Invalid entries are reported elsewhere.
+        let Ok(artifact_entry) = serde_json::from_value::<ArtifactEntry>(value.clone()) else {
+            return;
+        };
+        let artifact_location = determine_location(&artifact_entry, dotslash_cache);
+        let Ok(Some(origin)) = ArtifactManifest::read_origin(&artifact_location.manifest_path)
+        else {
+            return;
+        };
+        let Some(mismatch) = origin
+            .magic
+            .and_then(|magic| magic.mismatch_with(artifact_entry.format))
+        else {
+            return;
+        };
+        let offset = entry
+            .get("format")
+            .map_or(entry.range.start, |prop| prop.range.start);
+        self.report(
+            offset,
+            Severity::Warning,
+            "format-mismatch",
+            format!("the cached artifact {mismatch}"),
+        );
+    }
+
+    fn lint_provider(
+        &mut self,
+        provider: &ast::Object<'_>,
+        value: &Value,
+        urls: &mut HashSet<String>,
+    ) {
+        let (provider_type, offset) = match provider.get("type") {
+            None => (DEFAULT_PROVDIER_TYPE, provider.range.start),
+            Some(prop) => match prop.value.as_string_lit() {
+                Some(lit) => (lit.value.as_ref(), lit.range.start),
+                None => return,
+            },
+        };
+        let provider_impl = match DefaultProviderFactory.get_provider(provider_type) {
+            Ok(provider_impl) => provider_impl,
+            Err(err) => {
+                self.report(
+                    offset,
+                    Severity::Error,
+                    "unknown-provider-type",
+                    format!("{err:#}"),
+                );
+                return;
+            }
+        };
+
+        let Ok(Some(url)) = provider_impl.artifact_url(value) else {
+            return;
+        };
+        let offset = provider
+            .get("url")
+            .map_or(provider.range.start, |prop| prop.value.range().start);
+        if url.to_ascii_lowercase().starts_with("http://") {
+            self.report(
+                offset,
+                Severity::Warning,
+                "insecure-url",
+                format!("`{url}` is fetched over plain HTTP; use `https://` instead"),
+            );
+        }
+        if !urls.insert(url.clone()) {
+            self.report(
+                offset,
+                Severity::Warning,
+                "duplicate-url",
+                format!("`{url}` is already listed by another provider"),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::SUPPORTED_PLATFORM;
+
+    fn lint_str(dotslash_data: &str) -> Vec<(usize, usize, &'static str)> {
+        lint(Path::new("tool"), dotslash_data, None)
+            .into_iter()
+            .map(|diagnostic| (diagnostic.line, diagnostic.column, diagnostic.code))
+            .collect()
+    }
+
+    #[test]
+    fn supported_platform_is_known() {
+        assert!(ALL_PLATFORMS.contains(&SUPPORTED_PLATFORM));
+    }
+
+    #[test]
+    fn clean_file_has_no_diagnostics() {
+        let entry = |platform: &str| {
+            format!(
+                r#""{platform}": {{
+      "size": 5,
+      "hash": "blake3",
+      "digest": "{}",
+      "format": "tar.gz",
+      "path": "tool-1.0/tool",
+      "providers": [
+        {{"url": "https://example.com/{platform}.tar.gz"}},
+      ],
+    }},"#,
+                "a".repeat(64),
+            )
+        };
+        let platforms = COMMON_PLATFORMS.map(entry).join("\n    ");
+        let dotslash_data = format!(
+            "#!/usr/bin/env dotslash\n// A comment.\n{{\n  \"name\": \"tool\",\n  \"platforms\": {{\n    {platforms}\n  }},\n}}\n"
+        );
+        assert_eq!(lint_str(&dotslash_data), []);
+    }
+
+    #[test]
+    fn reports_positions_of_mistakes() {
+        let dotslash_data = r#"#!/usr/bin/env dotslash
+{
+  "name": "tool",
+  "platforms": {
+    "linux-x86_64": {
+      "size": 5,
+      "hash": "blake3",
+      "digest": "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+      "path": "../tool",
+      "read_only": false,
+      "providers": [
+        {"url": "http://example.com/tool", "weight": 2},
+        {"type": "github-release", "repo": "a/b", "tag": "v1", "name": "tool"},
+        {"url": "http://example.com/tool"},
+        {"type": "ftp", "url": "ftp://example.com/tool"},
+      ],
+    },
+    "linux-x86-64": {},
+  },
+}
+"#;
+        assert_eq!(
+            lint_str(dotslash_data),
+            [
+                (4, 3, "missing-platform"),
+                (9, 15, "invalid-path"),
+                (10, 7, "unknown-field"),
+                (12, 17, "insecure-url"),
+                (12, 44, "unused-weight"),
+                (14, 17, "insecure-url"),
+                (14, 17, "duplicate-url"),
+                (15, 18, "unknown-provider-type"),
+                (18, 5, "unknown-platform"),
+            ],
+        );
+    }
+
+    #[test]
+    fn reports_invalid_files() {
+        assert_eq!(lint_str("{}"), [(1, 1, "header")]);
+        assert_eq!(
+            lint_str("#!/usr/bin/env dotslash\n{\n  \"name\": \n}"),
+            [(4, 1, "syntax")],
+        );
+        // Only reported if nothing else is an error.
+        assert_eq!(
+            lint_str("#!/usr/bin/env dotslash\n{\"name\": 1, \"platforms\": {}}"),
+            [(2, 1, "invalid"), (2, 13, "missing-platform")],
+        );
+    }
+}
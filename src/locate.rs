@@ -16,15 +16,19 @@ use crate::config;
 use crate::config::ArtifactEntry;
 use crate::dotslash_cache::DotslashCache;
 use crate::platform::SUPPORTED_PLATFORM;
+use crate::signature;
 use crate::util;
 use crate::util::ListOf;
 
+/// Returns the `name` of the DotSlash file along with the entry and cache
+/// location for the current platform.
 pub fn locate_artifact(
     dotslash_data: &str,
     dotslash_cache: &DotslashCache,
-) -> anyhow::Result<(ArtifactEntry, ArtifactLocation)> {
-    let (_original_json, mut config_file) =
+) -> anyhow::Result<(String, ArtifactEntry, ArtifactLocation)> {
+    let (original_json, mut config_file) =
         config::parse_file(dotslash_data).context("failed to parse DotSlash file")?;
+    signature::check_signature(&original_json)?;
 
     let (_platform, artifact_entry) = config_file
         .platforms
@@ -51,5 +55,90 @@ pub fn locate_artifact(
         let _ = util::update_mtime(&artifact_location.executable);
     }
 
-    Ok((artifact_entry, artifact_location))
+    Ok((config_file.name, artifact_entry, artifact_location))
+}
+
+/// Which entries of a DotSlash file to operate on.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum PlatformSelection {
+    /// Only `SUPPORTED_PLATFORM`, i.e., what running the file would use.
+    #[default]
+    Host,
+    /// The named platforms, each of which must be in the file.
+    Named(Vec<String>),
+    /// Every platform in the file.
+    All,
+}
+
+/// An entry selected from a DotSlash file along with its cache location.
+pub struct LocatedEntry {
+    pub platform: String,
+    pub artifact_entry: ArtifactEntry,
+    pub artifact_location: ArtifactLocation,
+}
+
+/// Like `locate_artifact`, but for the platforms in `platforms`, sorted by
+/// platform name.
+///
+/// Unlike `locate_artifact`, this does not bump any mtimes unless the host
+/// platform is selected via `PlatformSelection::Host`.
+pub fn locate_artifacts(
+    dotslash_data: &str,
+    dotslash_cache: &DotslashCache,
+    platforms: &PlatformSelection,
+) -> anyhow::Result<(String, Vec<LocatedEntry>)> {
+    let names = match platforms {
+        PlatformSelection::Host => {
+            let (name, artifact_entry, artifact_location) =
+                locate_artifact(dotslash_data, dotslash_cache)?;
+            let entry = LocatedEntry {
+                platform: SUPPORTED_PLATFORM.to_owned(),
+                artifact_entry,
+                artifact_location,
+            };
+            return Ok((name, vec![entry]));
+        }
+        PlatformSelection::Named(names) => Some(names),
+        PlatformSelection::All => None,
+    };
+
+    let (original_json, mut config_file) =
+        config::parse_file(dotslash_data).context("failed to parse DotSlash file")?;
+    signature::check_signature(&original_json)?;
+
+    let mut selected = match names {
+        Some(names) => {
+            let mut selected = vec![];
+            for name in names {
+                let (platform, artifact_entry) = config_file
+                    .platforms
+                    .remove_entry(name)
+                    .ok_or_else(|| {
+                        anyhow::format_err!(
+                            "expected platform `{}` - but found {}",
+                            name,
+                            ListOf::new(config_file.platforms.keys()),
+                        )
+                    })
+                    .context("platform not supported")?;
+                selected.push((platform, artifact_entry));
+            }
+            selected
+        }
+        None => config_file.platforms.into_iter().collect(),
+    };
+    selected.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let entries = selected
+        .into_iter()
+        .map(|(platform, artifact_entry)| {
+            let artifact_location = determine_location(&artifact_entry, dotslash_cache);
+            LocatedEntry {
+                platform,
+                artifact_entry,
+                artifact_location,
+            }
+        })
+        .collect();
+    Ok((config_file.name, entries))
 }
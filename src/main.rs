@@ -8,34 +8,13 @@
  * above-listed licenses.
  */
 
-mod artifact_location;
-mod artifact_path;
-mod config;
-mod curl;
-mod default_provider_factory;
-mod digest;
-mod dotslash_cache;
-mod download;
-mod execution;
-mod fetch_method;
-mod gcs_provider;
-mod github_release_provider;
-mod http_provider;
-mod locate;
-mod platform;
-mod print_entry_for_url;
-mod provider;
-mod s3_provider;
-mod subcommand;
-mod util;
-
 use std::env;
 use std::process::ExitCode;
 
-use crate::default_provider_factory::DefaultProviderFactory;
+use dotslash::DefaultProviderFactory;
 
 fn main() -> ExitCode {
     let args = env::args_os();
     let provider_factory = DefaultProviderFactory {};
-    execution::run(args, &provider_factory)
+    dotslash::run(args, &provider_factory)
 }
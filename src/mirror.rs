@@ -0,0 +1,178 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! Rules from the global config that redirect artifact URLs to a mirror,
+//! e.g., for networks that block the original host. Mirrored artifacts are
+//! verified like any other, so a mirror cannot change what gets run.
+
+use regex_lite::Regex;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// How a rule appears in a config file: exactly one of `prefix` or `regex`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+struct MirrorRuleData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prefix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    regex: Option<String>,
+    replacement: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(try_from = "MirrorRuleData", into = "MirrorRuleData")]
+pub struct MirrorRule {
+    pattern: Pattern,
+    replacement: String,
+}
+
+#[derive(Clone, Debug)]
+enum Pattern {
+    Prefix(String),
+    /// Must match the whole URL.
+    Regex(Regex),
+}
+
+impl TryFrom<MirrorRuleData> for MirrorRule {
+    type Error = String;
+
+    fn try_from(data: MirrorRuleData) -> Result<Self, Self::Error> {
+        let pattern = match (data.prefix, data.regex) {
+            (Some(prefix), None) => Pattern::Prefix(prefix),
+            (None, Some(regex)) => Pattern::Regex(
+                Regex::new(&format!("^(?:{regex})$"))
+                    .map_err(|err| format!("invalid mirror regex `{regex}`: {err}"))?,
+            ),
+            _ => return Err("a mirror rule needs exactly one of `prefix` or `regex`".to_owned()),
+        };
+        Ok(Self {
+            pattern,
+            replacement: data.replacement,
+        })
+    }
+}
+
+impl From<MirrorRule> for MirrorRuleData {
+    fn from(rule: MirrorRule) -> Self {
+        let (prefix, regex) = match rule.pattern {
+            Pattern::Prefix(prefix) => (Some(prefix), None),
+            Pattern::Regex(regex) => {
+                // Undo the anchoring added by `try_from`.
+                let regex = regex.as_str();
+                let regex = &regex["^(?:".len()..regex.len() - ")$".len()];
+                (None, Some(regex.to_owned()))
+            }
+        };
+        Self {
+            prefix,
+            regex,
+            replacement: rule.replacement,
+        }
+    }
+}
+
+impl MirrorRule {
+    fn rewrite(&self, url: &str) -> Option<String> {
+        match &self.pattern {
+            Pattern::Prefix(prefix) => url
+                .strip_prefix(prefix.as_str())
+                .map(|rest| format!("{}{rest}", self.replacement)),
+            Pattern::Regex(regex) => regex
+                .is_match(url)
+                .then(|| regex.replace(url, self.replacement.as_str()).into_owned()),
+        }
+    }
+}
+
+/// Applies the first of `rules` that matches `url`. Returns `None` if none
+/// do, or if the result is `url` itself.
+pub fn rewrite_url(rules: &[MirrorRule], url: &str) -> Option<String> {
+    rules
+        .iter()
+        .find_map(|rule| rule.rewrite(url))
+        .filter(|mirror_url| mirror_url != url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(json: serde_json::Value) -> Vec<MirrorRule> {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn rewrites_with_first_matching_rule() {
+        let rules = rules(serde_json::json!([
+            {"prefix": "https://github.com/", "replacement": "https://mirror.example.com/gh/"},
+            {
+                "regex": r"https://storage\.googleapis\.com/([^/]+)/(.*)",
+                "replacement": "https://mirror.example.com/gcs/$1/$2",
+            },
+            {"prefix": "https://", "replacement": "https://fallback.example.com/"},
+        ]));
+
+        assert_eq!(
+            rewrite_url(
+                &rules,
+                "https://github.com/o/r/releases/download/v1/a.tar.gz"
+            ),
+            Some("https://mirror.example.com/gh/o/r/releases/download/v1/a.tar.gz".to_owned()),
+        );
+        assert_eq!(
+            rewrite_url(&rules, "https://storage.googleapis.com/bucket/dir/obj"),
+            Some("https://mirror.example.com/gcs/bucket/dir/obj".to_owned()),
+        );
+        assert_eq!(
+            rewrite_url(&rules, "https://example.com/a"),
+            Some("https://fallback.example.com/example.com/a".to_owned()),
+        );
+        assert_eq!(rewrite_url(&rules, "s3://bucket/key"), None);
+    }
+
+    #[test]
+    fn regex_must_match_whole_url() {
+        let rules = rules(serde_json::json!([
+            {"regex": "github.com", "replacement": "mirror"},
+        ]));
+        assert_eq!(rewrite_url(&rules, "https://github.com/a"), None);
+    }
+
+    #[test]
+    fn invalid_rules() {
+        for (json, expected) in [
+            (
+                serde_json::json!({"replacement": "x"}),
+                "a mirror rule needs exactly one of `prefix` or `regex`",
+            ),
+            (
+                serde_json::json!({"prefix": "a", "regex": "b", "replacement": "x"}),
+                "a mirror rule needs exactly one of `prefix` or `regex`",
+            ),
+            (
+                serde_json::json!({"regex": "(", "replacement": "x"}),
+                "invalid mirror regex `(`",
+            ),
+        ] {
+            let err = serde_json::from_value::<MirrorRule>(json).unwrap_err();
+            assert!(err.to_string().starts_with(expected), "{err}");
+        }
+    }
+
+    #[test]
+    fn serializes_as_written() {
+        let json = serde_json::json!([
+            {"prefix": "https://a/", "replacement": "https://b/"},
+            {"regex": "https://a/(.*)", "replacement": "https://b/$1"},
+        ]);
+        assert_eq!(serde_json::to_value(rules(json.clone())).unwrap(), json);
+    }
+}
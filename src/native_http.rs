@@ -0,0 +1,314 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! An in-process HTTP(S) client, enabled with the `native-http` cargo
+//! feature, for machines where `curl` is missing or spawning it is too slow.
+//! It mirrors the flags that `CurlCommand` passes to `curl` so that callers
+//! cannot tell the difference.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use thiserror::Error;
+use ureq::ResponseExt as _;
+
+use crate::curl;
+use crate::global_config::GlobalConfig;
+use crate::policy;
+use crate::util::HttpStatus;
+use crate::util::fs_ctx;
+
+/// Statuses that `curl --retry` considers transient.
+const TRANSIENT_HTTP_STATUSES: &[u16] = &[408, 429, 500, 502, 503, 504];
+
+#[derive(Debug, Error)]
+pub enum NativeHttpError {
+    // The request failed before the server returned a status, or while
+    // receiving the body.
+    #[error(transparent)]
+    Request(ureq::Error),
+
+    // The server returned 4xx or 5xx.
+    #[error(transparent)]
+    HttpStatus(HttpStatus),
+
+    #[error("failed to write `{0}`")]
+    Write(PathBuf, #[source] io::Error),
+
+    // A redirect led to a URL that a policy does not allow. The string is
+    // the reason.
+    #[error("redirected to a URL that is not allowed: {0}")]
+    RedirectRejected(String),
+}
+
+impl NativeHttpError {
+    /// The equivalent of `CURL_RETRYABLE_EXIT_CODES`.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::Request(
+                ureq::Error::Timeout(_)
+                    | ureq::Error::Io(_)
+                    | ureq::Error::ConnectionFailed
+                    | ureq::Error::Protocol(_)
+            )
+        )
+    }
+
+    fn is_transient(&self) -> bool {
+        match self {
+            Self::Request(ureq::Error::StatusCode(status)) => {
+                TRANSIENT_HTTP_STATUSES.contains(status)
+            }
+            _ => self.is_retryable(),
+        }
+    }
+
+    /// Reports 4xx and 5xx the same way as `curl --fail`.
+    fn into_http_status(self) -> Self {
+        match self {
+            Self::Request(ureq::Error::StatusCode(status)) => {
+                Self::HttpStatus(HttpStatus::from(status as usize))
+            }
+            err => err,
+        }
+    }
+}
+
+/// Returns `url` if the in-process client should fetch it. Other schemes,
+/// such as `file://`, are left to `curl`.
+pub fn handled_url(url: &OsStr) -> Option<&str> {
+    // Setting the client to `curl` picks up settings from `.curlrc` or a CA
+    // bundle that only `curl` knows about.
+    if GlobalConfig::get().http_client.value.as_deref() == Some("curl") {
+        return None;
+    }
+    let url = url.to_str()?;
+    let scheme = url.split_once("://")?.0;
+    (scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https")).then_some(url)
+}
+
+/// Downloads `url` to `target`, retrying transient errors up to `retry`
+/// times with the same backoff as `curl --retry`.
+pub fn get(url: &str, target: &Path, retry: u64) -> Result<(), NativeHttpError> {
+    let agent = agent();
+
+    let mut retries = 0..retry;
+    loop {
+        match get_once(&agent, url, target) {
+            Err(err) if err.is_transient() => {
+                if let Some(retry_num) = retries.next() {
+                    // 1s  ->  2s  ->  4s
+                    thread::sleep(Duration::from_secs(1 << retry_num.min(9)));
+                    continue;
+                }
+                return Err(err.into_http_status());
+            }
+            result => return result.map_err(NativeHttpError::into_http_status),
+        }
+    }
+}
+
+/// Opens the response body for `url` as a stream. Nothing is retried.
+pub fn open(url: &str) -> Result<Box<dyn io::Read>, NativeHttpError> {
+    let response = agent()
+        .get(url)
+        .call()
+        .map_err(|err| NativeHttpError::Request(err).into_http_status())?;
+    check_redirect(&response)?;
+    Ok(Box::new(response.into_body().into_reader()))
+}
+
+/// Fails if `response` came from a URL that the policies in the global
+/// config do not allow, which is checked before the body is read.
+fn check_redirect(response: &ureq::http::Response<ureq::Body>) -> Result<(), NativeHttpError> {
+    policy::check_redirect(
+        &GlobalConfig::get().policies,
+        &response.get_uri().to_string(),
+    )
+    .map_err(NativeHttpError::RedirectRejected)
+}
+
+fn agent() -> ureq::Agent {
+    ureq::Agent::config_builder()
+        .user_agent(curl::USER_AGENT)
+        .timeout_global(curl::max_time().map(Duration::from_secs))
+        .timeout_connect(curl::connect_timeout().map(Duration::from_secs))
+        .build()
+        .new_agent()
+}
+
+fn get_once(agent: &ureq::Agent, url: &str, target: &Path) -> Result<(), NativeHttpError> {
+    // Resume from the end of the partial file that a previous attempt left
+    // behind, if any.
+    let resume_from = fs_ctx::metadata(target).map_or(0, |metadata| metadata.len());
+    let mut request = agent.get(url);
+    if resume_from > 0 {
+        request = request.header("Range", format!("bytes={resume_from}-"));
+    }
+    let response = match request.call() {
+        // The partial file is no use, e.g., because the artifact changed.
+        Err(ureq::Error::StatusCode(416)) if resume_from > 0 => {
+            return restart(agent, url, target);
+        }
+        result => result.map_err(NativeHttpError::Request)?,
+    };
+    check_redirect(&response)?;
+
+    let mut file = if resume_from == 0 || response.status() != 206 {
+        // Either this is the first attempt, or the server ignored the range
+        // and sent the whole artifact.
+        fs_ctx::file_create(target)
+    } else if content_range_start(&response) == Some(resume_from) {
+        fs::OpenOptions::new().append(true).open(target)
+    } else {
+        return restart(agent, url, target);
+    }
+    .map_err(|err| NativeHttpError::Write(target.to_owned(), err))?;
+
+    let mut reader = response.into_body().into_reader();
+    // Distinguish failing to receive the body from failing to write it, as
+    // only the former is worth retrying.
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let n = match io::Read::read(&mut reader, &mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(NativeHttpError::Request(err.into())),
+        };
+        io::Write::write_all(&mut file, &buf[..n])
+            .map_err(|err| NativeHttpError::Write(target.to_owned(), err))?;
+    }
+}
+
+/// Discards the partial file and downloads the whole artifact.
+fn restart(agent: &ureq::Agent, url: &str, target: &Path) -> Result<(), NativeHttpError> {
+    fs_ctx::remove_file(target).map_err(|err| NativeHttpError::Write(target.to_owned(), err))?;
+    get_once(agent, url, target)
+}
+
+/// Parses the first byte position of a `Content-Range: bytes START-END/SIZE`
+/// header.
+fn content_range_start(response: &ureq::http::Response<ureq::Body>) -> Option<u64> {
+    response
+        .headers()
+        .get("Content-Range")?
+        .to_str()
+        .ok()?
+        .strip_prefix("bytes ")?
+        .split_once('-')?
+        .0
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufRead as _;
+    use std::io::BufReader;
+    use std::io::Write as _;
+    use std::net::TcpListener;
+
+    use assert_matches::assert_matches;
+
+    use super::*;
+
+    /// Serves one canned response per connection, in order.
+    fn serve(responses: Vec<String>) -> anyhow::Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/artifact", listener.local_addr()?);
+        thread::spawn(move || {
+            for response in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(&stream);
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                (&stream).write_all(response.as_bytes()).unwrap();
+            }
+        });
+        Ok(url)
+    }
+
+    fn response(status: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len(),
+        )
+    }
+
+    #[test]
+    fn test_handled_url() {
+        assert_eq!(
+            handled_url(OsStr::new("https://example.com/a")),
+            Some("https://example.com/a"),
+        );
+        assert_eq!(
+            handled_url(OsStr::new("HTTP://example.com/a")),
+            Some("HTTP://example.com/a"),
+        );
+        assert_eq!(handled_url(OsStr::new("file:///tmp/a")), None);
+        assert_eq!(handled_url(OsStr::new("example.com/a")), None);
+    }
+
+    #[test]
+    fn downloads_after_transient_error() -> anyhow::Result<()> {
+        let url = serve(vec![
+            response("503 Service Unavailable", ""),
+            response("200 OK", "hello"),
+        ])?;
+        let temp_dir = tempfile::tempdir()?;
+        let target = temp_dir.path().join("artifact");
+
+        get(&url, &target, 1)?;
+        assert_eq!(std::fs::read_to_string(&target)?, "hello");
+        Ok(())
+    }
+
+    #[test]
+    fn resumes_partial_download() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let target = temp_dir.path().join("artifact");
+
+        std::fs::write(&target, "hel")?;
+        let url = serve(vec![
+            "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 3-4/5\r\n\
+             Content-Length: 2\r\nConnection: close\r\n\r\nlo"
+                .to_owned(),
+        ])?;
+        get(&url, &target, 0)?;
+        assert_eq!(std::fs::read_to_string(&target)?, "hello");
+
+        // A server that does not support ranges sends everything again.
+        std::fs::write(&target, "hel")?;
+        let url = serve(vec![response("200 OK", "hello")])?;
+        get(&url, &target, 0)?;
+        assert_eq!(std::fs::read_to_string(&target)?, "hello");
+        Ok(())
+    }
+
+    #[test]
+    fn reports_http_status() -> anyhow::Result<()> {
+        let url = serve(vec![response("404 Not Found", "")])?;
+        let temp_dir = tempfile::tempdir()?;
+
+        let err = get(&url, &temp_dir.path().join("artifact"), 0).unwrap_err();
+        assert_matches!(err, NativeHttpError::HttpStatus(HttpStatus::NotFound));
+        assert!(!err.is_retryable());
+        Ok(())
+    }
+}
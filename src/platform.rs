@@ -40,7 +40,7 @@ macro_rules! if_platform {
 
 pub(crate) use if_platform;
 
-pub const SUPPORTED_PLATFORM: &str = if_platform! {
+pub const SUPPORTED_PLATFORM: &str = self::if_platform! {
     linux_aarch64 = "linux-aarch64",
     linux_riscv64 = "linux-riscv64",
     linux_x86_64 = "linux-x86_64",
@@ -0,0 +1,286 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! A policy from the global config that restricts which providers DotSlash
+//! may use, e.g., so that it only ever fetches over `https://` from approved
+//! hosts. Providers that the policy rejects are skipped as if they were not
+//! in the DotSlash file.
+
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::global_config::Setting;
+
+/// For each kind of restriction, an `allow_*` list (if present) names the
+/// only values that are allowed and a `deny_*` list names values that are
+/// not. A value in both is denied.
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    /// Provider `type`s, e.g., `http` or `s3`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    allow_provider_types: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    deny_provider_types: Vec<String>,
+
+    /// URL schemes, e.g., `https`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    allow_schemes: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    deny_schemes: Vec<String>,
+
+    /// URL hosts, e.g., `github.com`. `*.example.com` matches any subdomain
+    /// of `example.com`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    allow_hosts: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    deny_hosts: Vec<String>,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum PolicyViolation {
+    #[error("provider type `{0}` is not allowed")]
+    ProviderType(String),
+
+    #[error("scheme `{scheme}` of `{url}` is not allowed")]
+    Scheme { scheme: String, url: String },
+
+    #[error("host `{host}` of `{url}` is not allowed")]
+    Host { host: String, url: String },
+
+    /// Checking schemes or hosts requires knowing the URL.
+    #[error("provider type `{0}` does not say which URL it fetches from")]
+    UnknownUrl(String),
+}
+
+impl Policy {
+    /// Whether checking a provider requires `Provider::artifact_url`.
+    pub fn restricts_urls(&self) -> bool {
+        self.allow_schemes.is_some()
+            || !self.deny_schemes.is_empty()
+            || self.allow_hosts.is_some()
+            || !self.deny_hosts.is_empty()
+    }
+
+    pub fn check_provider_type(&self, provider_type: &str) -> Result<(), PolicyViolation> {
+        if is_allowed(
+            self.allow_provider_types.as_deref(),
+            &self.deny_provider_types,
+            |allowed| allowed == provider_type,
+        ) {
+            Ok(())
+        } else {
+            Err(PolicyViolation::ProviderType(provider_type.to_owned()))
+        }
+    }
+
+    /// `url` is what `Provider::artifact_url` returned for a provider of type
+    /// `provider_type`.
+    pub fn check_url(&self, provider_type: &str, url: Option<&str>) -> Result<(), PolicyViolation> {
+        if !self.restricts_urls() {
+            return Ok(());
+        }
+        let Some(url) = url else {
+            return Err(PolicyViolation::UnknownUrl(provider_type.to_owned()));
+        };
+
+        let (scheme, host) = scheme_and_host(url);
+        if !is_allowed(
+            self.allow_schemes.as_deref(),
+            &self.deny_schemes,
+            |allowed| allowed.eq_ignore_ascii_case(scheme),
+        ) {
+            return Err(PolicyViolation::Scheme {
+                scheme: scheme.to_owned(),
+                url: url.to_owned(),
+            });
+        }
+        if !is_allowed(self.allow_hosts.as_deref(), &self.deny_hosts, |pattern| {
+            host_matches(pattern, &host)
+        }) {
+            return Err(PolicyViolation::Host {
+                host,
+                url: url.to_owned(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Whether any of `policies` restricts URLs, in which case HTTP clients must
+/// check where they were redirected to with `check_redirect`.
+pub fn restrict_redirects(policies: &[Setting<Policy>]) -> bool {
+    policies.iter().any(|policy| policy.value.restricts_urls())
+}
+
+/// Checks `url`, which an HTTP client ended up at after following redirects,
+/// against `policies`. Only the URL of a provider is checked before it is
+/// tried, so without this a redirect could get around the policies. Unlike
+/// that check, this one can only be made once the request has been sent, so
+/// callers must discard whatever they fetched if it fails.
+pub fn check_redirect(policies: &[Setting<Policy>], url: &str) -> Result<(), String> {
+    for policy in policies {
+        if let Err(violation) = policy.value.check_url("http", Some(url)) {
+            return Err(format!("{violation} (policy in {})", policy.source));
+        }
+    }
+    Ok(())
+}
+
+fn is_allowed(allow: Option<&[String]>, deny: &[String], matches: impl Fn(&str) -> bool) -> bool {
+    let allowed = allow.is_none_or(|allow| allow.iter().any(|value| matches(value)));
+    allowed && !deny.iter().any(|value| matches(value))
+}
+
+fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain.to_ascii_lowercase().as_str())
+            .is_some_and(|subdomain| subdomain.len() > 1 && subdomain.ends_with('.')),
+        None => pattern.eq_ignore_ascii_case(host),
+    }
+}
+
+/// Splits `scheme://[userinfo@]host[:port]/...` into its scheme and its
+/// (lowercase) host. Either may be empty, e.g., for `file:///path`.
+fn scheme_and_host(url: &str) -> (&str, String) {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return ("", String::new());
+    };
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host_and_port = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    let host = if let Some(rest) = host_and_port.strip_prefix('[') {
+        // An IPv6 address, e.g., `[::1]:8080`.
+        rest.split(']').next().unwrap_or_default()
+    } else {
+        host_and_port.split(':').next().unwrap_or_default()
+    };
+    (scheme, host.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(json: serde_json::Value) -> Policy {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn default_policy_allows_everything() {
+        let policy = Policy::default();
+        assert!(!policy.restricts_urls());
+        assert_eq!(policy.check_provider_type("anything"), Ok(()));
+        assert_eq!(policy.check_url("anything", None), Ok(()));
+    }
+
+    #[test]
+    fn provider_types() {
+        let policy = policy(serde_json::json!({
+            "allow_provider_types": ["http", "s3"],
+            "deny_provider_types": ["s3"],
+        }));
+        assert_eq!(policy.check_provider_type("http"), Ok(()));
+        assert_eq!(
+            policy.check_provider_type("s3"),
+            Err(PolicyViolation::ProviderType("s3".to_owned())),
+        );
+        assert_eq!(
+            policy.check_provider_type("gcs"),
+            Err(PolicyViolation::ProviderType("gcs".to_owned())),
+        );
+    }
+
+    #[test]
+    fn schemes_and_hosts() {
+        let policy = policy(serde_json::json!({
+            "deny_schemes": ["http"],
+            "allow_hosts": ["github.com", "*.example.com"],
+            "deny_hosts": ["bad.example.com"],
+        }));
+
+        for url in [
+            "https://github.com/a",
+            "https://GitHub.com:443/a",
+            "https://user@cdn.example.com/a",
+            "https://a.b.example.com?a",
+        ] {
+            assert_eq!(policy.check_url("http", Some(url)), Ok(()), "{url}");
+        }
+
+        assert_eq!(
+            policy.check_url("http", Some("HTTP://github.com/a")),
+            Err(PolicyViolation::Scheme {
+                scheme: "HTTP".to_owned(),
+                url: "HTTP://github.com/a".to_owned(),
+            }),
+        );
+        for (url, host) in [
+            ("https://example.com/a", "example.com"),
+            ("https://notexample.com/a", "notexample.com"),
+            ("https://bad.example.com/a", "bad.example.com"),
+            ("https://github.com.evil.com/a", "github.com.evil.com"),
+            ("https://github.com@evil.com/a", "evil.com"),
+            ("https://[::1]:8080/a", "::1"),
+            ("file:///tmp/a", ""),
+        ] {
+            assert_eq!(
+                policy.check_url("http", Some(url)),
+                Err(PolicyViolation::Host {
+                    host: host.to_owned(),
+                    url: url.to_owned(),
+                }),
+            );
+        }
+        assert_eq!(
+            policy.check_url("custom", None),
+            Err(PolicyViolation::UnknownUrl("custom".to_owned())),
+        );
+    }
+
+    #[test]
+    fn redirects() {
+        use crate::global_config::SettingSource;
+
+        let policies = [
+            Setting {
+                value: Policy::default(),
+                source: SettingSource::Default,
+            },
+            Setting {
+                value: policy(serde_json::json!({"allow_hosts": ["github.com"]})),
+                source: SettingSource::Env { name: "TEST" },
+            },
+        ];
+        assert!(restrict_redirects(&policies));
+        assert!(!restrict_redirects(&policies[..1]));
+        assert_eq!(check_redirect(&policies, "https://github.com/a"), Ok(()));
+        assert_eq!(
+            check_redirect(&policies, "https://evil.com/a"),
+            Err(
+                "host `evil.com` of `https://evil.com/a` is not allowed (policy in `TEST`)"
+                    .to_owned()
+            ),
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        let err =
+            serde_json::from_value::<Policy>(serde_json::json!({"allow_host": []})).unwrap_err();
+        assert!(
+            err.to_string().starts_with("unknown field `allow_host`"),
+            "{err}"
+        );
+    }
+}
@@ -23,8 +23,9 @@ use crate::config::ProvidersOrder;
 use crate::curl::CurlCommand;
 use crate::curl::FetchContext;
 use crate::fetch_method::ArtifactFormat;
-
-type LooseArtifactEntry = ArtifactEntry<String>;
+use crate::inspect_artifact::guess_path;
+use crate::inspect_artifact::sniff_format;
+use crate::inspect_artifact::sniff_magic;
 
 /// This function creates an approximate ArtifactEntry for the specified URL
 /// and writes it to stdout as pretty-printed JSON.
@@ -48,57 +49,59 @@ pub fn print_entry_for_url(url: &OsStr) -> anyhow::Result<()> {
     let size = io::copy(&mut file, &mut hasher)?;
     let hex_digest = format!("{}", hasher.finalize().to_hex());
 
-    let entry_json = serialize_entry(url, size, hex_digest)?;
+    let format = sniff_format(tempfile.path())?;
+    if format == ArtifactFormat::Plain {
+        let magic = sniff_magic(tempfile.path())?;
+        if !magic.is_executable() {
+            // E.g., the HTML of a login page rather than the artifact.
+            eprintln!("warning: `{url}` is neither an archive nor an executable");
+        }
+    }
+    let path = match exe_name_from_url(url, format) {
+        Some(exe_name) => guess_path(tempfile.path(), format, exe_name)?,
+        None => "TODO: specify the appropriate `path` for this artifact".to_owned(),
+    };
+    let entry_json = serialize_entry(url, size, hex_digest, format, &path)?;
     println!("{}", entry_json);
     Ok(())
 }
 
-fn serialize_entry(url: &str, size: u64, hex_digest: String) -> anyhow::Result<String> {
-    let format = match guess_artifact_format_from_url(url.as_bytes()) {
-        ArtifactFormat::Plain => {
-            "TODO: specify this value; could not guess format from URL".to_owned()
-        }
-        format => serde_json::to_value(format)?.as_str().unwrap().to_owned(),
-    };
-    let entry = LooseArtifactEntry {
+fn serialize_entry(
+    url: &str,
+    size: u64,
+    hex_digest: String,
+    format: ArtifactFormat,
+    path: &str,
+) -> anyhow::Result<String> {
+    let entry = ArtifactEntry {
         size,
         hash: HashAlgorithm::Blake3,
         digest: hex_digest.try_into()?,
         format,
-        path: "TODO: specify the appropriate `path` for this artifact".parse()?,
+        path: path.parse()?,
         providers: vec![serde_json::json!({"url": url})],
         arg0: Arg0::default(),
         providers_order: ProvidersOrder::Sequential,
+        additional_digests: vec![],
         readonly: true,
     };
     let entry_json = serde_json::to_string_pretty(&entry)?;
     Ok(entry_json)
 }
 
-fn guess_artifact_format_from_url(url: &[u8]) -> ArtifactFormat {
-    if url.ends_with(b".tar.bz2") {
-        ArtifactFormat::TarBzip2
-    } else if url.ends_with(b".tar.gz") || url.ends_with(b".tgz") {
-        ArtifactFormat::TarGz
-    } else if url.ends_with(b".tar.zst") || url.ends_with(b".tzst") {
-        ArtifactFormat::TarZstd
-    } else if url.ends_with(b".tar.xz") {
-        ArtifactFormat::TarXz
-    } else if url.ends_with(b".tar") {
-        ArtifactFormat::Tar
-    } else if url.ends_with(b".zip") {
-        ArtifactFormat::Zip
-    } else if url.ends_with(b".bz2") {
-        ArtifactFormat::Bzip2
-    } else if url.ends_with(b".gz") {
-        ArtifactFormat::Gz
-    } else if url.ends_with(b".xz") {
-        ArtifactFormat::Xz
-    } else if url.ends_with(b".zst") {
-        ArtifactFormat::Zstd
-    } else {
-        ArtifactFormat::Plain
-    }
+/// The name of the file at `url`, minus the extension for `format`. This is
+/// the likely name of the executable, e.g., `rg` for `.../rg.gz`.
+fn exe_name_from_url(url: &str, format: ArtifactFormat) -> Option<&str> {
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    let (_, url_path) = url
+        .split_once("://")
+        .map_or(url, |(_, rest)| rest)
+        .split_once('/')?;
+    let file_name = url_path.rsplit('/').next().unwrap_or_default();
+    let exe_name = file_name
+        .strip_suffix(format.extension())
+        .unwrap_or(file_name);
+    (!exe_name.is_empty()).then_some(exe_name)
 }
 
 #[cfg(test)]
@@ -108,12 +111,18 @@ mod tests {
     use super::*;
 
     #[test]
-    fn serialize_entry_recognized_format() -> anyhow::Result<()> {
+    fn serialize_entry_archive() -> anyhow::Result<()> {
         let url = "https://github.com/zertosh/dotslash_fixtures/raw/462625c6bf2671439dce66bd5bc40b05f2ed8819/pack.tar.gz";
         let size = 48689_u64;
         let hex_digest =
             "068464830bd5c276e085a4eab5ef9cc57159f94273db296d6a638e49b78ca55f".to_owned();
-        let entry_json = serialize_entry(url, size, hex_digest.clone())?;
+        let entry_json = serialize_entry(
+            url,
+            size,
+            hex_digest.clone(),
+            ArtifactFormat::TarGz,
+            "subdir/print_argv",
+        )?;
         let entry = serde_json::from_str::<ArtifactEntry>(&entry_json)?;
         // Ensure the output parses as a valid ArtifactEntry.
         assert_eq!(
@@ -122,10 +131,11 @@ mod tests {
                 hash: HashAlgorithm::Blake3,
                 digest: hex_digest.try_into()?,
                 format: ArtifactFormat::TarGz,
-                path: "TODO: specify the appropriate `path` for this artifact".parse()?,
+                path: "subdir/print_argv".parse()?,
                 providers: vec![serde_json::json!({"url": url})],
                 arg0: Arg0::DotslashFile,
                 providers_order: ProvidersOrder::Sequential,
+                additional_digests: vec![],
                 readonly: true,
             },
             entry,
@@ -134,20 +144,24 @@ mod tests {
     }
 
     #[test]
-    fn serialize_entry_unrecognized_format() -> anyhow::Result<()> {
+    fn serialize_entry_plain() -> anyhow::Result<()> {
         let url = "http://example.com/somefile";
         let size = 48689_u64;
         let hex_digest =
             "068464830bd5c276e085a4eab5ef9cc57159f94273db296d6a638e49b78ca55f".to_owned();
-        let entry_json = serialize_entry(url, size, hex_digest.clone())?;
-        // Note that `entry_json` cannot be deserialized into an ArtifactEntry
-        // due to the illegal value for `format`.
+        let entry_json = serialize_entry(
+            url,
+            size,
+            hex_digest.clone(),
+            ArtifactFormat::Plain,
+            "somefile",
+        )?;
+        // A plain artifact has no `format`.
         let expected = serde_json::json!({
             "size": size,
             "hash": "blake3",
             "digest": hex_digest,
-            "format": "TODO: specify this value; could not guess format from URL",
-            "path": "TODO: specify the appropriate `path` for this artifact",
+            "path": "somefile",
             "providers": [{"url": url}],
         });
         let actual = serde_json::from_str::<Value>(&entry_json)?;
@@ -156,34 +170,37 @@ mod tests {
     }
 
     #[test]
-    fn guess_artifact_format() {
+    fn exe_name_from_url_strips_extension() {
         #[track_caller]
-        fn test(url: &str, expected_format: ArtifactFormat) {
-            assert_eq!(
-                expected_format,
-                guess_artifact_format_from_url(url.as_bytes())
-            );
+        fn test(url: &str, format: ArtifactFormat, expected: Option<&str>) {
+            assert_eq!(exe_name_from_url(url, format), expected);
         }
 
-        test("http://example.com/foo.tar.gz", ArtifactFormat::TarGz);
-        test("http://example.com/foo.tgz", ArtifactFormat::TarGz);
-        test("http://example.com/foo.tar.xz", ArtifactFormat::TarXz);
-        test("http://example.com/foo.tar.zst", ArtifactFormat::TarZstd);
-        test("http://example.com/foo.tzst", ArtifactFormat::TarZstd);
-        test("http://example.com/foo.tar", ArtifactFormat::Tar);
-        test("http://example.com/foo.gz", ArtifactFormat::Gz);
-        test("http://example.com/foo.zip", ArtifactFormat::Zip);
-        test("http://example.com/foo.zst", ArtifactFormat::Zstd);
-
-        // These "backwards" extensions are interpreted as Tar.
-        test("http://example.com/foo.zst.tar", ArtifactFormat::Tar);
-        test("http://example.com/foo.gz.tar", ArtifactFormat::Tar);
-
-        test("http://example.com/foo", ArtifactFormat::Plain);
-        test("http://example.com/foo.bar", ArtifactFormat::Plain);
-        test("http://example.com/foo.zstd", ArtifactFormat::Plain);
+        test("http://example.com/rg", ArtifactFormat::Plain, Some("rg"));
+        test("http://example.com/a/rg.gz", ArtifactFormat::Gz, Some("rg"));
+        test(
+            "http://example.com/rg.zst",
+            ArtifactFormat::Zstd,
+            Some("rg"),
+        );
+        test(
+            "http://example.com/rg.tar.gz?dl=1#x",
+            ArtifactFormat::TarGz,
+            Some("rg"),
+        );
+        // The extension is only stripped if it matches the content.
+        test(
+            "http://example.com/rg.gz",
+            ArtifactFormat::Plain,
+            Some("rg.gz"),
+        );
 
-        // The tool currently ignores query parameters.
-        test("http://example.com/foo.tar.gz?dl=1", ArtifactFormat::Plain);
+        test("http://example.com", ArtifactFormat::Plain, None);
+        test("http://example.com/", ArtifactFormat::Plain, None);
+        test(
+            "http://example.com/dir/?name=rg",
+            ArtifactFormat::Plain,
+            None,
+        );
     }
 }
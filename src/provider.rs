@@ -8,6 +8,7 @@
  * above-listed licenses.
  */
 
+use std::io::Read;
 use std::path::Path;
 
 use serde_json::Value;
@@ -40,6 +41,32 @@ pub trait Provider {
         fetch_lock: &FileLock,
         artifact_entry: &ArtifactEntry,
     ) -> anyhow::Result<()>;
+
+    /// Optionally, opens the artifact as a stream so that it can be verified
+    /// and unpacked while it is being fetched instead of being written to
+    /// disk and read back. The arguments are the same as for
+    /// `fetch_artifact`. Returns `None` if the provider does not support
+    /// this, in which case `fetch_artifact` is used.
+    ///
+    /// Reading the stream must fail if the fetch fails, even part of the way
+    /// through. Callers then call `fetch_artifact` with whatever was read so
+    /// far already in `destination`, so that it can resume from there.
+    fn open_artifact_stream(
+        &self,
+        _provider_config: &Value,
+        _fetch_lock: &FileLock,
+        _artifact_entry: &ArtifactEntry,
+    ) -> anyhow::Result<Option<Box<dyn Read>>> {
+        Ok(None)
+    }
+
+    /// Optionally, the URL that the artifact is fetched from, even if the
+    /// provider does not fetch it over HTTP itself. If a mirror rule in the
+    /// global config matches it, the mirrored URL is tried with the `http`
+    /// provider before this provider.
+    fn artifact_url(&self, _provider_config: &Value) -> anyhow::Result<Option<String>> {
+        Ok(None)
+    }
 }
 
 pub trait ProviderFactory {
@@ -23,7 +23,8 @@ use crate::util::FileLock;
 pub struct S3Provider {}
 
 #[derive(Deserialize, Debug)]
-struct S3ProviderConfig {
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct S3ProviderConfig {
     bucket: String,
     key: String,
     region: Option<String>,
@@ -64,4 +65,11 @@ impl Provider for S3Provider {
         }
         Ok(())
     }
+
+    /// The `s3://` URL that is passed to the AWS CLI. A mirror rule has to
+    /// rewrite it to an `http(s)://` URL to be of use.
+    fn artifact_url(&self, provider_config: &Value) -> anyhow::Result<Option<String>> {
+        let S3ProviderConfig { bucket, key, .. } = <_>::deserialize(provider_config)?;
+        Ok(Some(format!("s3://{bucket}/{key}")))
+    }
 }
@@ -0,0 +1,527 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! A JSON Schema for the JSON in a DotSlash file (i.e., everything after the
+//! `#!/usr/bin/env dotslash` line), so that editors and CI can check
+//! DotSlash files without running DotSlash. The tests check it against the
+//! serde definitions that DotSlash actually parses with.
+
+use serde_json::Value;
+use serde_json::json;
+
+use crate::config::Arg0;
+use crate::config::HashAlgorithm;
+use crate::config::ProvidersOrder;
+use crate::exec_provider::NAME_PATTERN;
+use crate::fetch_method::ArtifactFormat;
+use crate::signature::SIGNATURE_FIELD;
+use crate::update::TEMPLATE_FIELD;
+use crate::update::VARS_FIELD;
+
+const SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Every variant that can appear in a DotSlash file. `Plain` is written by
+/// omitting `format`.
+const ARTIFACT_FORMATS: [ArtifactFormat; 10] = [
+    ArtifactFormat::Bzip2,
+    ArtifactFormat::Gz,
+    ArtifactFormat::Tar,
+    ArtifactFormat::TarBzip2,
+    ArtifactFormat::TarGz,
+    ArtifactFormat::TarZstd,
+    ArtifactFormat::TarXz,
+    ArtifactFormat::Xz,
+    ArtifactFormat::Zstd,
+    ArtifactFormat::Zip,
+];
+
+const ARG0S: [Arg0; 2] = [Arg0::DotslashFile, Arg0::UnderlyingExecutable];
+
+const PROVIDERS_ORDERS: [ProvidersOrder; 2] =
+    [ProvidersOrder::Sequential, ProvidersOrder::WeightedRandom];
+
+/// The provider types that `DefaultProviderFactory` knows, with the
+/// definition of the config of each.
+const PROVIDER_TYPES: [(&str, &str); 5] = [
+    ("http", "HttpProviderConfig"),
+    ("github-release", "GitHubReleaseProviderConfig"),
+    ("s3", "S3ProviderConfig"),
+    ("gcs", "GcsProviderConfig"),
+    ("exec", "ExecProviderConfig"),
+];
+
+/// A normalized, relative path with `/` as the separator, as `ArtifactPath`
+/// requires, except for `.` and `..` components (see `PATH_DOT_COMPONENT`).
+const PATH_PATTERN: &str = r"^[^/\\\x00]+(/[^/\\\x00]+)*$";
+const PATH_DOT_COMPONENT: &str = r"(^|/)\.\.?(/|$)";
+
+pub fn dotslash_file_schema() -> Value {
+    let provider_types = PROVIDER_TYPES
+        .iter()
+        .map(|(provider_type, _)| *provider_type)
+        .collect::<Vec<_>>();
+    // The config of a provider depends on its `type`, which is `http` if it
+    // is omitted.
+    let provider_configs = PROVIDER_TYPES
+        .iter()
+        .map(|(provider_type, definition)| {
+            let mut condition = json!({"properties": {"type": {"const": provider_type}}});
+            if *provider_type != "http" {
+                condition["required"] = json!(["type"]);
+            }
+            json!({
+                "if": condition,
+                "then": {"$ref": format!("#/$defs/{definition}")},
+            })
+        })
+        .collect::<Vec<_>>();
+    // `HashDigest` also has a `hash` and `digest`, so it is checked the same
+    // way.
+    let digest_lens = HashAlgorithm::ALL
+        .iter()
+        .map(|hash| {
+            json!({
+                "if": {"properties": {"hash": {"const": hash.as_str()}}},
+                "then": {
+                    "properties": {
+                        "digest": {"pattern": format!("^[0-9a-f]{{{}}}$", hash.digest_len())},
+                    },
+                },
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "$schema": SCHEMA_DIALECT,
+        "title": "DotSlash file",
+        "description": "The JSON (with comments) that follows `#!/usr/bin/env dotslash` in a DotSlash file.",
+        "type": "object",
+        "required": ["name", "platforms"],
+        "properties": {
+            "name": {
+                "description": "The name of the tool, used in messages.",
+                "type": "string",
+            },
+            "platforms": {
+                "description": "The artifact for each platform, e.g., `linux-x86_64`.",
+                "type": "object",
+                "additionalProperties": {"$ref": "#/$defs/ArtifactEntry"},
+            },
+            SIGNATURE_FIELD: {"$ref": "#/$defs/Signature"},
+            VARS_FIELD: {
+                "description": "Variables for the templates of providers. Ignored by DotSlash, but set by `dotslash -- update`.",
+                "type": "object",
+                "additionalProperties": {"type": "string"},
+            },
+        },
+        "$defs": {
+            "ArtifactEntry": {
+                "type": "object",
+                "required": ["size", "hash", "digest", "path", "providers"],
+                "properties": {
+                    "size": {
+                        "description": "The size of the artifact in bytes.",
+                        "type": "integer",
+                        "minimum": 0,
+                    },
+                    "hash": {"$ref": "#/$defs/HashAlgorithm"},
+                    "digest": {"$ref": "#/$defs/Digest"},
+                    "format": {"$ref": "#/$defs/ArtifactFormat"},
+                    "path": {"$ref": "#/$defs/ArtifactPath"},
+                    "providers": {
+                        "type": "array",
+                        "items": {"$ref": "#/$defs/Provider"},
+                    },
+                    "arg0": {"$ref": "#/$defs/Arg0"},
+                    "readonly": {
+                        "description": "Whether the unpacked artifact is made read-only.",
+                        "type": "boolean",
+                        "default": true,
+                    },
+                    "providers_order": {"$ref": "#/$defs/ProvidersOrder"},
+                    "additional_digests": {
+                        "description": "Digests that the artifact must also match.",
+                        "type": "array",
+                        "items": {"$ref": "#/$defs/HashDigest"},
+                    },
+                },
+                "allOf": digest_lens,
+            },
+            "HashDigest": {
+                "type": "object",
+                "required": ["hash", "digest"],
+                "properties": {
+                    "hash": {"$ref": "#/$defs/HashAlgorithm"},
+                    "digest": {"$ref": "#/$defs/Digest"},
+                },
+                "allOf": digest_lens,
+            },
+            "Digest": {
+                "description": "The digest as lowercase hex.",
+                "type": "string",
+                "pattern": "^([0-9a-f]{64}|[0-9a-f]{128})$",
+            },
+            "ArtifactPath": {
+                "description": "The path of the executable within the unpacked artifact: relative, normalized and separated by `/`.",
+                "type": "string",
+                "pattern": PATH_PATTERN,
+                "not": {"pattern": PATH_DOT_COMPONENT},
+            },
+            "ArtifactFormat": {
+                "description": "How the artifact is packed. Omitted for an executable that is not packed.",
+                "enum": to_values(&ARTIFACT_FORMATS),
+            },
+            "HashAlgorithm": {"enum": to_values(&HashAlgorithm::ALL)},
+            "Arg0": {
+                "description": "What `argv[0]` is set to: the DotSlash file or the executable in the cache. Has no effect on Windows.",
+                "enum": to_values(&ARG0S),
+                "default": to_value(Arg0::default()),
+            },
+            "ProvidersOrder": {
+                "description": "The order in which providers are tried. `weighted-random` uses the `weight` of each provider.",
+                "enum": to_values(&PROVIDERS_ORDERS),
+                "default": to_value(ProvidersOrder::default()),
+            },
+            "Provider": {
+                "type": "object",
+                "properties": {
+                    "type": {"enum": provider_types, "default": "http"},
+                    "weight": {
+                        "description": "How likely the provider is to be tried first with `weighted-random`.",
+                        "type": "integer",
+                        "minimum": 1,
+                        "default": 1,
+                    },
+                    TEMPLATE_FIELD: {
+                        "description": "Templates for the fields of the provider, in which `{NAME}` stands for a variable in `vars`. Ignored by DotSlash, but filled in by `dotslash -- update`.",
+                        "type": "object",
+                        "additionalProperties": {"type": "string"},
+                    },
+                },
+                "allOf": provider_configs,
+            },
+            "HttpProviderConfig": {
+                "type": "object",
+                "required": ["url"],
+                "properties": {"url": {"type": "string"}},
+            },
+            "GitHubReleaseProviderConfig": {
+                "type": "object",
+                "required": ["tag", "repo", "name"],
+                "properties": {
+                    "tag": {"type": "string"},
+                    "repo": {"description": "E.g., `facebook/dotslash`.", "type": "string"},
+                    "name": {"description": "The name of the release asset.", "type": "string"},
+                },
+            },
+            "S3ProviderConfig": {
+                "type": "object",
+                "required": ["bucket", "key"],
+                "properties": {
+                    "bucket": {"type": "string"},
+                    "key": {"type": "string"},
+                    "region": {"type": "string"},
+                },
+            },
+            "GcsProviderConfig": {
+                "type": "object",
+                "required": ["bucket", "object"],
+                "properties": {
+                    "bucket": {"type": "string"},
+                    "object": {"type": "string"},
+                },
+            },
+            "ExecProviderConfig": {
+                "description": "Runs `dotslash-provider-{name}` from `PATH`, sending a JSON request on stdin and reading a JSON result from stdout. Other fields are passed along to it.",
+                "type": "object",
+                "required": ["name"],
+                "properties": {
+                    "name": {"type": "string", "pattern": NAME_PATTERN},
+                },
+            },
+            "Signature": {
+                "description": "Written by `dotslash -- sign`.",
+                "type": "object",
+                "required": ["key_id", "signature"],
+                "properties": {
+                    "key_id": {"type": "string"},
+                    "signature": {"type": "string"},
+                },
+                "additionalProperties": false,
+            },
+        },
+    })
+}
+
+fn to_value<T: serde::Serialize>(value: T) -> Value {
+    serde_json::to_value(value).expect("enum variants serialize as strings")
+}
+
+fn to_values<T: serde::Serialize + Copy>(values: &[T]) -> Vec<Value> {
+    values.iter().copied().map(to_value).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use regex_lite::Regex;
+    use serde::Serialize;
+    use serde::de::DeserializeOwned;
+
+    use super::*;
+    use crate::artifact_path::ArtifactPath;
+    use crate::config::ARTIFACT_ENTRY_FIELDS;
+    use crate::config::ArtifactEntry;
+    use crate::config::ConfigFile;
+    use crate::config::HashDigest;
+    use crate::default_provider_factory::DefaultProviderFactory;
+    use crate::exec_provider::ExecProviderConfig;
+    use crate::exec_provider::is_valid_name;
+    use crate::gcs_provider::GcsProviderConfig;
+    use crate::github_release_provider::GitHubReleaseProviderConfig;
+    use crate::http_provider::HttpProviderConfig;
+    use crate::provider::ProviderFactory as _;
+    use crate::s3_provider::S3ProviderConfig;
+
+    fn definition(name: &str) -> Value {
+        dotslash_file_schema()["$defs"][name].clone()
+    }
+
+    fn keys(value: &Value) -> BTreeSet<String> {
+        value.as_object().unwrap().keys().cloned().collect()
+    }
+
+    fn strings(value: &Value) -> BTreeSet<String> {
+        value
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|value| value.as_str().unwrap().to_owned())
+            .collect()
+    }
+
+    /// Checks that `T` has the properties of `definition` and requires the
+    /// ones it requires. `example` must have a valid value for each
+    /// property.
+    #[track_caller]
+    fn check_struct<T: DeserializeOwned>(definition: &Value, example: &Value) {
+        assert_eq!(keys(example), keys(&definition["properties"]));
+        serde_json::from_value::<T>(example.clone()).unwrap();
+        let required = strings(&definition["required"]);
+        for property in keys(example) {
+            let mut partial = example.clone();
+            partial.as_object_mut().unwrap().remove(&property);
+            assert_eq!(
+                serde_json::from_value::<T>(partial).is_err(),
+                required.contains(&property),
+                "`{property}` is required",
+            );
+        }
+    }
+
+    /// Checks that the config of a provider has the properties of its
+    /// definition, which are all strings.
+    #[track_caller]
+    fn check_provider_config<T: DeserializeOwned + Serialize>(name: &str) {
+        let definition = definition(name);
+        let example = keys(&definition["properties"])
+            .into_iter()
+            .map(|property| (property, json!("value")))
+            .collect::<serde_json::Map<_, _>>();
+        let example = Value::Object(example);
+        check_struct::<T>(&definition, &example);
+        // Catches fields that are missing from the definition.
+        let config = serde_json::from_value::<T>(example).unwrap();
+        assert_eq!(
+            keys(&serde_json::to_value(config).unwrap()),
+            keys(&definition["properties"]),
+        );
+    }
+
+    /// Checks that the `enum` of `name` has `len` values, each a different
+    /// variant of `T`.
+    #[track_caller]
+    fn check_enum<T: DeserializeOwned + PartialEq + std::fmt::Debug>(name: &str, len: usize) {
+        let values = definition(name)["enum"].as_array().unwrap().clone();
+        assert_eq!(values.len(), len);
+        let variants = values
+            .into_iter()
+            .map(|value| serde_json::from_value::<T>(value).unwrap())
+            .collect::<Vec<_>>();
+        for (i, variant) in variants.iter().enumerate() {
+            assert!(!variants[..i].contains(variant), "duplicate {variant:?}");
+        }
+    }
+
+    #[test]
+    fn config_file_matches_schema() {
+        let schema = dotslash_file_schema();
+        let example = json!({"name": "tool", "platforms": {}});
+        let required = strings(&schema["required"]);
+        assert_eq!(keys(&example), required);
+        check_struct::<ConfigFile>(
+            &json!({"properties": example, "required": required}),
+            &example,
+        );
+        assert_eq!(
+            keys(&schema["properties"]),
+            ["name", "platforms", SIGNATURE_FIELD, VARS_FIELD]
+                .map(str::to_owned)
+                .into(),
+        );
+    }
+
+    #[test]
+    fn artifact_entry_matches_schema() {
+        let entry = definition("ArtifactEntry");
+        assert_eq!(
+            keys(&entry["properties"]),
+            ARTIFACT_ENTRY_FIELDS.map(str::to_owned).into(),
+        );
+        let example = json!({
+            "size": 5,
+            "hash": "blake3",
+            "digest": "a".repeat(64),
+            "format": "tar.gz",
+            "path": "bin/tool",
+            "providers": [],
+            "arg0": "underlying-executable",
+            "readonly": false,
+            "providers_order": "weighted-random",
+            "additional_digests": [],
+        });
+        check_struct::<ArtifactEntry>(&entry, &example);
+        check_struct::<HashDigest>(
+            &definition("HashDigest"),
+            &json!({"hash": "sha512", "digest": "a".repeat(128)}),
+        );
+    }
+
+    #[test]
+    fn enums_match_schema() {
+        // These fail to compile when a variant is added, as a reminder to
+        // add it to the lists above too.
+        for format in ARTIFACT_FORMATS {
+            match format {
+                ArtifactFormat::Plain => panic!("`Plain` cannot be written"),
+                ArtifactFormat::Bzip2
+                | ArtifactFormat::Gz
+                | ArtifactFormat::Tar
+                | ArtifactFormat::TarBzip2
+                | ArtifactFormat::TarGz
+                | ArtifactFormat::TarZstd
+                | ArtifactFormat::TarXz
+                | ArtifactFormat::Xz
+                | ArtifactFormat::Zstd
+                | ArtifactFormat::Zip => {}
+            }
+        }
+        for hash in HashAlgorithm::ALL {
+            match hash {
+                HashAlgorithm::Blake3 | HashAlgorithm::Sha256 | HashAlgorithm::Sha512 => {}
+            }
+        }
+        for arg0 in ARG0S {
+            match arg0 {
+                Arg0::DotslashFile | Arg0::UnderlyingExecutable => {}
+            }
+        }
+        for providers_order in PROVIDERS_ORDERS {
+            match providers_order {
+                ProvidersOrder::Sequential | ProvidersOrder::WeightedRandom => {}
+            }
+        }
+
+        check_enum::<ArtifactFormat>("ArtifactFormat", ARTIFACT_FORMATS.len());
+        check_enum::<HashAlgorithm>("HashAlgorithm", HashAlgorithm::ALL.len());
+        check_enum::<Arg0>("Arg0", ARG0S.len());
+        check_enum::<ProvidersOrder>("ProvidersOrder", PROVIDERS_ORDERS.len());
+    }
+
+    #[test]
+    fn provider_configs_match_schema() {
+        check_provider_config::<HttpProviderConfig>("HttpProviderConfig");
+        check_provider_config::<GitHubReleaseProviderConfig>("GitHubReleaseProviderConfig");
+        check_provider_config::<S3ProviderConfig>("S3ProviderConfig");
+        check_provider_config::<GcsProviderConfig>("GcsProviderConfig");
+        check_provider_config::<ExecProviderConfig>("ExecProviderConfig");
+
+        let provider_types = strings(&definition("Provider")["properties"]["type"]["enum"]);
+        for provider_type in &provider_types {
+            DefaultProviderFactory.get_provider(provider_type).unwrap();
+        }
+        assert_eq!(provider_types.len(), PROVIDER_TYPES.len());
+    }
+
+    #[test]
+    fn path_pattern_matches_artifact_path() {
+        let pattern = Regex::new(PATH_PATTERN).unwrap();
+        let dot_component = Regex::new(PATH_DOT_COMPONENT).unwrap();
+        for path in [
+            "tool",
+            "bin/tool",
+            "a/b/c.exe",
+            ".hidden/tool",
+            "tool..",
+            "",
+            "/tool",
+            "bin/",
+            "bin//tool",
+            "./tool",
+            "bin/../tool",
+            "..",
+            "bin\\tool",
+            "bin\0tool",
+        ] {
+            assert_eq!(
+                pattern.is_match(path) && !dot_component.is_match(path),
+                path.parse::<ArtifactPath>().is_ok(),
+                "{path:?}",
+            );
+        }
+    }
+
+    #[test]
+    fn exec_name_pattern_matches_validation() {
+        let pattern = Regex::new(NAME_PATTERN).unwrap();
+        for name in [
+            "store",
+            "my_store-2",
+            "",
+            "../store",
+            "a/b",
+            "store.sh",
+            "store ",
+        ] {
+            assert_eq!(pattern.is_match(name), is_valid_name(name), "{name:?}");
+        }
+    }
+
+    #[test]
+    fn digest_pattern_depends_on_hash() {
+        let schema = dotslash_file_schema();
+        let digest_lens = schema["$defs"]["ArtifactEntry"]["allOf"]
+            .as_array()
+            .unwrap();
+        for hash in HashAlgorithm::ALL {
+            let digest_len = digest_lens
+                .iter()
+                .find(|digest_len| digest_len["if"]["properties"]["hash"]["const"] == hash.as_str())
+                .unwrap();
+            let pattern = digest_len["then"]["properties"]["digest"]["pattern"]
+                .as_str()
+                .unwrap();
+            let pattern = Regex::new(pattern).unwrap();
+            assert!(pattern.is_match(&"a".repeat(hash.digest_len())));
+            assert!(!pattern.is_match(&"a".repeat(hash.digest_len() + 1)));
+        }
+    }
+}
@@ -0,0 +1,433 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! Ed25519 signatures for DotSlash files. Digests protect the artifacts, but
+//! a signature protects the DotSlash file itself, e.g., from being edited to
+//! point at a different artifact.
+//!
+//! A signed DotSlash file has a top-level `signature` field:
+//!
+//! ```json
+//! "signature": {
+//!   "key_id": "<first 8 bytes of the BLAKE3 hash of the public key>",
+//!   "signature": "<ed25519 signature>"
+//! }
+//! ```
+//!
+//! The signature covers the rest of the file as canonical JSON (no
+//! whitespace, object keys sorted), so reformatting the file or editing its
+//! comments does not invalidate it. Keys and signatures are written in hex,
+//! like digests.
+
+use std::fmt;
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::Context as _;
+use jsonc_parser::CollectOptions;
+use jsonc_parser::ParseOptions;
+use jsonc_parser::common::Ranged as _;
+use jsonc_parser::parse_to_ast;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::config;
+use crate::config::REQUIRED_HEADER;
+use crate::global_config::GlobalConfig;
+use crate::util::fs_ctx;
+
+pub const SIGNATURE_FIELD: &str = "signature";
+
+/// Prepended to what is signed so that a signature over a DotSlash file
+/// cannot be passed off as a signature over anything else.
+const SIGNING_CONTEXT: &[u8] = b"DotSlash signature v1\n";
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct SignatureBlock {
+    key_id: String,
+    signature: String,
+}
+
+#[derive(Debug, Error)]
+pub enum SignatureError {
+    #[error("DotSlash file is not signed")]
+    Unsigned,
+
+    #[error("`{SIGNATURE_FIELD}` is malformed")]
+    Malformed(#[source] anyhow::Error),
+
+    #[error("no trusted keys are configured")]
+    NoTrustedKeys,
+
+    #[error("DotSlash file is signed by key `{0}`, which is not trusted")]
+    UntrustedKey(String),
+
+    #[error("signature by key `{0}` does not match the contents of the DotSlash file")]
+    Mismatch(String),
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct PublicKey(ed25519_compact::PublicKey);
+
+impl PublicKey {
+    pub fn key_id(&self) -> String {
+        to_hex(&blake3::hash(self.0.as_ref()).as_bytes()[..8])
+    }
+}
+
+impl fmt::Display for PublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&to_hex(self.0.as_ref()))
+    }
+}
+
+impl FromStr for PublicKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = from_hex(s).context("public key must be hex")?;
+        let key = ed25519_compact::PublicKey::from_slice(&bytes)
+            .map_err(|_| anyhow::format_err!("public key must be 32 bytes"))?;
+        Ok(Self(key))
+    }
+}
+
+impl TryFrom<String> for PublicKey {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<PublicKey> for String {
+    fn from(key: PublicKey) -> Self {
+        key.to_string()
+    }
+}
+
+/// A key pair, stored on disk as the hex of its 32 byte seed.
+pub struct SecretKey(ed25519_compact::KeyPair);
+
+impl SecretKey {
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let contents = fs_ctx::read_to_string(path)?;
+        let seed = from_hex(contents.trim())
+            .ok()
+            .and_then(|bytes| ed25519_compact::Seed::from_slice(&bytes).ok())
+            .with_context(|| format!("`{}` is not a DotSlash secret key", path.display()))?;
+        Ok(Self(ed25519_compact::KeyPair::from_seed(seed)))
+    }
+
+    /// Writes a new key to `path`, which must not exist yet.
+    pub fn generate(path: &Path) -> anyhow::Result<Self> {
+        let mut seed = [0; ed25519_compact::Seed::BYTES];
+        rand::fill(&mut seed);
+
+        let mut file = fs_ctx::file_create_private(path)?;
+        writeln!(file, "{}", to_hex(&seed))
+            .with_context(|| format!("failed to write `{}`", path.display()))?;
+
+        Ok(Self(ed25519_compact::KeyPair::from_seed(
+            ed25519_compact::Seed::new(seed),
+        )))
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey(self.0.pk)
+    }
+}
+
+/// Returns `dotslash_data` with its signature replaced by one from `key`.
+/// Everything else, including comments and formatting, is left alone.
+pub fn sign(dotslash_data: &str, key: &SecretKey) -> anyhow::Result<String> {
+    let (value, _config_file) = config::parse_file(dotslash_data)?;
+    let signature = key.0.sk.sign(signed_payload(&value), None);
+    let block = SignatureBlock {
+        key_id: key.public_key().key_id(),
+        signature: to_hex(signature.as_ref()),
+    };
+
+    // `parse_file` already checked that the header is there.
+    let body_start = dotslash_data.len() - strip_header(dotslash_data).len();
+    let body = &dotslash_data[body_start..];
+    let ast = parse_to_ast(body, &CollectOptions::default(), &ParseOptions::default())?;
+    let object = ast
+        .value
+        .as_ref()
+        .and_then(|value| value.as_object())
+        .context("DotSlash file must contain a JSON object")?;
+    let last_prop = object
+        .properties
+        .last()
+        .context("DotSlash file must not be empty")?;
+
+    // Match the indentation of the existing fields.
+    let first_prop_start = object.properties[0].range.start;
+    let line_start = body[..first_prop_start].rfind('\n').map_or(0, |i| i + 1);
+    let indent = &body[line_start..first_prop_start];
+    let indent = if indent.trim().is_empty() && !indent.is_empty() {
+        indent
+    } else {
+        "  "
+    };
+    let block_json = format!(
+        "{{\n{indent}{indent}\"key_id\": \"{}\",\n{indent}{indent}\"signature\": \"{}\"\n{indent}}}",
+        block.key_id, block.signature,
+    );
+
+    let mut signed = dotslash_data[..body_start].to_owned();
+    if let Some(prop) = object.get(SIGNATURE_FIELD) {
+        let range = prop.value.range();
+        signed.push_str(&body[..range.start]);
+        signed.push_str(&block_json);
+        signed.push_str(&body[range.end..]);
+    } else {
+        let end = last_prop.range.end;
+        signed.push_str(&body[..end]);
+        let _ = write!(signed, ",\n{indent}\"{SIGNATURE_FIELD}\": {block_json}");
+        signed.push_str(&body[end..]);
+    }
+    Ok(signed)
+}
+
+/// Checks the signature of a parsed DotSlash file against `trusted_keys`,
+/// returning the ID of the key that signed it.
+pub fn verify(value: &Value, trusted_keys: &[PublicKey]) -> Result<String, SignatureError> {
+    let block = value.get(SIGNATURE_FIELD).ok_or(SignatureError::Unsigned)?;
+    let block =
+        SignatureBlock::deserialize(block).map_err(|err| SignatureError::Malformed(err.into()))?;
+    if trusted_keys.is_empty() {
+        return Err(SignatureError::NoTrustedKeys);
+    }
+    let key = trusted_keys
+        .iter()
+        .find(|key| key.key_id() == block.key_id)
+        .ok_or_else(|| SignatureError::UntrustedKey(block.key_id.clone()))?;
+    let signature = from_hex(&block.signature)
+        .ok()
+        .and_then(|bytes| ed25519_compact::Signature::from_slice(&bytes).ok())
+        .ok_or_else(|| {
+            SignatureError::Malformed(anyhow::format_err!("signature must be 64 bytes of hex"))
+        })?;
+    key.0
+        .verify(signed_payload(value), &signature)
+        .map_err(|_| SignatureError::Mismatch(block.key_id.clone()))?;
+    Ok(block.key_id)
+}
+
+/// Enforces the signature settings in the global config for a parsed
+/// DotSlash file. Signatures are only checked if there are trusted keys to
+/// check them against, unless they are required.
+pub fn check_signature(value: &Value) -> anyhow::Result<()> {
+    let config = GlobalConfig::get();
+    let required = config.require_signature.value;
+    let is_signed = value.get(SIGNATURE_FIELD).is_some();
+    if !required && (!is_signed || config.trusted_keys.value.is_empty()) {
+        return Ok(());
+    }
+    verify(value, &config.trusted_keys.value)
+        .map(drop)
+        .with_context(|| {
+            if required {
+                format!(
+                    "signature verification failed (signatures are required by {})",
+                    config.require_signature.source,
+                )
+            } else {
+                "signature verification failed".to_owned()
+            }
+        })
+}
+
+/// What is signed: `SIGNING_CONTEXT` followed by the DotSlash file, minus
+/// its signature, as canonical JSON.
+fn signed_payload(value: &Value) -> Vec<u8> {
+    let mut value = canonicalize(value);
+    if let Value::Object(map) = &mut value {
+        map.remove(SIGNATURE_FIELD);
+    }
+    let mut payload = SIGNING_CONTEXT.to_vec();
+    // Serializing a `Value` cannot fail.
+    serde_json::to_writer(&mut payload, &value).unwrap();
+    payload
+}
+
+/// Sorts the keys of every object, whether or not `serde_json` preserves
+/// insertion order.
+fn canonicalize(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries = map.iter().collect::<Vec<_>>();
+            entries.sort_by_key(|(key, _)| *key);
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key.clone(), canonicalize(value)))
+                    .collect(),
+            )
+        }
+        Value::Array(values) => Value::Array(values.iter().map(canonicalize).collect()),
+        value => value.clone(),
+    }
+}
+
+fn strip_header(dotslash_data: &str) -> &str {
+    dotslash_data
+        .strip_prefix(REQUIRED_HEADER)
+        .and_then(|rest| {
+            rest.strip_prefix("\r\n")
+                .or_else(|| rest.strip_prefix('\n'))
+        })
+        .unwrap_or(dotslash_data)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .fold(String::with_capacity(bytes.len() * 2), |mut output, b| {
+            let _ = write!(output, "{b:02x}");
+            output
+        })
+}
+
+fn from_hex(s: &str) -> anyhow::Result<Vec<u8>> {
+    if s.len() % 2 != 0 || !s.is_ascii() {
+        return Err(anyhow::format_err!("invalid hex"));
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).context("invalid hex"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOTSLASH_FILE: &str = r#"#!/usr/bin/env dotslash
+
+// The tool.
+{
+    "name": "my_tool",
+    "platforms": {
+        "linux-x86_64": {
+            "size": 5,
+            "hash": "blake3",
+            "digest": "dc5a4edb8240b018124052c330270696f96771a63b45250a5c17d3000e823355",
+            "path": "my_tool",
+            "providers": [{"url": "https://example.com/my_tool"}],
+        },
+    },
+}
+"#;
+
+    fn generate_key(dir: &Path, name: &str) -> SecretKey {
+        SecretKey::generate(&dir.join(name)).unwrap()
+    }
+
+    fn parse(dotslash_data: &str) -> Value {
+        config::parse_file(dotslash_data).unwrap().0
+    }
+
+    #[test]
+    fn sign_and_verify() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let key = generate_key(temp_dir.path(), "key");
+        let public_key = key.public_key();
+
+        let signed = sign(DOTSLASH_FILE, &key)?;
+        assert!(signed.starts_with("#!/usr/bin/env dotslash\n\n// The tool.\n{\n"));
+        assert!(signed.contains(&format!(
+            "    }},\n    \"signature\": {{\n        \"key_id\": \"{}\",\n",
+            public_key.key_id(),
+        )));
+        assert_eq!(
+            verify(&parse(&signed), std::slice::from_ref(&public_key))?,
+            public_key.key_id(),
+        );
+
+        // The key survives a round trip through disk and re-signing replaces
+        // the signature rather than adding another.
+        let key = SecretKey::read(&temp_dir.path().join("key"))?;
+        assert_eq!(sign(&signed, &key)?, signed);
+
+        // Formatting does not matter, but the contents do.
+        let reformatted = signed
+            .replace("\n        ", " ")
+            .replace("// The tool.", "");
+        verify(&parse(&reformatted), std::slice::from_ref(&public_key))?;
+        let tampered = signed.replace("\"size\": 5", "\"size\": 6");
+        assert!(matches!(
+            verify(&parse(&tampered), &[public_key]),
+            Err(SignatureError::Mismatch(_)),
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn verify_errors() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let key = generate_key(temp_dir.path(), "key");
+        let other_key = generate_key(temp_dir.path(), "other_key");
+
+        assert!(matches!(
+            verify(&parse(DOTSLASH_FILE), &[key.public_key()]),
+            Err(SignatureError::Unsigned),
+        ));
+        let signed = parse(&sign(DOTSLASH_FILE, &key)?);
+        assert!(matches!(
+            verify(&signed, &[]),
+            Err(SignatureError::NoTrustedKeys),
+        ));
+        assert!(matches!(
+            verify(&signed, &[other_key.public_key()]),
+            Err(SignatureError::UntrustedKey(key_id)) if key_id == key.public_key().key_id(),
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn generate_creates_private_file() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("key");
+        SecretKey::generate(&path)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt as _;
+            assert_eq!(
+                std::fs::metadata(&path)?.permissions().mode() & 0o777,
+                0o600
+            );
+        }
+        // An existing file is never overwritten.
+        assert!(SecretKey::generate(&path).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn public_key_round_trip() {
+        let hex = "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+        let key = hex.parse::<PublicKey>().unwrap();
+        assert_eq!(key.to_string(), hex);
+        assert_eq!(
+            serde_json::from_value::<PublicKey>(serde_json::json!(hex)).unwrap(),
+            key,
+        );
+        assert!("d75a".parse::<PublicKey>().is_err());
+        assert!("xyz".parse::<PublicKey>().is_err());
+    }
+}
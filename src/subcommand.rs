@@ -9,25 +9,58 @@
  */
 
 use std::env::ArgsOs;
+use std::ffi::OsStr;
 use std::ffi::OsString;
 use std::fmt;
 use std::io;
+use std::path::Path;
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
+use std::time::SystemTime;
 
 use anyhow::Context as _;
 use sha2::Digest as _;
 use sha2::Sha256;
 use thiserror::Error;
 
+use crate::artifact_location::determine_location;
+use crate::batch_fetch;
+use crate::batch_fetch::FetchDestination;
+use crate::bundle;
+use crate::bundle::ImportStatus;
+use crate::cache_gc;
+use crate::cache_gc::GcOptions;
+use crate::cache_list;
+use crate::cache_verify;
+use crate::cache_verify::VerifyOptions;
+use crate::cache_verify::VerifyStatus;
 use crate::config::REQUIRED_HEADER;
 use crate::config::parse_file;
+use crate::create_from_release::ReleaseOptions;
+use crate::create_from_release::create_from_release;
 use crate::default_provider_factory::DefaultProviderFactory;
 use crate::dotslash_cache::DotslashCache;
+use crate::download::DotslashFileInfo;
 use crate::download::download_artifact;
-use crate::locate::locate_artifact;
+use crate::formatter::format_dotslash_file;
+use crate::global_config::ConfigFileStatus;
+use crate::global_config::GlobalConfig;
+use crate::global_config::SettingSource;
+use crate::lint;
+use crate::lint::Severity;
+use crate::locate::PlatformSelection;
+use crate::locate::locate_artifacts;
 use crate::platform::SUPPORTED_PLATFORM;
 use crate::print_entry_for_url::print_entry_for_url;
+use crate::schema::dotslash_file_schema;
+use crate::signature;
+use crate::signature::PublicKey;
+use crate::signature::SecretKey;
+use crate::update::update_dotslash_file;
 use crate::util;
+use crate::util::ByteSize;
+use crate::util::ListOf;
 use crate::util::fs_ctx;
 
 #[derive(Debug)]
@@ -36,28 +69,65 @@ pub enum Subcommand {
     /// only the hash
     B3Sum,
 
+    /// Export artifacts to, or import them from, a portable bundle
+    Bundle,
+
     /// Clean the cache directory
     Clean,
 
+    /// Create a DotSlash file from the assets of a GitHub release
+    CreateFromRelease,
+
     /// Create a the artifact entry for DotSlash file from a URL
     CreateUrlEntry,
 
+    /// Inspect the cache, e.g., `cache ls`
+    Cache,
+
     /// Print the cache directory
     CacheDir,
 
+    /// Print the effective global config, e.g., `config show`
+    Config,
+
     /// Fetch and cache an artifact but do not execute it
     Fetch,
 
+    /// Rewrite DotSlash files in a canonical layout
+    Fmt,
+
+    /// Evict least-recently-used artifacts from the cache
+    Gc,
+
     /// Print where the artifact would be cached, without fetching it
     GetExtractedCachePath,
 
+    /// Check DotSlash files for likely mistakes
+    Lint,
+
     /// Parse a DotSlash file and print its data as JSON
     Parse,
 
+    /// Print a JSON Schema for DotSlash files
+    Schema,
+
     /// Similar to running `shasum -a 256`, though takes exactly one argument
     /// and prints only the hash
     Sha256,
 
+    /// Sign DotSlash files, or generate a key to sign them with
+    Sign,
+
+    /// Set the variables of a DotSlash file and update its artifacts to match
+    Update,
+
+    /// Check cached artifacts for drift from what their DotSlash files
+    /// specify, optionally repairing them
+    Verify,
+
+    /// Check the signatures of DotSlash files
+    VerifySignature,
+
     /// Version
     Version,
 
@@ -69,13 +139,25 @@ impl fmt::Display for Subcommand {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str(match self {
             Self::B3Sum => "b3sum",
+            Self::Bundle => "bundle",
             Self::Clean => "clean",
+            Self::CreateFromRelease => "create-from-release",
             Self::CreateUrlEntry => "create-url-entry",
+            Self::Cache => "cache",
             Self::CacheDir => "cache-dir",
+            Self::Config => "config",
             Self::Fetch => "fetch",
+            Self::Fmt => "fmt",
+            Self::Gc => "gc",
             Self::GetExtractedCachePath => "get-extracted-cache-path",
+            Self::Lint => "lint",
             Self::Parse => "parse",
+            Self::Schema => "schema",
             Self::Sha256 => "sha256",
+            Self::Sign => "sign",
+            Self::Update => "update",
+            Self::Verify => "verify",
+            Self::VerifySignature => "verify-signature",
             Self::Version => "version",
             Self::Help => "help",
         })
@@ -88,13 +170,25 @@ impl FromStr for Subcommand {
     fn from_str(name: &str) -> Result<Self, Self::Err> {
         match name {
             "b3sum" => Ok(Subcommand::B3Sum),
+            "bundle" => Ok(Subcommand::Bundle),
             "clean" => Ok(Subcommand::Clean),
+            "create-from-release" => Ok(Subcommand::CreateFromRelease),
             "create-url-entry" => Ok(Subcommand::CreateUrlEntry),
+            "cache" => Ok(Subcommand::Cache),
             "cache-dir" => Ok(Subcommand::CacheDir),
+            "config" => Ok(Subcommand::Config),
             "fetch" => Ok(Subcommand::Fetch),
+            "fmt" => Ok(Subcommand::Fmt),
+            "gc" => Ok(Subcommand::Gc),
             "get-extracted-cache-path" => Ok(Subcommand::GetExtractedCachePath),
+            "lint" => Ok(Subcommand::Lint),
             "parse" => Ok(Subcommand::Parse),
+            "schema" => Ok(Subcommand::Schema),
             "sha256" => Ok(Subcommand::Sha256),
+            "sign" => Ok(Subcommand::Sign),
+            "update" => Ok(Subcommand::Update),
+            "verify" => Ok(Subcommand::Verify),
+            "verify-signature" => Ok(Subcommand::VerifySignature),
             "version" => Ok(Subcommand::Version),
             "help" => Ok(Subcommand::Help),
             _ => Err(SubcommandError::UnknownCommand(name.to_owned())),
@@ -127,6 +221,19 @@ pub fn run_subcommand(subcommand: Subcommand, args: &mut ArgsOs) -> Result<(), S
 }
 
 fn run_subcommand_impl(subcommand: &Subcommand, args: &mut ArgsOs) -> anyhow::Result<()> {
+    // These fetch artifacts or check signatures, so fail early, rather than
+    // part of the way through, if a config file with the policies or
+    // signature requirements for them is broken.
+    if matches!(
+        subcommand,
+        Subcommand::Bundle
+            | Subcommand::Fetch
+            | Subcommand::Update
+            | Subcommand::Verify
+            | Subcommand::VerifySignature
+    ) {
+        GlobalConfig::try_get()?;
+    }
     match subcommand {
         Subcommand::B3Sum => {
             let file_arg = take_exactly_one_arg(args)?;
@@ -138,6 +245,79 @@ fn run_subcommand_impl(subcommand: &Subcommand, args: &mut ArgsOs) -> anyhow::Re
             println!("{}", hex_digest);
         }
 
+        Subcommand::Bundle => {
+            let action = take_flag_value(args, "bundle")?;
+            match action.as_str() {
+                "export" => {
+                    let mut file_args = vec![];
+                    let mut output = None;
+                    let mut jobs = None;
+                    let mut platforms = PlatformSelection::Host;
+                    while let Some(arg) = args.next() {
+                        if take_platform_flag(&arg, args, &mut platforms)? {
+                            continue;
+                        }
+                        match arg.to_str() {
+                            Some("-o" | "--output") => {
+                                output = Some(PathBuf::from(take_flag_value(args, "--output")?));
+                            }
+                            Some("--jobs") => jobs = Some(take_jobs_flag(args)?),
+                            _ => file_args.push(PathBuf::from(arg)),
+                        }
+                    }
+                    let output = output.context("expected `--output FILE`")?;
+                    if file_args.is_empty() {
+                        return Err(anyhow::format_err!(
+                            "expected at least one DotSlash file but received none"
+                        ));
+                    }
+
+                    let num_records = bundle::export_bundle(
+                        &file_args,
+                        &platforms,
+                        &output,
+                        &DotslashCache::new(),
+                        jobs.unwrap_or_else(batch_fetch::default_jobs),
+                        &DefaultProviderFactory {},
+                    )?;
+                    eprintln!(
+                        "Exported {num_records} artifact(s) to `{}`",
+                        output.display()
+                    );
+                }
+                "import" => {
+                    let bundle_path = take_exactly_one_arg(args)?;
+                    let results =
+                        bundle::import_bundle(Path::new(&bundle_path), &DotslashCache::new())?;
+                    let mut num_failed = 0;
+                    for (record, result) in &results {
+                        let name = &record.name;
+                        let platform = &record.platform;
+                        match result {
+                            Ok(ImportStatus::Imported) => println!("{name}: {platform}: imported"),
+                            Ok(ImportStatus::AlreadyCached) => {
+                                println!("{name}: {platform}: already cached");
+                            }
+                            Err(err) => {
+                                num_failed += 1;
+                                println!("{name}: {platform}: error: {err:#}");
+                            }
+                        }
+                    }
+                    if num_failed > 0 {
+                        return Err(anyhow::format_err!(
+                            "failed to import {num_failed} artifact(s)"
+                        ));
+                    }
+                }
+                _ => {
+                    return Err(anyhow::format_err!(
+                        "unknown action `{action}` for `bundle` (expected `export` or `import`)",
+                    ));
+                }
+            }
+        }
+
         Subcommand::Clean => {
             if args.next().is_some() {
                 return Err(anyhow::format_err!(
@@ -153,11 +333,80 @@ fn run_subcommand_impl(subcommand: &Subcommand, args: &mut ArgsOs) -> anyhow::Re
             fs_ctx::remove_dir_all(dotslash_cache.cache_dir())?;
         }
 
+        Subcommand::CreateFromRelease => {
+            let mut name = None;
+            let mut release_json = None;
+            let mut platform_patterns = vec![];
+            let mut positional = vec![];
+            while let Some(arg) = args.next() {
+                match arg.to_str() {
+                    Some("--name") => name = Some(take_flag_value(args, "--name")?),
+                    Some("--release-json") => {
+                        release_json =
+                            Some(PathBuf::from(take_flag_value(args, "--release-json")?));
+                    }
+                    Some("--platform") => {
+                        let value = take_flag_value(args, "--platform")?;
+                        let (platform, pattern) = value.split_once('=').with_context(|| {
+                            format!("expected `--platform PLATFORM=REGEX` but received `{value}`")
+                        })?;
+                        platform_patterns.push((platform.to_owned(), pattern.to_owned()));
+                    }
+                    _ => positional.push(arg.into_string().map_err(|arg| {
+                        anyhow::format_err!("arg is not UTF-8 `{}`", arg.to_string_lossy())
+                    })?),
+                }
+            }
+            let [repo, tag] = <[String; 2]>::try_from(positional).map_err(|_| {
+                anyhow::format_err!("expected exactly two arguments, `OWNER/REPO` and `TAG`")
+            })?;
+
+            let dotslash_file = create_from_release(&ReleaseOptions {
+                repo,
+                tag,
+                name,
+                release_json,
+                platform_patterns,
+            })?;
+            print!("{dotslash_file}");
+        }
+
         Subcommand::CreateUrlEntry => {
             let url = take_exactly_one_arg(args)?;
             print_entry_for_url(&url)?;
         }
 
+        Subcommand::Cache => {
+            let action = take_flag_value(args, "cache")?;
+            if action != "ls" {
+                return Err(anyhow::format_err!(
+                    "unknown action `{action}` for `cache` (expected `ls`)",
+                ));
+            }
+            let mut json = false;
+            for arg in args.by_ref() {
+                match arg.to_str() {
+                    Some("--json") => json = true,
+                    _ => {
+                        return Err(anyhow::format_err!(
+                            "unexpected argument `{}`",
+                            arg.to_string_lossy(),
+                        ));
+                    }
+                }
+            }
+
+            let dotslash_cache = DotslashCache::new();
+            let entries = cache_list::list_artifacts(&dotslash_cache)?;
+            if json {
+                let json =
+                    serde_json::to_string_pretty(&entries).context("failed to serialize value")?;
+                println!("{json}");
+            } else {
+                print_cache_list(&entries, SystemTime::now());
+            }
+        }
+
         Subcommand::CacheDir => {
             if args.next().is_some() {
                 return Err(anyhow::format_err!(
@@ -169,26 +418,365 @@ fn run_subcommand_impl(subcommand: &Subcommand, args: &mut ArgsOs) -> anyhow::Re
             println!("{}", dotslash_cache.cache_dir().display());
         }
 
+        Subcommand::Config => {
+            let action = take_flag_value(args, "config")?;
+            if action != "show" {
+                return Err(anyhow::format_err!(
+                    "unknown action `{action}` for `config` (expected `show`)",
+                ));
+            }
+            let mut json = false;
+            for arg in args.by_ref() {
+                match arg.to_str() {
+                    Some("--json") => json = true,
+                    _ => {
+                        return Err(anyhow::format_err!(
+                            "unexpected argument `{}`",
+                            arg.to_string_lossy(),
+                        ));
+                    }
+                }
+            }
+
+            let config = GlobalConfig::get();
+            // Show where the cache actually is rather than nothing when the
+            // default is used.
+            let cache_dir = DotslashCache::new().cache_dir().to_owned();
+            if json {
+                let mut value =
+                    serde_json::to_value(config).context("failed to serialize value")?;
+                value["cache_dir"]["value"] = serde_json::json!(cache_dir);
+                let json =
+                    serde_json::to_string_pretty(&value).context("failed to serialize value")?;
+                println!("{json}");
+            } else {
+                print_config(config, &cache_dir);
+            }
+        }
+
         Subcommand::Fetch => {
-            let file_arg = take_exactly_one_arg(args)?;
-            let dotslash_data = fs_ctx::read_to_string(file_arg)?;
+            let mut file_args = vec![];
+            let mut recursive_dirs = vec![];
+            let mut jobs = None;
+            let mut platforms = PlatformSelection::Host;
+            let mut destination = FetchDestination::Cache;
+            while let Some(arg) = args.next() {
+                if take_platform_flag(&arg, args, &mut platforms)? {
+                    continue;
+                }
+                match arg.to_str() {
+                    Some("--recursive") => {
+                        recursive_dirs.push(take_flag_value(args, "--recursive")?);
+                    }
+                    Some("--jobs") => jobs = Some(take_jobs_flag(args)?),
+                    Some("--output-dir") => {
+                        let dir = take_flag_value(args, "--output-dir")?;
+                        destination = FetchDestination::OutputDir(PathBuf::from(dir));
+                    }
+                    _ => file_args.push(PathBuf::from(arg)),
+                }
+            }
+
+            let dotslash_cache = DotslashCache::new();
+            let provider_factory = DefaultProviderFactory {};
+
+            // A single file (and platform) keeps the original behavior: print
+            // the executable, or fail with the full error.
+            if let ([file_arg], [], None, FetchDestination::Cache) = (
+                file_args.as_slice(),
+                recursive_dirs.as_slice(),
+                jobs,
+                &destination,
+            ) {
+                let dotslash_data = fs_ctx::read_to_string(file_arg)?;
+                let (name, entries) =
+                    locate_artifacts(&dotslash_data, &dotslash_cache, &platforms)?;
+                if let [entry] = entries.as_slice() {
+                    if !entry.artifact_location.executable.exists() {
+                        let dotslash_file = DotslashFileInfo {
+                            name: &name,
+                            path: file_arg,
+                        };
+                        download_artifact(
+                            &entry.artifact_entry,
+                            &entry.artifact_location,
+                            dotslash_file,
+                            &provider_factory,
+                        )?;
+                    }
+                    println!("{}", entry.artifact_location.executable.display());
+                    return Ok(());
+                }
+            }
+
+            for dir in &recursive_dirs {
+                let found = batch_fetch::find_dotslash_files(Path::new(dir))
+                    .with_context(|| format!("failed to search `{dir}` for DotSlash files"))?;
+                file_args.extend(found);
+            }
+            if file_args.is_empty() {
+                return Err(anyhow::format_err!(
+                    "expected at least one DotSlash file but received none"
+                ));
+            }
+
+            let results = batch_fetch::fetch_all(
+                &file_args,
+                &platforms,
+                &destination,
+                &dotslash_cache,
+                jobs.unwrap_or_else(batch_fetch::default_jobs),
+                &provider_factory,
+            );
+            // With the host platform, there is one result per file.
+            let show_platform = platforms != PlatformSelection::Host;
+            let mut num_fetched = 0;
+            let mut num_cached = 0;
+            let mut num_failed = 0;
+            for result in &results {
+                let file_name = result.dotslash_file.display();
+                let platform = match &result.platform {
+                    Some(platform) if show_platform => format!("{platform}: "),
+                    _ => String::new(),
+                };
+                match &result.result {
+                    Ok(fetched) => {
+                        if fetched.was_cached {
+                            num_cached += 1;
+                        } else {
+                            num_fetched += 1;
+                        }
+                        println!("{file_name}: {platform}{}", fetched.path.display());
+                    }
+                    Err(err) => {
+                        num_failed += 1;
+                        eprintln!("Failed to fetch `{file_name}`: {platform}{err:#}");
+                    }
+                }
+            }
+            let what = if show_platform {
+                "platform entries"
+            } else {
+                "DotSlash file(s)"
+            };
+            eprintln!(
+                "{} {what}: {num_fetched} fetched, {num_cached} already cached, \
+                 {num_failed} failed",
+                results.len(),
+            );
+            if num_failed > 0 {
+                return Err(anyhow::format_err!("failed to fetch {num_failed} {what}"));
+            }
+        }
+
+        Subcommand::Gc => {
+            let mut options = GcOptions::default();
+            while let Some(arg) = args.next() {
+                match arg.to_str() {
+                    Some("--max-size") => {
+                        let value = take_flag_value(args, "--max-size")?;
+                        options.max_size = Some(value.parse::<ByteSize>()?.0);
+                    }
+                    Some("--max-age") => {
+                        let value = take_flag_value(args, "--max-age")?;
+                        options.max_age = Some(cache_gc::parse_duration(&value)?);
+                    }
+                    Some("--dry-run") => options.dry_run = true,
+                    _ => {
+                        return Err(anyhow::format_err!(
+                            "unexpected argument `{}`",
+                            arg.to_string_lossy(),
+                        ));
+                    }
+                }
+            }
+            if options.max_size.is_none() && options.max_age.is_none() {
+                return Err(anyhow::format_err!(
+                    "expected at least one of `--max-size` or `--max-age`",
+                ));
+            }
+
             let dotslash_cache = DotslashCache::new();
-            let (artifact_entry, artifact_location) =
-                locate_artifact(&dotslash_data, &dotslash_cache)?;
-            if !artifact_location.executable.exists() {
-                let provider_factory = DefaultProviderFactory {};
-                download_artifact(&artifact_entry, &artifact_location, &provider_factory)?;
+            let report = cache_gc::collect_garbage(&dotslash_cache, &options, SystemTime::now())?;
+            let verb = if options.dry_run {
+                "Would remove"
+            } else {
+                "Removed"
+            };
+            for temp_entry in &report.removed_temp {
+                eprintln!(
+                    "{verb} stale temp entry `{}` ({})",
+                    temp_entry.artifact_directory.display(),
+                    ByteSize(temp_entry.size_in_bytes),
+                );
+            }
+            for artifact in &report.removed {
+                eprintln!(
+                    "{verb} `{}` ({})",
+                    artifact.artifact_directory.display(),
+                    ByteSize(artifact.size_in_bytes),
+                );
+            }
+            for artifact in &report.in_use {
+                eprintln!(
+                    "Skipped `{}` because it is in use",
+                    artifact.artifact_directory.display(),
+                );
+            }
+            for (artifact, err) in &report.failed {
+                eprintln!(
+                    "Failed to remove `{}`: {:#}",
+                    artifact.artifact_directory.display(),
+                    err,
+                );
+            }
+            eprintln!(
+                "{verb} {} artifact(s); cache size {} -> {} ({} artifact(s) remaining)",
+                report.removed.len(),
+                ByteSize(report.size_before),
+                ByteSize(report.size_after),
+                report.num_remaining,
+            );
+            if !report.failed.is_empty() {
+                return Err(anyhow::format_err!(
+                    "failed to remove {} artifact(s)",
+                    report.failed.len(),
+                ));
+            }
+        }
+
+        Subcommand::Fmt => {
+            let mut check = false;
+            let mut file_args = vec![];
+            for arg in args.by_ref() {
+                match arg.to_str() {
+                    Some("--check") => check = true,
+                    _ => file_args.push(PathBuf::from(arg)),
+                }
+            }
+            if file_args.is_empty() {
+                return Err(anyhow::format_err!(
+                    "expected at least one DotSlash file but received none"
+                ));
+            }
+
+            let mut unformatted = 0;
+            for file_arg in &file_args {
+                let dotslash_data = fs_ctx::read_to_string(file_arg)?;
+                let formatted = format_dotslash_file(&dotslash_data)
+                    .with_context(|| format!("failed to format `{}`", file_arg.display()))?;
+                if formatted == dotslash_data {
+                    continue;
+                }
+                if check {
+                    println!("{}", file_arg.display());
+                    unformatted += 1;
+                } else {
+                    rewrite_file(file_arg, &formatted)?;
+                    eprintln!("Formatted `{}`", file_arg.display());
+                }
+            }
+            if unformatted > 0 {
+                return Err(anyhow::format_err!(
+                    "{unformatted} DotSlash file(s) are not formatted"
+                ));
             }
-            println!("{}", artifact_location.executable.display());
         }
 
         Subcommand::GetExtractedCachePath => {
-            let file_arg = take_exactly_one_arg(args)?;
+            let mut platforms = PlatformSelection::Host;
+            let mut file_args = vec![];
+            while let Some(arg) = args.next() {
+                if !take_platform_flag(&arg, args, &mut platforms)? {
+                    file_args.push(arg);
+                }
+            }
+            let file_arg = match file_args.as_slice() {
+                [] => {
+                    return Err(anyhow::format_err!(
+                        "expected exactly one argument but received none"
+                    ));
+                }
+                [file_arg] => file_arg,
+                _ => {
+                    return Err(anyhow::format_err!(
+                        "expected exactly one argument but received more"
+                    ));
+                }
+            };
             let dotslash_data = fs_ctx::read_to_string(file_arg)?;
             let dotslash_cache = DotslashCache::new();
-            let (_artifact_entry, artifact_location) =
-                locate_artifact(&dotslash_data, &dotslash_cache)?;
-            println!("{}", artifact_location.executable.display());
+            let (_name, entries) = locate_artifacts(&dotslash_data, &dotslash_cache, &platforms)?;
+            if let [entry] = entries.as_slice() {
+                println!("{}", entry.artifact_location.executable.display());
+            } else {
+                for entry in entries {
+                    println!(
+                        "{}: {}",
+                        entry.platform,
+                        entry.artifact_location.executable.display()
+                    );
+                }
+            }
+        }
+
+        Subcommand::Lint => {
+            let mut json = false;
+            let mut path_args = vec![];
+            for arg in args.by_ref() {
+                match arg.to_str() {
+                    Some("--json") => json = true,
+                    _ => path_args.push(PathBuf::from(arg)),
+                }
+            }
+            if path_args.is_empty() {
+                return Err(anyhow::format_err!(
+                    "expected at least one DotSlash file or directory but received none"
+                ));
+            }
+
+            let mut file_args = vec![];
+            for path in path_args {
+                if path.is_dir() {
+                    let found = batch_fetch::find_dotslash_files(&path).with_context(|| {
+                        format!("failed to search `{}` for DotSlash files", path.display())
+                    })?;
+                    file_args.extend(found);
+                } else {
+                    file_args.push(path);
+                }
+            }
+            let dotslash_cache = DotslashCache::new();
+            let mut diagnostics = vec![];
+            for file_arg in &file_args {
+                let found = lint::lint_file(file_arg, &dotslash_cache)
+                    .with_context(|| format!("failed to lint `{}`", file_arg.display()))?;
+                diagnostics.extend(found);
+            }
+
+            if json {
+                let json = serde_json::to_string_pretty(&diagnostics)
+                    .context("failed to serialize value")?;
+                println!("{json}");
+            } else {
+                for diagnostic in &diagnostics {
+                    println!("{diagnostic}");
+                }
+            }
+            let num_errors = diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.severity == Severity::Error)
+                .count();
+            eprintln!(
+                "{} DotSlash file(s): {num_errors} error(s), {} warning(s)",
+                file_args.len(),
+                diagnostics.len() - num_errors,
+            );
+            if num_errors > 0 {
+                return Err(anyhow::format_err!(
+                    "found {num_errors} error(s) in DotSlash files"
+                ));
+            }
         }
 
         Subcommand::Parse => {
@@ -201,6 +789,181 @@ fn run_subcommand_impl(subcommand: &Subcommand, args: &mut ArgsOs) -> anyhow::Re
             println!("{json}");
         }
 
+        Subcommand::Update => {
+            let mut assignments = vec![];
+            let mut file_args = vec![];
+            while let Some(arg) = args.next() {
+                match arg.to_str() {
+                    Some("--set") => {
+                        let value = take_flag_value(args, "--set")?;
+                        let (name, value) = value.split_once('=').with_context(|| {
+                            format!("expected `--set NAME=VALUE` but received `{value}`")
+                        })?;
+                        assignments.push((name.to_owned(), value.to_owned()));
+                    }
+                    _ => file_args.push(PathBuf::from(arg)),
+                }
+            }
+            let [file_arg] = file_args.as_slice() else {
+                return Err(anyhow::format_err!(
+                    "expected exactly one DotSlash file but received {}",
+                    file_args.len(),
+                ));
+            };
+
+            let dotslash_data = fs_ctx::read_to_string(file_arg)?;
+            let updated =
+                update_dotslash_file(&dotslash_data, &assignments, &DefaultProviderFactory {})
+                    .with_context(|| format!("failed to update `{}`", file_arg.display()))?;
+            rewrite_file(file_arg, &updated)?;
+        }
+
+        Subcommand::Schema => {
+            if args.next().is_some() {
+                return Err(anyhow::format_err!(
+                    "expected no arguments but received some",
+                ));
+            }
+            let json = serde_json::to_string_pretty(&dotslash_file_schema())
+                .context("failed to serialize value")?;
+            println!("{json}");
+        }
+
+        Subcommand::Verify => {
+            let mut options = VerifyOptions::default();
+            let mut file_args = vec![];
+            for arg in args.by_ref() {
+                match arg.to_str() {
+                    Some("--refetch") => options.refetch = true,
+                    Some("--repair") => options.repair = true,
+                    _ => file_args.push(arg),
+                }
+            }
+            if file_args.is_empty() {
+                return Err(anyhow::format_err!(
+                    "expected at least one argument but received none"
+                ));
+            }
+
+            let dotslash_cache = DotslashCache::new();
+            let provider_factory = DefaultProviderFactory {};
+            let mut num_problems = 0;
+            for file_arg in &file_args {
+                let file_name = file_arg.to_string_lossy();
+                let dotslash_data = fs_ctx::read_to_string(file_arg)?;
+                let (original_json, config_file) = parse_file(&dotslash_data)
+                    .with_context(|| format!("failed to parse `{file_name}`"))?;
+                // Only fetch for a DotSlash file that passes the signature
+                // checks of `locate_artifact`. `--refetch` and `--repair`
+                // have to fetch, but otherwise, the artifacts can still be
+                // compared against what is recorded in the cache.
+                options.no_fetch = match signature::check_signature(&original_json) {
+                    Ok(()) => false,
+                    Err(err) if options.refetch || options.repair => {
+                        return Err(err.context(format!("problem with `{file_name}`")));
+                    }
+                    Err(err) => {
+                        eprintln!(
+                            "dotslash warning: only checking `{file_name}` against the cache \
+                             without fetching anything: {err:#}"
+                        );
+                        true
+                    }
+                };
+                let mut platforms = config_file.platforms.iter().collect::<Vec<_>>();
+                platforms.sort_by_key(|(platform, _)| platform.as_str());
+                for (platform, artifact_entry) in platforms {
+                    let artifact_location = determine_location(artifact_entry, &dotslash_cache);
+                    let dotslash_file = DotslashFileInfo {
+                        name: &config_file.name,
+                        path: Path::new(file_arg),
+                    };
+                    let status = cache_verify::verify_cached_artifact(
+                        artifact_entry,
+                        &artifact_location,
+                        dotslash_file,
+                        &options,
+                        &provider_factory,
+                    );
+                    match status {
+                        Ok(VerifyStatus::NotCached) => {
+                            println!("{file_name}: {platform}: not in cache");
+                        }
+                        Ok(VerifyStatus::Ok) => println!("{file_name}: {platform}: ok"),
+                        Ok(VerifyStatus::Unverified) => {
+                            println!("{file_name}: {platform}: ok (contents unverified)");
+                        }
+                        Ok(VerifyStatus::Drift(differences)) => {
+                            num_problems += 1;
+                            println!("{file_name}: {platform}: drift detected");
+                            for difference in differences {
+                                println!("  {difference}");
+                            }
+                        }
+                        Ok(VerifyStatus::Repaired(differences)) => {
+                            println!("{file_name}: {platform}: repaired");
+                            for difference in differences {
+                                println!("  {difference}");
+                            }
+                        }
+                        Err(err) => {
+                            num_problems += 1;
+                            println!("{file_name}: {platform}: error: {err:#}");
+                        }
+                    }
+                }
+            }
+            if num_problems > 0 {
+                return Err(anyhow::format_err!(
+                    "{num_problems} cached artifact(s) failed verification"
+                ));
+            }
+        }
+
+        Subcommand::VerifySignature => {
+            let mut public_key = None;
+            let mut file_args = vec![];
+            while let Some(arg) = args.next() {
+                match arg.to_str() {
+                    Some("--public-key") => {
+                        let value = take_flag_value(args, "--public-key")?;
+                        public_key = Some(value.parse::<PublicKey>()?);
+                    }
+                    _ => file_args.push(arg),
+                }
+            }
+            if file_args.is_empty() {
+                return Err(anyhow::format_err!(
+                    "expected at least one argument but received none"
+                ));
+            }
+
+            let trusted_keys = match public_key {
+                Some(public_key) => vec![public_key],
+                None => GlobalConfig::get().trusted_keys.value.clone(),
+            };
+            let mut num_failed = 0;
+            for file_arg in &file_args {
+                let file_name = file_arg.to_string_lossy();
+                let result = fs_ctx::read_to_string(file_arg)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|dotslash_data| Ok(parse_file(&dotslash_data)?.0))
+                    .and_then(|value| Ok(signature::verify(&value, &trusted_keys)?));
+                match result {
+                    Ok(key_id) => println!("{file_name}: signed by {key_id}"),
+                    Err(err) => {
+                        num_failed += 1;
+                        println!("{file_name}: error: {err:#}");
+                    }
+                }
+            }
+            if num_failed > 0 {
+                return Err(anyhow::format_err!(
+                    "{num_failed} DotSlash file(s) failed signature verification"
+                ));
+            }
+        }
+
         Subcommand::Version => {
             if args.next().is_some() {
                 return Err(anyhow::format_err!(
@@ -236,16 +999,63 @@ dotslash also has these special experimental commands:
   dotslash --help                   Print this message
   dotslash --version                Print the version of dotslash
   dotslash -- b3sum FILE            Compute blake3 hash
+  dotslash -- bundle export -o FILE [--jobs N] DOTSLASH_FILE...
+                                    Write the artifacts to a portable bundle
+                                    (also accepts `--platform NAME` or
+                                    `--all-platforms`)
+  dotslash -- bundle import FILE    Verify the artifacts in a bundle and add
+                                    them to the cache, e.g., when air-gapped
+  dotslash -- cache ls [--json]     List cached artifacts with their size,
+                                    last use and origin
   dotslash -- clean                 Clean dotslash cache
+  dotslash -- create-from-release [--name NAME] [--platform PLATFORM=REGEX]...
+      [--release-json FILE] OWNER/REPO TAG
+                                    Generate a DotSlash file from the assets
+                                    of a GitHub release
   dotslash -- create-url-entry URL  Generate "http" provider entry
   dotslash -- cache-dir             Print path to the cache directory
+  dotslash -- config show [--json]  Print the effective global config and
+                                    where each setting comes from
   dotslash -- fetch DOTSLASH_FILE   Prepare for execution, but print exe path
                                     instead of executing
+  dotslash -- fetch [--recursive DIR] [--jobs N] DOTSLASH_FILE...
+                                    Fetch many DotSlash files in parallel,
+                                    e.g., to warm the cache
+  dotslash -- fetch [--platform NAME | --all-platforms] [--output-dir DIR] ...
+                                    Fetch artifacts for other platforms, into
+                                    the cache or (not unpacked) into DIR
+  dotslash -- fmt [--check] DOTSLASH_FILE...
+                                    Rewrite DotSlash files in a canonical
+                                    layout, or list the ones that are not
+  dotslash -- gc [--max-size SIZE] [--max-age AGE] [--dry-run]
+                                    Evict least-recently-used artifacts until
+                                    the cache fits the budget (e.g. `10G`, `30d`)
   dotslash -- get-extracted-cache-path DOTSLASH_FILE
                                     Print where the artifact would be cached,
-                                    without fetching it
+                                    without fetching it (also accepts
+                                    `--platform NAME` or `--all-platforms`)
+  dotslash -- lint [--json] PATH...
+                                    Check DotSlash files, or the DotSlash
+                                    files under directories, for mistakes
   dotslash -- parse DOTSLASH_FILE   Parse the dotslash file
+  dotslash -- schema                Print a JSON Schema for DotSlash files
   dotslash -- sha256 FILE           Compute sha256 sum of the file
+  dotslash -- sign --key KEY_FILE DOTSLASH_FILE...
+                                    Add or replace the signature of DotSlash
+                                    files
+  dotslash -- sign --generate-key KEY_FILE
+                                    Write a new secret key to KEY_FILE and
+                                    print its public key
+  dotslash -- update [--set NAME=VALUE]... DOTSLASH_FILE
+                                    Set variables, then refetch the artifacts
+                                    of providers with a `template` and
+                                    rewrite their size and digests
+  dotslash -- verify [--refetch] [--repair] DOTSLASH_FILE...
+                                    Check cached artifacts for corruption or
+                                    tampering, optionally replacing them
+  dotslash -- verify-signature [--public-key KEY] DOTSLASH_FILE...
+                                    Check signatures against KEY or the
+                                    trusted keys in the global config
 
 Learn more at {}
 "#,
@@ -256,6 +1066,52 @@ Learn more at {}
             );
         }
 
+        Subcommand::Sign => {
+            let mut key_file = None;
+            let mut generate_key = None;
+            let mut file_args = vec![];
+            while let Some(arg) = args.next() {
+                match arg.to_str() {
+                    Some("--key") => {
+                        key_file = Some(PathBuf::from(take_flag_value(args, "--key")?));
+                    }
+                    Some("--generate-key") => {
+                        generate_key =
+                            Some(PathBuf::from(take_flag_value(args, "--generate-key")?));
+                    }
+                    _ => file_args.push(PathBuf::from(arg)),
+                }
+            }
+
+            if let Some(key_file) = generate_key {
+                if !file_args.is_empty() {
+                    return Err(anyhow::format_err!(
+                        "`--generate-key` does not sign DotSlash files; sign them with `--key`"
+                    ));
+                }
+                let key = SecretKey::generate(&key_file)?;
+                eprintln!("Wrote secret key to `{}`", key_file.display());
+                println!("{}", key.public_key());
+                return Ok(());
+            }
+
+            let key_file =
+                key_file.context("expected `--key KEY_FILE` or `--generate-key KEY_FILE`")?;
+            if file_args.is_empty() {
+                return Err(anyhow::format_err!(
+                    "expected at least one DotSlash file but received none"
+                ));
+            }
+            let key = SecretKey::read(&key_file)?;
+            for file_arg in &file_args {
+                let dotslash_data = fs_ctx::read_to_string(file_arg)?;
+                let signed = signature::sign(&dotslash_data, &key)
+                    .with_context(|| format!("failed to sign `{}`", file_arg.display()))?;
+                rewrite_file(file_arg, &signed)?;
+                eprintln!("Signed `{}`", file_arg.display());
+            }
+        }
+
         Subcommand::Sha256 => {
             let file_arg = take_exactly_one_arg(args)?;
             // TODO: read from stdin if file_arg is `-`
@@ -270,6 +1126,211 @@ Learn more at {}
     Ok(())
 }
 
+fn print_cache_list(entries: &[cache_list::CacheListEntry], now: SystemTime) {
+    let mut rows = vec![[
+        "SIZE".to_owned(),
+        "LAST USED".to_owned(),
+        "NAME".to_owned(),
+        "KEY".to_owned(),
+        "DOTSLASH FILE".to_owned(),
+    ]];
+    for entry in entries {
+        let last_used = SystemTime::UNIX_EPOCH + Duration::from_secs(entry.last_used);
+        let (name, dotslash_file) = match &entry.origin {
+            Some(origin) => (
+                origin.name.clone(),
+                origin.dotslash_file.display().to_string(),
+            ),
+            None => ("-".to_owned(), "-".to_owned()),
+        };
+        rows.push([
+            ByteSize(entry.size_in_bytes).to_string(),
+            cache_list::format_age(now.duration_since(last_used).unwrap_or_default()),
+            name,
+            entry.key.clone(),
+            dotslash_file,
+        ]);
+    }
+
+    print_table(&rows);
+
+    let total = entries.iter().map(|entry| entry.size_in_bytes).sum();
+    println!("{} artifact(s), {} total", entries.len(), ByteSize(total));
+}
+
+fn print_config(config: &GlobalConfig, cache_dir: &Path) {
+    fn row(key: &str, value: Option<String>, source: &SettingSource) -> [String; 3] {
+        [
+            key.to_owned(),
+            value.unwrap_or_else(|| "-".to_owned()),
+            source.to_string(),
+        ]
+    }
+
+    let mut rows = vec![
+        ["KEY".to_owned(), "VALUE".to_owned(), "SOURCE".to_owned()],
+        row(
+            "cache_dir",
+            Some(cache_dir.display().to_string()),
+            &config.cache_dir.source,
+        ),
+        row(
+            "timeout_sec",
+            config.timeout_sec.value.map(|v| v.to_string()),
+            &config.timeout_sec.source,
+        ),
+        row(
+            "connect_timeout_sec",
+            config.connect_timeout_sec.value.map(|v| v.to_string()),
+            &config.connect_timeout_sec.source,
+        ),
+        row(
+            "offline",
+            Some(config.offline.value.to_string()),
+            &config.offline.source,
+        ),
+        row(
+            "http_client",
+            config.http_client.value.clone(),
+            &config.http_client.source,
+        ),
+        row(
+            "mirrors",
+            Some(format!("{} rule(s)", config.mirrors.value.len())),
+            &config.mirrors.source,
+        ),
+    ];
+    if config.policies.is_empty() {
+        rows.push(row("policy", None, &SettingSource::Default));
+    }
+    for policy in &config.policies {
+        rows.push(row(
+            "policy",
+            serde_json::to_string(&policy.value).ok(),
+            &policy.source,
+        ));
+    }
+    rows.push(row(
+        "trusted_keys",
+        Some(format!("{} key(s)", config.trusted_keys.value.len())),
+        &config.trusted_keys.source,
+    ));
+    rows.push(row(
+        "require_signature",
+        Some(config.require_signature.value.to_string()),
+        &config.require_signature.source,
+    ));
+    print_table(&rows);
+
+    println!();
+    let mut rows = vec![["CONFIG FILE".to_owned(), "STATUS".to_owned()]];
+    for file in &config.files {
+        let status = match &file.status {
+            ConfigFileStatus::Loaded if file.unknown_keys.is_empty() => "loaded".to_owned(),
+            ConfigFileStatus::Loaded => {
+                format!("loaded, ignoring {}", ListOf::new(&file.unknown_keys))
+            }
+            ConfigFileStatus::NotFound => "not found".to_owned(),
+            ConfigFileStatus::Invalid(err) => format!("invalid: {err}"),
+        };
+        rows.push([file.path.display().to_string(), status]);
+    }
+    print_table(&rows);
+}
+
+/// Prints `rows` as left-aligned columns, the first row being the header.
+fn print_table<const N: usize>(rows: &[[String; N]]) {
+    let mut widths = [0; N];
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in rows {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+    }
+}
+
+/// Handles `--platform NAME` (which may be repeated) and `--all-platforms`.
+/// Returns whether `arg` was one of them.
+fn take_platform_flag(
+    arg: &OsStr,
+    args: &mut ArgsOs,
+    platforms: &mut PlatformSelection,
+) -> anyhow::Result<bool> {
+    let conflict = || anyhow::format_err!("`--platform` and `--all-platforms` cannot be combined");
+    match arg.to_str() {
+        Some("--platform") => {
+            let name = take_flag_value(args, "--platform")?;
+            match platforms {
+                PlatformSelection::Host => *platforms = PlatformSelection::Named(vec![name]),
+                PlatformSelection::Named(names) => {
+                    if !names.contains(&name) {
+                        names.push(name);
+                    }
+                }
+                PlatformSelection::All => return Err(conflict()),
+            }
+            Ok(true)
+        }
+        Some("--all-platforms") => {
+            if matches!(platforms, PlatformSelection::Named(_)) {
+                return Err(conflict());
+            }
+            *platforms = PlatformSelection::All;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+fn take_jobs_flag(args: &mut ArgsOs) -> anyhow::Result<usize> {
+    let value = take_flag_value(args, "--jobs")?;
+    value
+        .parse::<usize>()
+        .ok()
+        .filter(|jobs| *jobs > 0)
+        .with_context(|| format!("invalid value for `--jobs` `{value}`"))
+}
+
+fn take_flag_value(args: &mut ArgsOs, flag: &str) -> anyhow::Result<String> {
+    let value = args
+        .next()
+        .with_context(|| format!("expected a value after `{flag}`"))?;
+    value.into_string().map_err(|value| {
+        anyhow::format_err!(
+            "value for `{flag}` is not UTF-8 `{}`",
+            value.to_string_lossy()
+        )
+    })
+}
+
+/// Replaces the contents of the existing file at `path` atomically, so that
+/// an interrupted write cannot leave a truncated DotSlash file behind. The
+/// file keeps its permissions (DotSlash files are usually executable), and if
+/// `path` is a symlink, the file it points to is replaced instead.
+fn rewrite_file(path: &Path, contents: &str) -> anyhow::Result<()> {
+    let path = fs_ctx::canonicalize(path)?;
+    let permissions = fs_ctx::metadata(&path)?.permissions();
+    let parent = path
+        .parent()
+        .with_context(|| format!("no parent for `{}`", path.display()))?;
+    let temp_file = fs_ctx::namedtempfile_new_in(parent)?;
+    io::Write::write_all(&mut temp_file.as_file(), contents.as_bytes())
+        .with_context(|| format!("failed to write `{}`", temp_file.path().display()))?;
+    fs_ctx::set_permissions(temp_file.path(), permissions)?;
+    temp_file
+        .persist(&path)
+        .with_context(|| format!("failed to write `{}`", path.display()))?;
+    Ok(())
+}
+
 fn take_exactly_one_arg(args: &mut ArgsOs) -> anyhow::Result<OsString> {
     match (args.next(), args.next()) {
         (None, _) => Err(anyhow::format_err!(
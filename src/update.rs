@@ -0,0 +1,430 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! Updating a DotSlash file to new artifacts, e.g., for a new version of a
+//! tool. A provider can have a `template` with a value for any of its
+//! fields, in which `{NAME}` stands for the variable `NAME` in the top-level
+//! `vars`:
+//!
+//! ```json
+//! "vars": {"version": "1.2.3"},
+//! ...
+//!       "providers": [
+//!         {
+//!           "url": "https://example.com/v1.2.3/tool.tar.gz",
+//!           "template": {"url": "https://example.com/v{version}/tool.tar.gz"}
+//!         }
+//!       ]
+//! ```
+//!
+//! DotSlash ignores both when fetching an artifact. Updating sets variables,
+//! fills in the templates, fetches the artifacts from the updated providers
+//! and rewrites their `size` and digests. The rest of the file, comments
+//! included, is left as it is.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io;
+
+use anyhow::Context as _;
+use jsonc_parser::CollectOptions;
+use jsonc_parser::ParseOptions;
+use jsonc_parser::ast;
+use jsonc_parser::common::Range;
+use jsonc_parser::common::Ranged as _;
+use jsonc_parser::parse_to_ast;
+use serde_json::Value;
+
+use crate::artifact_hasher::ArtifactHasher;
+use crate::config;
+use crate::config::ArtifactEntry;
+use crate::config::blank_header;
+use crate::download::fetch_unverified_artifact;
+use crate::download::is_offline;
+use crate::global_config::GlobalConfig;
+use crate::inspect_artifact::sniff_magic;
+use crate::provider::ProviderFactory;
+use crate::signature::SIGNATURE_FIELD;
+use crate::util::ByteSize;
+use crate::util::ListOf;
+
+pub const VARS_FIELD: &str = "vars";
+pub const TEMPLATE_FIELD: &str = "template";
+
+/// Replaces the text at `range` of the DotSlash file.
+struct Edit {
+    range: Range,
+    text: String,
+}
+
+impl Edit {
+    fn json(value: &ast::Value<'_>, json: impl Into<Value>) -> Self {
+        Self {
+            range: value.range(),
+            text: json.into().to_string(),
+        }
+    }
+}
+
+/// Sets the variables in `assignments` and updates each platform with a
+/// provider that has a `template`. Returns the updated DotSlash file.
+/// Nothing is returned unless every such platform is updated.
+pub fn update_dotslash_file<P: ProviderFactory>(
+    dotslash_data: &str,
+    assignments: &[(String, String)],
+    provider_factory: &P,
+) -> anyhow::Result<String> {
+    let (_value, config_file) = config::parse_file(dotslash_data)?;
+    let json = blank_header(dotslash_data);
+    let ast = parse_to_ast(&json, &CollectOptions::default(), &ParseOptions::default())?;
+    let root = ast
+        .value
+        .as_ref()
+        .and_then(ast::Value::as_object)
+        .context("DotSlash file must contain a JSON object")?;
+
+    let mut edits = vec![];
+    let vars = set_vars(root, assignments, &mut edits)?;
+
+    let temp_dir = tempfile::tempdir()?;
+    let platforms = root
+        .get_object("platforms")
+        .context("DotSlash file must have `platforms`")?;
+    let mut num_updated = 0;
+    for prop in &platforms.properties {
+        let platform = prop.name.as_str();
+        let entry = prop
+            .value
+            .as_object()
+            .with_context(|| format!("`{platform}` must be an object"))?;
+        let artifact_entry = &config_file.platforms[platform];
+        let providers = fill_in_templates(entry, artifact_entry, &vars, &mut edits)
+            .with_context(|| format!("failed to fill in the templates for `{platform}`"))?;
+        if providers.is_empty() {
+            eprintln!(
+                "Skipped `{platform}` because none of its providers has a `{TEMPLATE_FIELD}`"
+            );
+            continue;
+        }
+
+        if is_offline() {
+            return Err(anyhow::format_err!(
+                "offline mode is enabled by {}, so the artifact for `{platform}` cannot be fetched",
+                GlobalConfig::get().offline.source,
+            ));
+        }
+        let destination = fetch_unverified_artifact(
+            artifact_entry,
+            &providers,
+            temp_dir.path(),
+            provider_factory,
+        )
+        .with_context(|| format!("failed to fetch the artifact for `{platform}`"))?;
+        let magic = sniff_magic(&destination)?;
+        if !magic.agrees_with(artifact_entry.format) {
+            return Err(anyhow::format_err!(
+                "the artifact for `{platform}` is now {magic}, which does not match its `format`"
+            ));
+        }
+        let mut hasher = ArtifactHasher::new(artifact_entry);
+        io::copy(&mut File::open(&destination)?, &mut hasher)?;
+        let (size, digests) = hasher.finish();
+        edits.extend(digest_edits(entry, size, digests)?);
+        eprintln!("Updated `{platform}` ({})", ByteSize(size));
+        num_updated += 1;
+    }
+    if num_updated == 0 {
+        return Err(anyhow::format_err!(
+            "no provider has a `{TEMPLATE_FIELD}` to update"
+        ));
+    }
+    if root.get(SIGNATURE_FIELD).is_some() {
+        eprintln!("warning: the signature no longer matches; sign the DotSlash file again");
+    }
+
+    let mut updated = dotslash_data.to_owned();
+    edits.sort_by_key(|edit| edit.range.start);
+    for edit in edits.iter().rev() {
+        updated.replace_range(edit.range.start..edit.range.end, &edit.text);
+    }
+    config::parse_file(&updated).context("updated DotSlash file is invalid")?;
+    Ok(updated)
+}
+
+/// The values of `vars`, after `assignments`.
+fn set_vars(
+    root: &ast::Object<'_>,
+    assignments: &[(String, String)],
+    edits: &mut Vec<Edit>,
+) -> anyhow::Result<BTreeMap<String, String>> {
+    let Some(vars_object) = root.get_object(VARS_FIELD) else {
+        if let Some((name, _)) = assignments.first() {
+            return Err(anyhow::format_err!(
+                "cannot set `{name}` because there are no `{VARS_FIELD}`"
+            ));
+        }
+        return Ok(BTreeMap::new());
+    };
+
+    let mut vars = BTreeMap::new();
+    for prop in &vars_object.properties {
+        let name = prop.name.as_str();
+        let value = prop
+            .value
+            .as_string_lit()
+            .with_context(|| format!("`{VARS_FIELD}.{name}` must be a string"))?;
+        vars.insert(name.to_owned(), value.value.to_string());
+    }
+    // If a variable is set more than once, the last value wins.
+    let assignments = assignments
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect::<BTreeMap<_, _>>();
+    for (name, value) in assignments {
+        let prop = vars_object.get(name).with_context(|| {
+            format!(
+                "unknown variable `{name}`; expected one of {}",
+                ListOf::new(vars.keys()),
+            )
+        })?;
+        edits.push(Edit::json(&prop.value, value));
+        vars.insert(name.to_owned(), value.to_owned());
+    }
+    Ok(vars)
+}
+
+/// The providers of `entry` with their templates filled in, or none if no
+/// provider has a template.
+fn fill_in_templates(
+    entry: &ast::Object<'_>,
+    artifact_entry: &ArtifactEntry,
+    vars: &BTreeMap<String, String>,
+    edits: &mut Vec<Edit>,
+) -> anyhow::Result<Vec<Value>> {
+    let Some(providers) = entry.get_array("providers") else {
+        return Ok(vec![]);
+    };
+    let mut filled_in = vec![];
+    for (provider, provider_config) in providers.elements.iter().zip(&artifact_entry.providers) {
+        let Some((provider, template)) = provider.as_object().and_then(|provider| {
+            let template = provider.get(TEMPLATE_FIELD)?;
+            Some((provider, template))
+        }) else {
+            filled_in.push(None);
+            continue;
+        };
+        let template = template
+            .value
+            .as_object()
+            .with_context(|| format!("`{TEMPLATE_FIELD}` must be an object"))?;
+
+        let mut provider_config = provider_config.clone();
+        for prop in &template.properties {
+            let field = prop.name.as_str();
+            let pattern = prop
+                .value
+                .as_string_lit()
+                .with_context(|| format!("`{TEMPLATE_FIELD}.{field}` must be a string"))?;
+            let value = fill_in(&pattern.value, vars)?;
+            let target = provider.get(field).with_context(|| {
+                format!("provider has no `{field}` for its `{TEMPLATE_FIELD}` to fill in")
+            })?;
+            edits.push(Edit::json(&target.value, value.as_str()));
+            provider_config[field] = Value::from(value);
+        }
+        filled_in.push(Some(provider_config));
+    }
+
+    if filled_in.iter().all(Option::is_none) {
+        return Ok(vec![]);
+    }
+    // Otherwise, the entry would list providers of different artifacts.
+    filled_in
+        .into_iter()
+        .collect::<Option<Vec<_>>>()
+        .with_context(|| format!("either all providers must have a `{TEMPLATE_FIELD}` or none"))
+}
+
+/// Replaces each `{NAME}` in `pattern` with the variable `NAME`.
+fn fill_in(pattern: &str, vars: &BTreeMap<String, String>) -> anyhow::Result<String> {
+    let mut filled_in = String::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        let len = rest[start..]
+            .find('}')
+            .with_context(|| format!("unclosed `{{` in `{pattern}`"))?;
+        let name = &rest[start + 1..start + len];
+        let value = vars.get(name).with_context(|| {
+            format!(
+                "unknown variable `{name}` in `{pattern}`; expected one of {}",
+                ListOf::new(vars.keys()),
+            )
+        })?;
+        filled_in.push_str(&rest[..start]);
+        filled_in.push_str(value);
+        rest = &rest[start + len + 1..];
+    }
+    filled_in.push_str(rest);
+    Ok(filled_in)
+}
+
+/// Rewrites `size`, `digest` and each of `additional_digests`, given
+/// `digests` in the order of `ArtifactEntry::digests()`.
+fn digest_edits(
+    entry: &ast::Object<'_>,
+    size: u64,
+    digests: Vec<String>,
+) -> anyhow::Result<Vec<Edit>> {
+    let mut digest_values = vec![&entry.get("digest").context("missing `digest`")?.value];
+    if let Some(additional_digests) = entry.get_array("additional_digests") {
+        for hash_digest in &additional_digests.elements {
+            let digest = hash_digest
+                .as_object()
+                .and_then(|hash_digest| hash_digest.get("digest"))
+                .context("malformed `additional_digests`")?;
+            digest_values.push(&digest.value);
+        }
+    }
+
+    let size_value = &entry.get("size").context("missing `size`")?.value;
+    let mut edits = vec![Edit::json(size_value, size)];
+    for (value, digest) in digest_values.into_iter().zip(digests) {
+        edits.push(Edit::json(value, digest));
+    }
+    Ok(edits)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::*;
+    use crate::provider::Provider;
+    use crate::util::FileLock;
+
+    /// Writes the `url` of the provider as the artifact.
+    struct EchoProvider;
+
+    impl Provider for EchoProvider {
+        fn fetch_artifact(
+            &self,
+            provider_config: &Value,
+            destination: &Path,
+            _fetch_lock: &FileLock,
+            _artifact_entry: &ArtifactEntry,
+        ) -> anyhow::Result<()> {
+            fs::write(destination, provider_config["url"].as_str().unwrap())?;
+            Ok(())
+        }
+    }
+
+    impl ProviderFactory for EchoProvider {
+        fn get_provider(&self, _provider_type: &str) -> anyhow::Result<Box<dyn Provider>> {
+            Ok(Box::new(Self))
+        }
+    }
+
+    fn dotslash_file(version: &str, size: usize, digest: &str, sha256: &str) -> String {
+        format!(
+            r#"#!/usr/bin/env dotslash
+{{
+  "name": "tool",
+  // The version to fetch.
+  "vars": {{"version": "{version}"}},
+  "platforms": {{
+    "linux-x86_64": {{
+      "size": {size},
+      "hash": "blake3",
+      "digest": "{digest}", // Updated too.
+      "path": "tool",
+      "providers": [
+        {{
+          "url": "https://example.com/v{version}/tool",
+          "template": {{"url": "https://example.com/v{{version}}/tool"}},
+        }},
+      ],
+      "additional_digests": [{{"hash": "sha256", "digest": "{sha256}"}}],
+    }},
+    "macos-aarch64": {{
+      "size": 0,
+      "hash": "blake3",
+      "digest": "{}",
+      "path": "tool",
+      "providers": [{{"url": "https://example.com/tool"}}],
+    }},
+  }},
+}}
+"#,
+            "0".repeat(64),
+        )
+    }
+
+    #[test]
+    fn updates_templated_platforms() -> anyhow::Result<()> {
+        let before = dotslash_file("1.0", 1, &"a".repeat(64), &"b".repeat(64));
+        let updated = update_dotslash_file(
+            &before,
+            &[("version".to_owned(), "2.0".to_owned())],
+            &EchoProvider,
+        )?;
+        let artifact = "https://example.com/v2.0/tool";
+        let sha256 = {
+            use sha2::Digest as _;
+            format!("{:x}", sha2::Sha256::digest(artifact))
+        };
+        assert_eq!(
+            updated,
+            dotslash_file(
+                "2.0",
+                artifact.len(),
+                blake3::hash(artifact.as_bytes()).to_hex().as_str(),
+                &sha256,
+            ),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_unknown_variables() {
+        let before = dotslash_file("1.0", 1, &"a".repeat(64), &"b".repeat(64));
+        let err = update_dotslash_file(
+            &before,
+            &[("verison".to_owned(), "2.0".to_owned())],
+            &EchoProvider,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown variable `verison`; expected one of `version`",
+        );
+    }
+
+    #[test]
+    fn fill_in_replaces_variables() -> anyhow::Result<()> {
+        let vars = BTreeMap::from([
+            ("version".to_owned(), "1.2.3".to_owned()),
+            ("os".to_owned(), "linux".to_owned()),
+        ]);
+        assert_eq!(
+            fill_in("https://example.com/v{version}/tool-{version}-{os}", &vars)?,
+            "https://example.com/v1.2.3/tool-1.2.3-linux",
+        );
+        assert_eq!(
+            fill_in("{arch}", &vars).unwrap_err().to_string(),
+            "unknown variable `arch` in `{arch}`; expected one of `os`, `version`",
+        );
+        assert_eq!(
+            fill_in("v{version", &vars).unwrap_err().to_string(),
+            "unclosed `{` in `v{version`",
+        );
+        Ok(())
+    }
+}
@@ -8,6 +8,7 @@
  * above-listed licenses.
  */
 
+mod byte_size;
 #[cfg(unix)]
 mod chmodx;
 mod display;
@@ -21,9 +22,11 @@ mod is_path_safe_to_own;
 mod mv_no_clobber;
 pub mod progress;
 mod tree_perms;
+mod tree_usage;
 pub mod unarchive;
 mod update_mtime;
 
+pub use self::byte_size::ByteSize;
 #[cfg(unix)]
 pub use self::chmodx::chmodx;
 pub use self::display::CommandDisplay;
@@ -37,7 +40,10 @@ pub use self::is_not_found_error::is_not_found_error;
 #[cfg(unix)]
 pub use self::is_path_safe_to_own::is_path_safe_to_own;
 pub use self::mv_no_clobber::mv_no_clobber;
+pub use self::progress::ProgressReader;
 pub use self::progress::display_progress;
 pub use self::tree_perms::make_tree_entries_read_only;
 pub use self::tree_perms::make_tree_entries_writable;
+pub use self::tree_usage::TreeUsage;
+pub use self::tree_usage::tree_usage;
 pub use self::update_mtime::update_mtime;
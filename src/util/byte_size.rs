@@ -0,0 +1,108 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! Human-friendly byte sizes, e.g. `512M` or `1.5 GiB`.
+
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+const UNITS: &[(&str, u64)] = &[
+    ("T", 1 << 40),
+    ("G", 1 << 30),
+    ("M", 1 << 20),
+    ("K", 1 << 10),
+];
+
+#[derive(Debug, Error)]
+#[error("invalid size `{0}` (expected a number of bytes with an optional K, M, G or T suffix)")]
+pub struct ByteSizeError(String);
+
+/// A number of bytes. Parses from strings like `1024`, `512K`, `10G` or
+/// `10GiB` (units are powers of 1024) and displays as e.g. `1.5 GiB`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ByteSize(pub u64);
+
+impl FromStr for ByteSize {
+    type Err = ByteSizeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ByteSizeError(s.to_owned());
+        let trimmed = s.trim();
+        let digits_end = trimmed
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(trimmed.len());
+        let (digits, suffix) = trimmed.split_at(digits_end);
+        let number = digits.parse::<u64>().map_err(|_| err())?;
+
+        let suffix = suffix.trim_start().to_ascii_uppercase();
+        let suffix = suffix
+            .strip_suffix("IB")
+            .or_else(|| suffix.strip_suffix('B'))
+            .unwrap_or(&suffix);
+        let multiplier = if suffix.is_empty() {
+            1
+        } else {
+            UNITS
+                .iter()
+                .find(|(unit, _)| *unit == suffix)
+                .map(|(_, multiplier)| *multiplier)
+                .ok_or_else(err)?
+        };
+
+        number.checked_mul(multiplier).map(ByteSize).ok_or_else(err)
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (unit, multiplier) in UNITS {
+            if self.0 >= *multiplier {
+                return write!(f, "{:.1} {}iB", self.0 as f64 / *multiplier as f64, unit);
+            }
+        }
+        write!(f, "{} B", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("0".parse::<ByteSize>().unwrap(), ByteSize(0));
+        assert_eq!("1234".parse::<ByteSize>().unwrap(), ByteSize(1234));
+        assert_eq!("1234B".parse::<ByteSize>().unwrap(), ByteSize(1234));
+        assert_eq!("2K".parse::<ByteSize>().unwrap(), ByteSize(2048));
+        assert_eq!("2k".parse::<ByteSize>().unwrap(), ByteSize(2048));
+        assert_eq!("3M".parse::<ByteSize>().unwrap(), ByteSize(3 << 20));
+        assert_eq!("10G".parse::<ByteSize>().unwrap(), ByteSize(10 << 30));
+        assert_eq!("10GiB".parse::<ByteSize>().unwrap(), ByteSize(10 << 30));
+        assert_eq!("10 GB".parse::<ByteSize>().unwrap(), ByteSize(10 << 30));
+        assert_eq!("1T".parse::<ByteSize>().unwrap(), ByteSize(1 << 40));
+
+        assert!("".parse::<ByteSize>().is_err());
+        assert!("G".parse::<ByteSize>().is_err());
+        assert!("1.5G".parse::<ByteSize>().is_err());
+        assert!("10X".parse::<ByteSize>().is_err());
+        assert!("-1".parse::<ByteSize>().is_err());
+        assert!("99999999999T".parse::<ByteSize>().is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(ByteSize(0).to_string(), "0 B");
+        assert_eq!(ByteSize(1023).to_string(), "1023 B");
+        assert_eq!(ByteSize(1024).to_string(), "1.0 KiB");
+        assert_eq!(ByteSize(1536 << 20).to_string(), "1.5 GiB");
+    }
+}
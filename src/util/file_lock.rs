@@ -59,6 +59,34 @@ impl FileLock {
         inner(path.as_ref())
     }
 
+    /// Like `acquire`, but returns `Ok(None)` instead of blocking if another
+    /// process already holds the lock.
+    pub fn try_acquire<P>(path: P) -> Result<Option<FileLock>, FileLockError>
+    where
+        P: AsRef<Path>,
+    {
+        fn inner(path: &Path) -> Result<Option<FileLock>, FileLockError> {
+            let lock_file = File::options()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(path)
+                .map_err(|e| FileLockError::Create(path.to_path_buf(), e))?;
+
+            match fs2::FileExt::try_lock_exclusive(&lock_file) {
+                Ok(()) => Ok(Some(FileLock {
+                    file: Some(lock_file),
+                })),
+                Err(e) if e.raw_os_error() == fs2::lock_contended_error().raw_os_error() => {
+                    Ok(None)
+                }
+                Err(e) => Err(FileLockError::LockExclusive(path.to_path_buf(), e)),
+            }
+        }
+        inner(path.as_ref())
+    }
+
     pub fn acquire_shared_lock<P>(path: P) -> Result<FileLock, FileLockError>
     where
         P: AsRef<Path>,
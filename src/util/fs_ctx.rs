@@ -79,7 +79,6 @@ pub fn canonicalize<P: AsRef<Path>>(path: P) -> io::Result<PathBuf> {
     fs::canonicalize(&path).map_err(|source| wrap1(source, "canonicalize", path))
 }
 
-#[cfg_attr(not(dotslash_internal), expect(dead_code))]
 pub fn copy<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> io::Result<u64> {
     fs::copy(&from, &to).map_err(|source| wrap2(source, "copy from", from, "to", to))
 }
@@ -92,6 +91,18 @@ pub fn file_create<P: AsRef<Path>>(path: P) -> io::Result<fs::File> {
     fs::File::create(&path).map_err(|source| wrap1(source, "create file", path))
 }
 
+/// Creates a file that, on Unix, only its owner can read or write. Fails if
+/// `path` already exists, which would keep its own permissions.
+pub fn file_create_private<P: AsRef<Path>>(path: P) -> io::Result<fs::File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+        .open(&path)
+        .map_err(|source| wrap1(source, "create file", path))
+}
+
 pub fn file_open<P: AsRef<Path>>(path: P) -> io::Result<fs::File> {
     fs::File::open(&path).map_err(|source| wrap1(source, "open file", path))
 }
@@ -127,7 +138,6 @@ pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> io::Result<()>
     fs::rename(&from, &to).map_err(|source| wrap2(source, "rename from", from, "to", to))
 }
 
-#[cfg_attr(not(dotslash_internal), expect(dead_code))]
 pub fn remove_file<P: AsRef<Path>>(path: P) -> io::Result<()> {
     fs::remove_file(&path).map_err(|source| wrap1(source, "remove file", path))
 }
@@ -9,7 +9,12 @@
  */
 
 use std::fs;
+use std::io;
+use std::io::Read;
 use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
 use std::sync::mpsc;
 use std::sync::mpsc::Receiver;
 use std::sync::mpsc::Sender;
@@ -107,3 +112,42 @@ fn should_end_progress(recv: &Receiver<()>) -> bool {
         Err(TryRecvError::Empty) => false,
     }
 }
+
+/// Displays the progress of reading `R`, for downloads that are streamed
+/// rather than written to a file that `display_progress` could poll.
+pub struct ProgressReader<R> {
+    inner: R,
+    bytes_read: Arc<AtomicU64>,
+    progress: Option<(Sender<()>, JoinHandle<()>)>,
+}
+
+impl<R: Read> ProgressReader<R> {
+    pub fn new(inner: R, content_length: u64) -> Self {
+        let bytes_read = Arc::new(AtomicU64::new(0));
+        let progress = display_progress_with_size(content_length, {
+            let bytes_read = Arc::clone(&bytes_read);
+            move || Some(bytes_read.load(Ordering::Relaxed))
+        });
+        Self {
+            inner,
+            bytes_read,
+            progress: Some(progress),
+        }
+    }
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.bytes_read.fetch_add(n as u64, Ordering::Relaxed);
+        if n == 0 && !buf.is_empty() {
+            // If the stream is dropped part of the way through instead, the
+            // progress thread finishes by itself when `send` is dropped.
+            if let Some((send, handle)) = self.progress.take() {
+                let _ = send.send(());
+                let _ = handle.join();
+            }
+        }
+        Ok(n)
+    }
+}
@@ -0,0 +1,93 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+use std::io;
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::util::fs_ctx;
+
+/// Disk usage and recency information for a directory tree.
+#[derive(Copy, Clone, Debug)]
+pub struct TreeUsage {
+    /// Sum of the lengths of all regular files in the tree.
+    pub size_in_bytes: u64,
+    /// The most recent mtime of any entry in the tree, including the root.
+    pub last_modified: SystemTime,
+}
+
+/// Walks `folder` and reports its disk usage and most recent mtime.
+///
+/// The most recent mtime is used as a "last used" signal for artifacts in
+/// the DotSlash cache because `locate_artifact` bumps the mtime of the
+/// executable each time it is run. Symlinks are not followed.
+pub fn tree_usage(folder: &Path) -> io::Result<TreeUsage> {
+    let metadata = fs_ctx::symlink_metadata(folder)?;
+    let mut usage = TreeUsage {
+        size_in_bytes: 0,
+        last_modified: metadata.modified()?,
+    };
+    tree_usage_impl(folder, &mut usage)?;
+    Ok(usage)
+}
+
+fn tree_usage_impl(folder: &Path, usage: &mut TreeUsage) -> io::Result<()> {
+    for entry in fs_ctx::read_dir(folder)? {
+        let entry = entry?;
+        let metadata = fs_ctx::symlink_metadata(entry.path())?;
+
+        if let Ok(modified) = metadata.modified() {
+            usage.last_modified = usage.last_modified.max(modified);
+        }
+
+        if metadata.is_dir() {
+            tree_usage_impl(&entry.path(), usage)?;
+        } else if metadata.is_file() {
+            usage.size_in_bytes += metadata.len();
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::Duration;
+
+    use filetime::FileTime;
+
+    use super::*;
+
+    #[test]
+    fn test_tree_usage() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let root = temp_dir.path();
+        fs::create_dir(root.join("bin"))?;
+        fs::write(root.join("bin/tool"), b"12345")?;
+        fs::write(root.join("README"), b"123")?;
+
+        let old = FileTime::from_unix_time(1_000_000_000, 0);
+        let new = FileTime::from_unix_time(1_500_000_000, 0);
+        filetime::set_file_mtime(root.join("README"), old)?;
+        filetime::set_file_mtime(root.join("bin"), old)?;
+        filetime::set_file_mtime(root, old)?;
+        filetime::set_file_mtime(root.join("bin/tool"), new)?;
+
+        let usage = tree_usage(root)?;
+        assert_eq!(usage.size_in_bytes, 8);
+        assert_eq!(
+            usage.last_modified,
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1_500_000_000),
+        );
+
+        Ok(())
+    }
+}
@@ -54,6 +54,30 @@ pub enum ArchiveType {
 pub fn unarchive<R>(reader: R, destination: &Path, archive_type: ArchiveType) -> io::Result<()>
 where
     R: BufRead + Seek,
+{
+    match archive_type {
+        #[cfg(not(dotslash_internal))]
+        ArchiveType::Zip => {
+            let destination = fs_ctx::canonicalize(destination)?;
+            let mut archive = ZipArchive::new(reader)?;
+            archive.extract(destination)?;
+            Ok(())
+        }
+
+        _ => unarchive_stream(reader, destination, archive_type),
+    }
+}
+
+/// Like `unarchive`, but in a single pass over `reader`, e.g., while the
+/// archive is still being downloaded. Zip archives cannot be extracted this
+/// way because the central directory is at the end.
+pub fn unarchive_stream<R>(
+    reader: R,
+    destination: &Path,
+    archive_type: ArchiveType,
+) -> io::Result<()>
+where
+    R: BufRead,
 {
     match archive_type {
         ArchiveType::Tar => unpack_tar(reader, destination),
@@ -75,14 +99,78 @@ where
         ArchiveType::Zstd => write_out(ZstdDecoder::with_buffer(reader)?, destination),
         ArchiveType::TarZstd => unpack_tar(ZstdDecoder::with_buffer(reader)?, destination),
 
+        #[cfg(not(dotslash_internal))]
+        ArchiveType::Zip => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "zip archives cannot be extracted in a single pass",
+        )),
+    }
+}
+
+/// A regular file in an archive, as listed by `list_files`.
+#[derive(Debug, PartialEq)]
+pub struct ArchivedFile {
+    /// Relative to the root of the archive, with `/` separators.
+    pub path: String,
+    /// Whether any execute bit is set. Zip archives that were not created on
+    /// a Unix have no mode bits, so their files are never executable.
+    pub is_executable: bool,
+}
+
+/// Lists the regular files in a tar/zip archive without extracting it.
+/// Archives that contain a single compressed file have nothing to list.
+pub fn list_files<R>(reader: R, archive_type: ArchiveType) -> io::Result<Vec<ArchivedFile>>
+where
+    R: BufRead + Seek,
+{
+    match archive_type {
+        ArchiveType::Tar => list_tar(reader),
+        ArchiveType::TarBzip2 => list_tar(BzDecoder::new(reader)),
+        ArchiveType::TarGz => list_tar(GzDecoder::new(reader)),
+        #[cfg(not(dotslash_internal))]
+        ArchiveType::TarXz => list_tar(XzDecoder::new(reader)),
+        ArchiveType::TarZstd => list_tar(ZstdDecoder::with_buffer(reader)?),
+
         #[cfg(not(dotslash_internal))]
         ArchiveType::Zip => {
-            let destination = fs_ctx::canonicalize(destination)?;
             let mut archive = ZipArchive::new(reader)?;
-            archive.extract(destination)?;
-            Ok(())
+            let mut files = vec![];
+            for i in 0..archive.len() {
+                let file = archive.by_index(i)?;
+                if file.is_file() {
+                    files.push(ArchivedFile {
+                        path: file.name().to_owned(),
+                        is_executable: file.unix_mode().is_some_and(|mode| mode & 0o111 != 0),
+                    });
+                }
+            }
+            Ok(files)
+        }
+
+        #[cfg(not(dotslash_internal))]
+        ArchiveType::Bzip2 | ArchiveType::Gz | ArchiveType::Xz | ArchiveType::Zstd => Ok(vec![]),
+    }
+}
+
+fn list_tar<R>(reader: R) -> io::Result<Vec<ArchivedFile>>
+where
+    R: Read,
+{
+    let mut archive = Archive::new(reader);
+    let mut files = vec![];
+    for entry in archive.entries()? {
+        let entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
         }
+        let path = entry.path()?;
+        let path = path.to_string_lossy();
+        files.push(ArchivedFile {
+            path: path.strip_prefix("./").unwrap_or(&path).to_owned(),
+            is_executable: entry.header().mode()? & 0o111 != 0,
+        });
     }
+    Ok(files)
 }
 
 #[cfg(not(dotslash_internal))]
@@ -25,7 +25,6 @@ use tempfile::TempDir;
 pub mod ci;
 
 #[path = "../../src/platform.rs"]
-#[expect(dead_code)]
 mod platform;
 
 macro_rules! if_win_else {
@@ -166,6 +165,7 @@ pub struct DotslashTestEnv {
     redactions: SuperRedactions,
     tempdir_path: PathBuf,
     _tempdir: TempDir,
+    fixtures_dir: TempDir,
 }
 
 impl DotslashTestEnv {
@@ -232,11 +232,17 @@ impl DotslashTestEnv {
         redactions.redaction("[IO_ERROR_NOT_FOUND]", IO_ERROR_NOT_FOUND)?;
         redactions.redaction("[DOTSLASH_USER_AGENT]", USER_AGENT)?;
 
+        let fixtures_dir = tempfile::Builder::new()
+            .prefix("dotslash_fixtures-")
+            .rand_bytes(5)
+            .tempdir()?;
+
         Ok(DotslashTestEnv {
             current_dir,
             redactions,
             tempdir_path,
             _tempdir: tempdir,
+            fixtures_dir,
         })
     }
 
@@ -258,6 +264,66 @@ impl DotslashTestEnv {
         &self.tempdir_path
     }
 
+    /// The user config file for `dotslash_command()`, which does not exist
+    /// unless a test writes it.
+    pub fn config_file(&self) -> PathBuf {
+        self.fixtures_dir.path().join("config.json")
+    }
+
+    /// Writes `contents` as a plain (uncompressed) artifact, and a DotSlash
+    /// file named `name` that fetches it with a `file://` URL on the
+    /// current platform. This makes it possible to test fetching without network
+    /// access. Returns the path to the DotSlash file.
+    pub fn local_dotslash_file(&self, name: &str, contents: &[u8]) -> anyhow::Result<PathBuf> {
+        self.local_dotslash_file_for_platforms(name, &[(platform::SUPPORTED_PLATFORM, contents)])
+    }
+
+    /// Like `local_dotslash_file`, but with an entry (and artifact) for each
+    /// of `platforms`.
+    pub fn local_dotslash_file_for_platforms(
+        &self,
+        name: &str,
+        platforms: &[(&str, &[u8])],
+    ) -> anyhow::Result<PathBuf> {
+        let fixtures_dir = self.fixtures_dir.path();
+        let mut entries = serde_json::Map::new();
+        for (platform, contents) in platforms {
+            let artifact_path = fixtures_dir.join(format!("{name}.{platform}.artifact"));
+            fs::write(&artifact_path, contents)?;
+
+            let artifact_path = artifact_path
+                .to_str()
+                .context("fixture path is not UTF-8")?;
+            let url = if cfg!(windows) {
+                format!("file:///{}", artifact_path.replace('\\', "/"))
+            } else {
+                format!("file://{artifact_path}")
+            };
+            let entry = serde_json::json!({
+                "size": contents.len(),
+                "hash": "blake3",
+                "digest": blake3::hash(contents).to_hex().as_str(),
+                "path": name,
+                "providers": [{"url": url}],
+            });
+            entries.insert((*platform).to_owned(), entry);
+        }
+        let json = serde_json::json!({
+            "name": name,
+            "platforms": entries,
+        });
+
+        let dotslash_file = fixtures_dir.join(name);
+        fs::write(
+            &dotslash_file,
+            format!(
+                "#!/usr/bin/env dotslash\n{}\n",
+                serde_json::to_string_pretty(&json)?
+            ),
+        )?;
+        Ok(dotslash_file)
+    }
+
     pub fn dotslash_command(&self) -> Command {
         let assert = Assert::new()
             .normalize_paths(false)
@@ -265,6 +331,8 @@ impl DotslashTestEnv {
         Command::new(ci::dotslash_bin())
             .current_dir(&self.current_dir)
             .env("DOTSLASH_CACHE", &self.tempdir_path)
+            .env("DOTSLASH_CONFIG", self.config_file())
+            .env_remove("DOTSLASH_OFFLINE")
             .envs(ci::envs())
             .with_assert(assert)
     }
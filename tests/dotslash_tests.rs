@@ -16,8 +16,10 @@ use std::ffi::OsString;
 use std::fs;
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt as _;
+use std::path::Path;
 use std::str;
 
+use anyhow::Context as _;
 use tempfile::NamedTempFile;
 
 use crate::common::DotslashTestEnv;
@@ -560,16 +562,63 @@ dotslash also has these special experimental commands:
   dotslash --help                   Print this message
   dotslash --version                Print the version of dotslash
   dotslash -- b3sum FILE            Compute blake3 hash
+  dotslash -- bundle export -o FILE [--jobs N] DOTSLASH_FILE...
+                                    Write the artifacts to a portable bundle
+                                    (also accepts `--platform NAME` or
+                                    `--all-platforms`)
+  dotslash -- bundle import FILE    Verify the artifacts in a bundle and add
+                                    them to the cache, e.g., when air-gapped
+  dotslash -- cache ls [--json]     List cached artifacts with their size,
+                                    last use and origin
   dotslash -- clean                 Clean dotslash cache
+  dotslash -- create-from-release [--name NAME] [--platform PLATFORM=REGEX]...
+      [--release-json FILE] OWNER/REPO TAG
+                                    Generate a DotSlash file from the assets
+                                    of a GitHub release
   dotslash -- create-url-entry URL  Generate \"http\" provider entry
   dotslash -- cache-dir             Print path to the cache directory
+  dotslash -- config show [--json]  Print the effective global config and
+                                    where each setting comes from
   dotslash -- fetch DOTSLASH_FILE   Prepare for execution, but print exe path
                                     instead of executing
+  dotslash -- fetch [--recursive DIR] [--jobs N] DOTSLASH_FILE...
+                                    Fetch many DotSlash files in parallel,
+                                    e.g., to warm the cache
+  dotslash -- fetch [--platform NAME | --all-platforms] [--output-dir DIR] ...
+                                    Fetch artifacts for other platforms, into
+                                    the cache or (not unpacked) into DIR
+  dotslash -- fmt [--check] DOTSLASH_FILE...
+                                    Rewrite DotSlash files in a canonical
+                                    layout, or list the ones that are not
+  dotslash -- gc [--max-size SIZE] [--max-age AGE] [--dry-run]
+                                    Evict least-recently-used artifacts until
+                                    the cache fits the budget (e.g. `10G`, `30d`)
   dotslash -- get-extracted-cache-path DOTSLASH_FILE
                                     Print where the artifact would be cached,
-                                    without fetching it
+                                    without fetching it (also accepts
+                                    `--platform NAME` or `--all-platforms`)
+  dotslash -- lint [--json] PATH...
+                                    Check DotSlash files, or the DotSlash
+                                    files under directories, for mistakes
   dotslash -- parse DOTSLASH_FILE   Parse the dotslash file
+  dotslash -- schema                Print a JSON Schema for DotSlash files
   dotslash -- sha256 FILE           Compute sha256 sum of the file
+  dotslash -- sign --key KEY_FILE DOTSLASH_FILE...
+                                    Add or replace the signature of DotSlash
+                                    files
+  dotslash -- sign --generate-key KEY_FILE
+                                    Write a new secret key to KEY_FILE and
+                                    print its public key
+  dotslash -- update [--set NAME=VALUE]... DOTSLASH_FILE
+                                    Set variables, then refetch the artifacts
+                                    of providers with a `template` and
+                                    rewrite their size and digests
+  dotslash -- verify [--refetch] [--repair] DOTSLASH_FILE...
+                                    Check cached artifacts for corruption or
+                                    tampering, optionally replacing them
+  dotslash -- verify-signature [--public-key KEY] DOTSLASH_FILE...
+                                    Check signatures against KEY or the
+                                    trusted keys in the global config
 
 Learn more at https://dotslash-cli.com
 ";
@@ -728,288 +777,1878 @@ fn b3sum_command_ok() -> anyhow::Result<()> {
 }
 
 //
-// "cache-dir" Command
+// "bundle" Command
 //
 
 #[test]
-fn cache_dir_command_ok() {
-    DotslashTestEnv::try_new()
-        .unwrap()
+fn bundle_command_export_and_import() -> anyhow::Result<()> {
+    let export_env = DotslashTestEnv::try_new()?;
+    let dotslash_file = export_env.local_dotslash_file_for_platforms(
+        "my_tool",
+        &[("linux-x86_64", b"linux\n"), ("macos-aarch64", b"macos\n")],
+    )?;
+    let bundle_dir = tempfile::tempdir()?;
+    let bundle_path = bundle_dir.path().join("tools.tar");
+
+    export_env
         .dotslash_command()
         .arg("--")
-        .arg("cache-dir")
+        .arg("bundle")
+        .arg("export")
+        .arg("--all-platforms")
+        .arg("-o")
+        .arg(&bundle_path)
+        .arg(&dotslash_file)
         .assert()
         .code(0)
-        .stderr_eq("")
-        .stdout_eq("[DOTSLASH_CACHE_DIR]\n");
-}
+        .stdout_eq("")
+        .stderr_eq(format!(
+            "Exported 2 artifact(s) to `{}`\n",
+            bundle_path.display()
+        ));
 
-#[test]
-fn cache_dir_command_extra_args() {
-    DotslashTestEnv::try_new()
-        .unwrap()
+    // The importing machine is offline and has a cache of its own.
+    let import_env = DotslashTestEnv::try_new()?;
+    import_env
         .dotslash_command()
+        .env("DOTSLASH_OFFLINE", "1")
         .arg("--")
-        .arg("cache-dir")
-        .arg("foo")
+        .arg("bundle")
+        .arg("import")
+        .arg(&bundle_path)
         .assert()
-        .code(1)
-        .stdout_eq("")
-        .stderr_eq(
+        .code(0)
+        .stdout_eq(
             "\
-dotslash error: 'cache-dir' command failed
-caused by: expected no arguments but received some
+my_tool: linux-x86_64: imported
+my_tool: macos-aarch64: imported
+",
+        )
+        .stderr_eq("");
+
+    let assert = import_env
+        .dotslash_command()
+        .arg("--")
+        .arg("get-extracted-cache-path")
+        .arg("--all-platforms")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0);
+    let stdout = str::from_utf8(&assert.get_output().stdout)?;
+    for line in stdout.lines() {
+        let (platform, executable) = line.split_once(": ").unwrap();
+        let expected = platform.split('-').next().unwrap();
+        assert_eq!(fs::read_to_string(executable)?, format!("{expected}\n"));
+    }
+
+    import_env
+        .dotslash_command()
+        .arg("--")
+        .arg("bundle")
+        .arg("import")
+        .arg(&bundle_path)
+        .assert()
+        .code(0)
+        .stdout_eq(
+            "\
+my_tool: linux-x86_64: already cached
+my_tool: macos-aarch64: already cached
 ",
         );
-}
 
-//
-// "clean" Command
-//
+    Ok(())
+}
 
 #[test]
-fn clean_command_ok() -> anyhow::Result<()> {
+fn bundle_command_export_same_name() -> anyhow::Result<()> {
     let test_env = DotslashTestEnv::try_new()?;
-
-    let cache_dir = test_env.dotslash_cache();
-
-    // Cache dir exists, but should be empty.
-    assert!(cache_dir.exists());
-    let is_empty = fs::read_dir(cache_dir)?.next().is_none();
-    assert!(is_empty);
+    // Two versions of a tool, e.g., from different branches.
+    let mut dotslash_files = vec![];
+    for (file_name, contents) in [("v1", b"one\n"), ("v2", b"two\n")] {
+        let dotslash_file = test_env.local_dotslash_file(file_name, contents)?;
+        let dotslash_data = fs::read_to_string(&dotslash_file)?;
+        fs::write(
+            &dotslash_file,
+            dotslash_data.replace(&format!(r#""name": "{file_name}""#), r#""name": "my_tool""#),
+        )?;
+        dotslash_files.push(dotslash_file);
+    }
+    let bundle_path = test_env.config_file().with_file_name("tools.tar");
 
     test_env
         .dotslash_command()
-        .arg("tests/fixtures/http__tar_gz__print_argv")
+        .arg("--")
+        .arg("bundle")
+        .arg("export")
+        .arg("-o")
+        .arg(&bundle_path)
+        .args(&dotslash_files)
         .assert()
-        .success();
+        .code(0)
+        .stdout_eq("")
+        .stderr_eq(format!(
+            "Exported 2 artifact(s) to `{}`\n",
+            bundle_path.display()
+        ));
 
-    assert!(cache_dir.exists());
+    let import_env = DotslashTestEnv::try_new()?;
+    import_env
+        .dotslash_command()
+        .arg("--")
+        .arg("bundle")
+        .arg("import")
+        .arg(&bundle_path)
+        .assert()
+        .code(0)
+        .stdout_eq(format!(
+            "my_tool: {platform}: imported\nmy_tool: {platform}: imported\n",
+            platform = dotslash::SUPPORTED_PLATFORM,
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn bundle_command_import_not_a_bundle() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let dotslash_file = test_env.local_dotslash_file("my_tool", b"tool\n")?;
+    let not_a_bundle = dotslash_file.with_file_name("not_a_bundle.tar");
+    let mut builder = tar::Builder::new(fs::File::create(&not_a_bundle)?);
+    builder.append_path_with_name(&dotslash_file, "my_tool")?;
+    builder.finish()?;
+    drop(builder);
 
     test_env
         .dotslash_command()
         .arg("--")
-        .arg("clean")
+        .arg("bundle")
+        .arg("import")
+        .arg(&not_a_bundle)
         .assert()
-        .code(0)
+        .code(1)
         .stdout_eq("")
-        .stderr_eq("Cleaning `[DOTSLASH_CACHE_DIR]`\n");
-
-    assert!(!cache_dir.exists());
+        .stderr_eq(format!(
+            "\
+dotslash error: 'bundle' command failed
+caused by: `{}` is not a DotSlash bundle
+",
+            not_a_bundle.display(),
+        ));
 
     Ok(())
 }
 
 #[test]
-fn clean_command_extra_args() {
+fn bundle_command_unknown_action() {
     DotslashTestEnv::try_new()
         .unwrap()
         .dotslash_command()
         .arg("--")
-        .arg("clean")
-        .arg("foo")
+        .arg("bundle")
+        .arg("pack")
         .assert()
         .code(1)
         .stdout_eq("")
         .stderr_eq(
             "\
-dotslash error: 'clean' command failed
-caused by: expected no arguments but received some
+dotslash error: 'bundle' command failed
+caused by: unknown action `pack` for `bundle` (expected `export` or `import`)
 ",
         );
 }
 
 //
-// "create-url-entry" Command
+// "cache" Command
 //
 
 #[test]
-fn create_url_entry_tar_gz() {
+fn cache_ls_command_empty_cache() {
     DotslashTestEnv::try_new()
         .unwrap()
         .dotslash_command()
         .arg("--")
-        .arg("create-url-entry")
-        .arg("https://github.com/zertosh/dotslash_fixtures/raw/462625c6bf2671439dce66bd5bc40b05f2ed8819/pack.tar.gz")
+        .arg("cache")
+        .arg("ls")
         .assert()
         .code(0)
         .stderr_eq("")
         .stdout_eq(
-            r#"{
-  "size": 48689,
-  "hash": "blake3",
-  "digest": "068464830bd5c276e085a4eab5ef9cc57159f94273db296d6a638e49b78ca55f",
-  "format": "tar.gz",
-  "path": "TODO: specify the appropriate `path` for this artifact",
-  "providers": [
-    {
-      "url": "https://github.com/zertosh/dotslash_fixtures/raw/462625c6bf2671439dce66bd5bc40b05f2ed8819/pack.tar.gz"
-    }
-  ]
-}
-"#,
+            "\
+SIZE  LAST USED  NAME  KEY  DOTSLASH FILE
+0 artifact(s), 0 B total
+",
         );
 }
 
 #[test]
-fn create_url_entry_tar_zst() {
-    DotslashTestEnv::try_new()
-        .unwrap()
+fn cache_ls_command_json() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let dotslash_file = test_env.local_dotslash_file("my_tool", b"contents\n")?;
+    test_env
         .dotslash_command()
         .arg("--")
-        .arg("create-url-entry")
-        .arg("https://github.com/zertosh/dotslash_fixtures/raw/462625c6bf2671439dce66bd5bc40b05f2ed8819/pack.tar.zst")
+        .arg("fetch")
+        .arg(&dotslash_file)
         .assert()
-        .code(0)
-        .stderr_eq("")
-        .stdout_eq(
-            r#"{
-  "size": 38886,
-  "hash": "blake3",
-  "digest": "2dedd1652985d33607d1eff62fe9f85f7f53dfce9902cb5de8a8c680cba48081",
-  "format": "tar.zst",
-  "path": "TODO: specify the appropriate `path` for this artifact",
-  "providers": [
-    {
-      "url": "https://github.com/zertosh/dotslash_fixtures/raw/462625c6bf2671439dce66bd5bc40b05f2ed8819/pack.tar.zst"
-    }
-  ]
-}
-"#,
-        );
-}
-
-//
-// "fetch" Command
-//
-
-#[test]
-fn fetch_simple() -> anyhow::Result<()> {
-    let mut test_env = DotslashTestEnv::try_new()?;
-    test_env.path_redaction(
-        "[ARTIFACT_EXE]",
-        "[DOTSLASH_CACHE_DIR]/[PACK_TGZ_HTTP_ARCHIVE_CACHE_DIR]/subdir/[PRINT_ARGV_EXECUTABLE]",
-    );
+        .code(0);
 
     let assert = test_env
         .dotslash_command()
         .arg("--")
-        .arg("fetch")
-        .arg("tests/fixtures/http__tar_gz__print_argv")
+        .arg("cache")
+        .arg("ls")
+        .arg("--json")
         .assert()
         .code(0)
-        .stderr_eq("")
-        .stdout_eq("[ARTIFACT_EXE]\n");
-
-    let artifact = str::from_utf8(&assert.get_output().stdout)?.trim_end();
+        .stderr_eq("");
+    let entries: serde_json::Value = serde_json::from_slice(&assert.get_output().stdout)?;
+    let entries = entries.as_array().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["size_in_bytes"], 9);
+    assert_eq!(entries[0]["origin"]["name"], "my_tool");
+    assert_eq!(
+        entries[0]["origin"]["dotslash_file"],
+        dotslash_file.to_str().unwrap(),
+    );
 
-    let metadata = fs::metadata(artifact)?;
-    assert!(metadata.is_file());
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("cache")
+        .arg("ls")
+        .assert()
+        .code(0);
+    let stdout = str::from_utf8(&assert.get_output().stdout)?;
+    assert!(stdout.contains("9 B"), "{stdout}");
+    assert!(stdout.contains("my_tool"), "{stdout}");
+    assert!(stdout.ends_with("1 artifact(s), 9 B total\n"), "{stdout}");
 
     Ok(())
 }
 
-#[cfg(unix)]
 #[test]
-fn fetch_plain_sets_default_executable_permissions() -> anyhow::Result<()> {
-    let mut test_env = DotslashTestEnv::try_new()?;
-    test_env.path_redaction(
-        "[ARTIFACT_EXE]",
-        "[DOTSLASH_CACHE_DIR]/[PACK_PLAIN_HTTP_ARCHIVE_CACHE_DIR]/subdir/[PRINT_ARGV_EXECUTABLE]",
-    );
-
-    let assert = test_env
+fn cache_command_unknown_action() {
+    DotslashTestEnv::try_new()
+        .unwrap()
         .dotslash_command()
         .arg("--")
-        .arg("fetch")
-        .arg("tests/fixtures/http__plain__print_argv")
+        .arg("cache")
+        .arg("rm")
         .assert()
-        .code(0)
-        .stderr_eq("")
-        .stdout_eq("[ARTIFACT_EXE]\n");
-
-    let artifact = str::from_utf8(&assert.get_output().stdout)?.trim_end();
-
-    let metadata = fs::metadata(artifact)?;
-    assert!(metadata.is_file());
-    assert_eq!(metadata.permissions().mode() & 0o777, 0o555);
-
-    Ok(())
+        .code(1)
+        .stdout_eq("")
+        .stderr_eq(
+            "\
+dotslash error: 'cache' command failed
+caused by: unknown action `rm` for `cache` (expected `ls`)
+",
+        );
 }
 
 //
-// "get-extracted-cache-path" Command
+// "cache-dir" Command
 //
 
 #[test]
-fn get_extracted_cache_path_simple() -> anyhow::Result<()> {
-    let mut test_env = DotslashTestEnv::try_new()?;
-    test_env.path_redaction(
-        "[ARTIFACT_EXE]",
-        "[DOTSLASH_CACHE_DIR]/[PACK_TGZ_HTTP_ARCHIVE_CACHE_DIR]/subdir/[PRINT_ARGV_EXECUTABLE]",
-    );
-
-    test_env
+fn cache_dir_command_ok() {
+    DotslashTestEnv::try_new()
+        .unwrap()
         .dotslash_command()
         .arg("--")
-        .arg("get-extracted-cache-path")
-        .arg("tests/fixtures/http__tar_gz__print_argv")
+        .arg("cache-dir")
         .assert()
         .code(0)
         .stderr_eq("")
-        .stdout_eq("[ARTIFACT_EXE]\n");
-
-    Ok(())
+        .stdout_eq("[DOTSLASH_CACHE_DIR]\n");
 }
 
 #[test]
-fn get_extracted_cache_path_no_args() {
+fn cache_dir_command_extra_args() {
     DotslashTestEnv::try_new()
         .unwrap()
         .dotslash_command()
         .arg("--")
-        .arg("get-extracted-cache-path")
+        .arg("cache-dir")
+        .arg("foo")
         .assert()
         .code(1)
         .stdout_eq("")
         .stderr_eq(
             "\
-dotslash error: 'get-extracted-cache-path' command failed
-caused by: expected exactly one argument but received none
+dotslash error: 'cache-dir' command failed
+caused by: expected no arguments but received some
 ",
         );
 }
 
+//
+// "config" Command
+//
+
 #[test]
-fn get_extracted_cache_path_extra_args() {
-    DotslashTestEnv::try_new()
-        .unwrap()
+fn config_command_show() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let config_file = test_env.config_file();
+    fs::write(&config_file, r#"{"offline": true, "timeout_sec": 30}"#)?;
+
+    let assert = test_env
         .dotslash_command()
+        .env("DOTSLASH_CURL_CONNECT_TIMEOUT_SEC", "5")
+        .env_remove("DOTSLASH_CURL_TIMEOUT_SEC")
+        .env_remove("DOTSLASH_HTTP_CLIENT")
         .arg("--")
-        .arg("get-extracted-cache-path")
-        .arg("tests/fixtures/http__tar_gz__print_argv")
-        .arg("extra_arg")
+        .arg("config")
+        .arg("show")
+        .arg("--json")
         .assert()
-        .code(1)
-        .stdout_eq("")
-        .stderr_eq(
-            "\
-dotslash error: 'get-extracted-cache-path' command failed
-caused by: expected exactly one argument but received more
-",
-        );
-}
+        .code(0)
+        .stderr_eq("");
+    let config: serde_json::Value = serde_json::from_slice(&assert.get_output().stdout)?;
+    let file_source = serde_json::json!({"type": "file", "path": config_file});
+    assert_eq!(
+        config["cache_dir"],
+        serde_json::json!({
+            "value": test_env.dotslash_cache(),
+            "source": {"type": "env", "name": "DOTSLASH_CACHE"},
+        }),
+    );
+    assert_eq!(
+        config["offline"],
+        serde_json::json!({"value": true, "source": file_source}),
+    );
+    assert_eq!(
+        config["timeout_sec"],
+        serde_json::json!({"value": 30, "source": file_source}),
+    );
+    assert_eq!(
+        config["connect_timeout_sec"],
+        serde_json::json!({
+            "value": 5,
+            "source": {"type": "env", "name": "DOTSLASH_CURL_CONNECT_TIMEOUT_SEC"},
+        }),
+    );
+    assert_eq!(
+        config["http_client"],
+        serde_json::json!({"value": null, "source": {"type": "default"}}),
+    );
+    assert_eq!(
+        config["files"][0],
+        serde_json::json!({"path": config_file, "status": "loaded"}),
+    );
 
-#[test]
-fn get_extracted_cache_path_nonexistent_file() {
-    let env = DotslashTestEnv::try_new().unwrap();
-    let output = env
+    let assert = test_env
         .dotslash_command()
+        .env_remove("DOTSLASH_HTTP_CLIENT")
         .arg("--")
-        .arg("get-extracted-cache-path")
-        .arg("nonexistent_file")
-        .output()
-        .unwrap();
+        .arg("config")
+        .arg("show")
+        .assert()
+        .code(0)
+        .stderr_eq("");
+    let stdout = str::from_utf8(&assert.get_output().stdout)?;
+    let rows = stdout
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    assert_eq!(rows[0], ["KEY", "VALUE", "SOURCE"]);
+    assert_eq!(rows[1][0], "cache_dir");
+    assert_eq!(rows[1][2], "`DOTSLASH_CACHE`");
+    assert_eq!(
+        rows[4],
+        ["offline", "true", &format!("`{}`", config_file.display()),],
+    );
+    assert_eq!(rows[5], ["http_client", "-", "default"]);
+    assert!(
+        stdout.contains(&format!("{}  loaded\n", config_file.display())),
+        "{stdout}",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn config_command_offline_from_file() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let dotslash_file = test_env.local_dotslash_file("my_tool", b"contents\n")?;
+    fs::write(test_env.config_file(), r#"{"offline": true}"#)?;
+
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg(&dotslash_file)
+        .assert()
+        .code(1)
+        .stdout_eq("");
+    let stderr = str::from_utf8(&assert.get_output().stderr)?;
+    assert!(
+        stderr.contains(&format!(
+            "caused by: offline mode is enabled by `{}` but the artifact for `my_tool`",
+            test_env.config_file().display(),
+        )),
+        "{stderr}",
+    );
+
+    // The environment takes precedence over the config file.
+    test_env
+        .dotslash_command()
+        .env("DOTSLASH_OFFLINE", "0")
+        .arg("--")
+        .arg("fetch")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0)
+        .stderr_eq("");
+
+    Ok(())
+}
+
+#[test]
+fn config_command_unknown_keys_are_ignored() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    fs::write(test_env.config_file(), r#"{"ofline": true}"#)?;
+
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("cache-dir")
+        .assert()
+        .code(0);
+    let stderr = str::from_utf8(&assert.get_output().stderr)?;
+    assert!(
+        stderr.contains(&format!(
+            "dotslash warning: ignoring unknown keys in config file `{}`: `ofline`\n",
+            test_env.config_file().display(),
+        )),
+        "{stderr}",
+    );
+
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("config")
+        .arg("show")
+        .assert()
+        .code(0);
+    let stdout = str::from_utf8(&assert.get_output().stdout)?;
+    assert!(stdout.contains("loaded, ignoring `ofline`"), "{stdout}");
+
+    Ok(())
+}
+
+#[test]
+fn config_file_with_broken_policy_only_stops_fetch_subcommands() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let dotslash_file = test_env.local_dotslash_file("my_tool", b"contents\n")?;
+    fs::write(test_env.config_file(), r#"{"policy": "none"}"#)?;
+
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("cache-dir")
+        .assert()
+        .code(0);
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("parse")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0);
+
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg(&dotslash_file)
+        .assert()
+        .code(1)
+        .stdout_eq("");
+    let stderr = str::from_utf8(&assert.get_output().stderr)?;
+    assert!(
+        stderr.contains("dotslash error: 'fetch' command failed\ncaused by: invalid config file"),
+        "{stderr}",
+    );
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn config_file_with_broken_policy_only_stops_fetching() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let cached = test_env.local_dotslash_file("cached", b"#!/bin/sh\necho cached\n")?;
+    let uncached = test_env.local_dotslash_file("uncached", b"#!/bin/sh\necho uncached\n")?;
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg(&cached)
+        .assert()
+        .code(0);
+
+    fs::write(test_env.config_file(), r#"{"policy": "none"}"#)?;
+
+    // What is already in the cache still runs.
+    test_env
+        .dotslash_command()
+        .arg(&cached)
+        .assert()
+        .code(0)
+        .stdout_eq("cached\n");
+
+    let assert = test_env
+        .dotslash_command()
+        .arg(&uncached)
+        .assert()
+        .code(1)
+        .stdout_eq("");
+    let stderr = str::from_utf8(&assert.get_output().stderr)?;
+    assert!(
+        stderr.contains(&format!(
+            "dotslash warning: ignoring config file `{}`: ",
+            test_env.config_file().display(),
+        )),
+        "{stderr}",
+    );
+    assert!(
+        stderr.contains(&format!(
+            "caused by: invalid config file `{}`, which may set `policy`, `require_signature`: ",
+            test_env.config_file().display(),
+        )),
+        "{stderr}",
+    );
+
+    Ok(())
+}
+
+//
+// "clean" Command
+//
+
+#[test]
+fn clean_command_ok() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+
+    let cache_dir = test_env.dotslash_cache();
+
+    // Cache dir exists, but should be empty.
+    assert!(cache_dir.exists());
+    let is_empty = fs::read_dir(cache_dir)?.next().is_none();
+    assert!(is_empty);
+
+    test_env
+        .dotslash_command()
+        .arg("tests/fixtures/http__tar_gz__print_argv")
+        .assert()
+        .success();
+
+    assert!(cache_dir.exists());
+
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("clean")
+        .assert()
+        .code(0)
+        .stdout_eq("")
+        .stderr_eq("Cleaning `[DOTSLASH_CACHE_DIR]`\n");
+
+    assert!(!cache_dir.exists());
+
+    Ok(())
+}
+
+#[test]
+fn clean_command_extra_args() {
+    DotslashTestEnv::try_new()
+        .unwrap()
+        .dotslash_command()
+        .arg("--")
+        .arg("clean")
+        .arg("foo")
+        .assert()
+        .code(1)
+        .stdout_eq("")
+        .stderr_eq(
+            "\
+dotslash error: 'clean' command failed
+caused by: expected no arguments but received some
+",
+        );
+}
+
+//
+// "create-from-release" Command
+//
+
+#[test]
+fn create_from_release_local_json() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let dir = test_env.config_file().with_file_name("release");
+    fs::create_dir(&dir)?;
+    let file_url = |name: &str| {
+        let path = dir.join(name).display().to_string();
+        if cfg!(windows) {
+            format!("file:///{}", path.replace('\\', "/"))
+        } else {
+            format!("file://{path}")
+        }
+    };
+
+    let mut tar = tar::Builder::new(vec![]);
+    for (path, mode) in [("tool-1.0/README.md", 0o644), ("tool-1.0/tool", 0o755)] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_mode(mode);
+        tar.append_data(&mut header, path, &b"hello"[..])?;
+    }
+    let tar = tar.into_inner()?;
+    fs::write(dir.join("tool-1.0-x86_64-unknown-linux-gnu.tar"), &tar)?;
+    fs::write(dir.join("tool-1.0-aarch64-apple-darwin"), b"macos")?;
+    let release_json = dir.join("release.json");
+    fs::write(
+        &release_json,
+        serde_json::to_string(&serde_json::json!({
+            "assets": [
+                {"name": "tool-1.0-x86_64-unknown-linux-gnu.tar", "url": file_url("tool-1.0-x86_64-unknown-linux-gnu.tar")},
+                {"name": "tool-1.0-aarch64-apple-darwin", "url": file_url("tool-1.0-aarch64-apple-darwin")},
+                {"name": "tool-1.0-aarch64-apple-darwin.sha256", "url": file_url("missing")},
+            ],
+        }))?,
+    )?;
+
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("create-from-release")
+        .arg("--release-json")
+        .arg(&release_json)
+        .arg("example/tool")
+        .arg("v1.0")
+        .assert()
+        .code(0);
+    let stdout = str::from_utf8(&assert.get_output().stdout)?;
+    let json = stdout
+        .strip_prefix("#!/usr/bin/env dotslash\n\n")
+        .context("missing header")?;
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(json)?,
+        serde_json::json!({
+            "name": "tool",
+            "platforms": {
+                "linux-x86_64": {
+                    "size": tar.len(),
+                    "hash": "blake3",
+                    "digest": blake3::hash(&tar).to_hex().as_str(),
+                    "format": "tar",
+                    "path": "tool-1.0/tool",
+                    "providers": [
+                        {"url": file_url("tool-1.0-x86_64-unknown-linux-gnu.tar")},
+                        {
+                            "type": "github-release",
+                            "repo": "example/tool",
+                            "tag": "v1.0",
+                            "name": "tool-1.0-x86_64-unknown-linux-gnu.tar",
+                        },
+                    ],
+                },
+                "macos-aarch64": {
+                    "size": 5,
+                    "hash": "blake3",
+                    "digest": blake3::hash(b"macos").to_hex().as_str(),
+                    "path": "tool",
+                    "providers": [
+                        {"url": file_url("tool-1.0-aarch64-apple-darwin")},
+                        {
+                            "type": "github-release",
+                            "repo": "example/tool",
+                            "tag": "v1.0",
+                            "name": "tool-1.0-aarch64-apple-darwin",
+                        },
+                    ],
+                },
+            },
+        }),
+    );
+    let stderr = str::from_utf8(&assert.get_output().stderr)?;
+    assert!(
+        stderr.contains("No asset matches `windows-x86_64`\n"),
+        "{stderr}"
+    );
+
+    // The output is a valid DotSlash file.
+    let dotslash_file = dir.join("tool");
+    fs::write(&dotslash_file, stdout)?;
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("parse")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0);
+
+    Ok(())
+}
+
+//
+// "create-url-entry" Command
+//
+
+#[test]
+fn create_url_entry_inspects_local_archive() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let mut tar = tar::Builder::new(vec![]);
+    for (path, mode) in [("tool-1.0/README.md", 0o644), ("tool-1.0/tool", 0o755)] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_mode(mode);
+        tar.append_data(&mut header, path, &b"hello"[..])?;
+    }
+    let tar = tar.into_inner()?;
+    // The name says nothing about the format; the content does.
+    let artifact = test_env.config_file().with_file_name("download");
+    fs::write(&artifact, &tar)?;
+    let path = artifact.display().to_string();
+    let url = if cfg!(windows) {
+        format!("file:///{}", path.replace('\\', "/"))
+    } else {
+        format!("file://{path}")
+    };
+
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("create-url-entry")
+        .arg(&url)
+        .assert()
+        .code(0);
+    assert_eq!(
+        serde_json::from_slice::<serde_json::Value>(&assert.get_output().stdout)?,
+        serde_json::json!({
+            "size": tar.len(),
+            "hash": "blake3",
+            "digest": blake3::hash(&tar).to_hex().as_str(),
+            "format": "tar",
+            "path": "tool-1.0/tool",
+            "providers": [{"url": url}],
+        }),
+    );
+    Ok(())
+}
+
+#[test]
+fn create_url_entry_tar_gz() {
+    DotslashTestEnv::try_new()
+        .unwrap()
+        .dotslash_command()
+        .arg("--")
+        .arg("create-url-entry")
+        .arg("https://github.com/zertosh/dotslash_fixtures/raw/462625c6bf2671439dce66bd5bc40b05f2ed8819/pack.tar.gz")
+        .assert()
+        .code(0)
+        .stderr_eq("")
+        .stdout_eq(
+            r#"{
+  "size": 48689,
+  "hash": "blake3",
+  "digest": "068464830bd5c276e085a4eab5ef9cc57159f94273db296d6a638e49b78ca55f",
+  "format": "tar.gz",
+  "path": "TODO: choose the executable from [..]",
+  "providers": [
+    {
+      "url": "https://github.com/zertosh/dotslash_fixtures/raw/462625c6bf2671439dce66bd5bc40b05f2ed8819/pack.tar.gz"
+    }
+  ]
+}
+"#,
+        );
+}
+
+#[test]
+fn create_url_entry_tar_zst() {
+    DotslashTestEnv::try_new()
+        .unwrap()
+        .dotslash_command()
+        .arg("--")
+        .arg("create-url-entry")
+        .arg("https://github.com/zertosh/dotslash_fixtures/raw/462625c6bf2671439dce66bd5bc40b05f2ed8819/pack.tar.zst")
+        .assert()
+        .code(0)
+        .stderr_eq("")
+        .stdout_eq(
+            r#"{
+  "size": 38886,
+  "hash": "blake3",
+  "digest": "2dedd1652985d33607d1eff62fe9f85f7f53dfce9902cb5de8a8c680cba48081",
+  "format": "tar.zst",
+  "path": "TODO: choose the executable from [..]",
+  "providers": [
+    {
+      "url": "https://github.com/zertosh/dotslash_fixtures/raw/462625c6bf2671439dce66bd5bc40b05f2ed8819/pack.tar.zst"
+    }
+  ]
+}
+"#,
+        );
+}
+
+//
+// "fetch" Command
+//
+
+#[test]
+fn fetch_simple() -> anyhow::Result<()> {
+    let mut test_env = DotslashTestEnv::try_new()?;
+    test_env.path_redaction(
+        "[ARTIFACT_EXE]",
+        "[DOTSLASH_CACHE_DIR]/[PACK_TGZ_HTTP_ARCHIVE_CACHE_DIR]/subdir/[PRINT_ARGV_EXECUTABLE]",
+    );
+
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg("tests/fixtures/http__tar_gz__print_argv")
+        .assert()
+        .code(0)
+        .stderr_eq("")
+        .stdout_eq("[ARTIFACT_EXE]\n");
+
+    let artifact = str::from_utf8(&assert.get_output().stdout)?.trim_end();
+
+    let metadata = fs::metadata(artifact)?;
+    assert!(metadata.is_file());
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn fetch_plain_sets_default_executable_permissions() -> anyhow::Result<()> {
+    let mut test_env = DotslashTestEnv::try_new()?;
+    test_env.path_redaction(
+        "[ARTIFACT_EXE]",
+        "[DOTSLASH_CACHE_DIR]/[PACK_PLAIN_HTTP_ARCHIVE_CACHE_DIR]/subdir/[PRINT_ARGV_EXECUTABLE]",
+    );
+
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg("tests/fixtures/http__plain__print_argv")
+        .assert()
+        .code(0)
+        .stderr_eq("")
+        .stdout_eq("[ARTIFACT_EXE]\n");
+
+    let artifact = str::from_utf8(&assert.get_output().stdout)?.trim_end();
+
+    let metadata = fs::metadata(artifact)?;
+    assert!(metadata.is_file());
+    assert_eq!(metadata.permissions().mode() & 0o777, 0o555);
+
+    Ok(())
+}
+
+#[test]
+fn fetch_offline() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let dotslash_file = test_env.local_dotslash_file("my_tool", b"contents\n")?;
+
+    let assert = test_env
+        .dotslash_command()
+        .env("DOTSLASH_OFFLINE", "1")
+        .arg("--")
+        .arg("fetch")
+        .arg(&dotslash_file)
+        .assert()
+        .code(1)
+        .stdout_eq("");
+    let stderr = str::from_utf8(&assert.get_output().stderr)?;
+    assert!(
+        stderr.contains(&format!(
+            "caused by: offline mode is enabled by `DOTSLASH_OFFLINE` but the artifact for `my_tool` (`{}`) is not in the cache at ",
+            dunce::canonicalize(&dotslash_file)?.display(),
+        )),
+        "{stderr}",
+    );
+
+    // Running the DotSlash file directly fails the same way.
+    let assert = test_env
+        .dotslash_command()
+        .env("DOTSLASH_OFFLINE", "1")
+        .arg(&dotslash_file)
+        .assert()
+        .code(1);
+    let stderr = str::from_utf8(&assert.get_output().stderr)?;
+    assert!(
+        stderr.contains("offline mode is enabled by `DOTSLASH_OFFLINE`"),
+        "{stderr}",
+    );
+
+    // Once the artifact is cached, offline mode does not get in the way.
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0);
+    test_env
+        .dotslash_command()
+        .env("DOTSLASH_OFFLINE", "1")
+        .arg("--")
+        .arg("fetch")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0)
+        .stderr_eq("");
+
+    Ok(())
+}
+
+#[test]
+fn fetch_from_mirror() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let dotslash_file = test_env.local_dotslash_file("my_tool", b"contents\n")?;
+    let fixtures_url = if cfg!(windows) {
+        format!(
+            "file:///{}/",
+            dotslash_file
+                .parent()
+                .unwrap()
+                .display()
+                .to_string()
+                .replace('\\', "/"),
+        )
+    } else {
+        format!("file://{}/", dotslash_file.parent().unwrap().display())
+    };
+
+    // The DotSlash file points somewhere unreachable, but the mirror has it.
+    let contents = fs::read_to_string(&dotslash_file)?;
+    fs::write(
+        &dotslash_file,
+        contents.replace(&fixtures_url, "file:///does-not-exist/"),
+    )?;
+    fs::write(
+        test_env.config_file(),
+        serde_json::to_string(&serde_json::json!({
+            "mirrors": [{"prefix": "file:///does-not-exist/", "replacement": fixtures_url}],
+        }))?,
+    )?;
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0);
+    let executable = str::from_utf8(&assert.get_output().stdout)?.trim_end();
+    assert_eq!(fs::read_to_string(executable)?, "contents\n");
+
+    // The other way around, the original provider is used when the mirror
+    // does not have the artifact.
+    fs::write(&dotslash_file, contents)?;
+    fs::write(
+        test_env.config_file(),
+        serde_json::to_string(&serde_json::json!({
+            "mirrors": [{"prefix": fixtures_url, "replacement": "file:///does-not-exist/"}],
+        }))?,
+    )?;
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("clean")
+        .assert()
+        .code(0);
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0);
+    let executable = str::from_utf8(&assert.get_output().stdout)?.trim_end();
+    assert_eq!(fs::read_to_string(executable)?, "contents\n");
+
+    Ok(())
+}
+
+#[test]
+fn fetch_rejected_by_policy() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let dotslash_file = test_env.local_dotslash_file("my_tool", b"contents\n")?;
+    let config_file = test_env.config_file();
+
+    for (policy, reason) in [
+        (
+            serde_json::json!({"allow_provider_types": ["s3"]}),
+            "provider type `http` is not allowed".to_owned(),
+        ),
+        (
+            serde_json::json!({"allow_schemes": ["https"]}),
+            "scheme `file` of `file://".to_owned(),
+        ),
+    ] {
+        fs::write(
+            &config_file,
+            serde_json::to_string(&serde_json::json!({"policy": policy}))?,
+        )?;
+        let assert = test_env
+            .dotslash_command()
+            .arg("--")
+            .arg("fetch")
+            .arg(&dotslash_file)
+            .assert()
+            .code(1)
+            .stdout_eq("");
+        let stderr = str::from_utf8(&assert.get_output().stderr)?;
+        assert!(
+            stderr.contains(&format!(
+                "caused by: no providers succeeded. rejected by policy:\n{reason}",
+            )),
+            "{stderr}",
+        );
+        assert!(
+            stderr.contains(&format!("(policy in `{}`)\n", config_file.display())),
+            "{stderr}",
+        );
+        assert!(!stderr.contains("warnings:"), "{stderr}");
+    }
+
+    // A policy that allows the provider does not get in the way.
+    fs::write(
+        &config_file,
+        r#"{"policy": {"allow_provider_types": ["http"], "deny_schemes": ["http"]}}"#,
+    )?;
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0)
+        .stderr_eq("");
+
+    Ok(())
+}
+
+#[test]
+fn fetch_checks_redirects_against_policy() -> anyhow::Result<()> {
+    use std::io::BufRead as _;
+    use std::io::BufReader;
+    use std::io::Write as _;
+    use std::net::TcpListener;
+
+    // Redirects `/artifact` on `127.0.0.1` to `localhost`, which serves it.
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let port = listener.local_addr()?.port();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { return };
+            let mut reader = BufReader::new(&stream);
+            let mut request_line = String::new();
+            let _ = reader.read_line(&mut request_line);
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap_or(0) > 2 {
+                line.clear();
+            }
+            let response = if request_line.contains("/redirected") {
+                "HTTP/1.1 200 OK\r\nContent-Length: 9\r\nConnection: close\r\n\r\ncontents\n"
+                    .to_owned()
+            } else {
+                format!(
+                    "HTTP/1.1 302 Found\r\nLocation: http://localhost:{port}/redirected\r\n\
+                     Content-Length: 0\r\nConnection: close\r\n\r\n",
+                )
+            };
+            let _ = (&stream).write_all(response.as_bytes());
+        }
+    });
+
+    let test_env = DotslashTestEnv::try_new()?;
+    let dotslash_file = test_env.local_dotslash_file("my_tool", b"contents\n")?;
+    let contents = fs::read_to_string(&dotslash_file)?;
+    let (before, after) = contents.split_once("\"url\": \"").context("no url")?;
+    let (_, after) = after.split_once('"').context("no url")?;
+    fs::write(
+        &dotslash_file,
+        format!("{before}\"url\": \"http://127.0.0.1:{port}/artifact\"{after}"),
+    )?;
+
+    let config_file = test_env.config_file();
+    fs::write(
+        &config_file,
+        r#"{"policy": {"allow_hosts": ["127.0.0.1"]}}"#,
+    )?;
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg(&dotslash_file)
+        .assert()
+        .code(1)
+        .stdout_eq("");
+    let stderr = str::from_utf8(&assert.get_output().stderr)?;
+    assert!(
+        stderr.contains(&format!(
+            "redirected to a URL that is not allowed: host `localhost` of \
+             `http://localhost:{port}/redirected` is not allowed (policy in `{}`)",
+            config_file.display(),
+        )),
+        "{stderr}",
+    );
+
+    fs::write(
+        &config_file,
+        r#"{"policy": {"allow_hosts": ["127.0.0.1", "localhost"]}}"#,
+    )?;
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0)
+        .stderr_eq("");
+
+    Ok(())
+}
+
+#[test]
+fn fetch_checks_additional_digests() -> anyhow::Result<()> {
+    const SHA512: &str = "ba20e411c40b8311f6364354226f8c3617cc8c3cf7df43d66ab1ced75da81e7e\
+                          5099933dc4b6deeed6dd69c0b87913616e532beb0d9118b724b16c47e8e76f36";
+    let test_env = DotslashTestEnv::try_new()?;
+    let dotslash_file = test_env.local_dotslash_file("my_tool", b"contents\n")?;
+    let contents = fs::read_to_string(&dotslash_file)?;
+    let with_sha512 = |digest: &str| {
+        contents.replace(
+            "\"hash\": \"blake3\",",
+            &format!(
+                "\"hash\": \"blake3\",\n\"additional_digests\": \
+                 [{{\"hash\": \"sha512\", \"digest\": \"{digest}\"}}],",
+            ),
+        )
+    };
+
+    fs::write(&dotslash_file, with_sha512(&"ab".repeat(64)))?;
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg(&dotslash_file)
+        .assert()
+        .code(1)
+        .stdout_eq("");
+    let stderr = str::from_utf8(&assert.get_output().stderr)?;
+    assert!(
+        stderr.contains(&format!(
+            "has incorrect sha512 digest: \
+             {SHA512} vs expected {}",
+            "ab".repeat(64),
+        )),
+        "{stderr}",
+    );
+
+    fs::write(&dotslash_file, with_sha512(SHA512))?;
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0)
+        .stderr_eq("");
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn fetch_with_exec_provider() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let dotslash_file = test_env.local_dotslash_file("my_tool", b"contents\n")?;
+    let contents = fs::read_to_string(&dotslash_file)?;
+    let (before, after) = contents
+        .split_once("\"providers\": [")
+        .context("no providers")?;
+    let (_, after) = after.split_once(']').context("no providers")?;
+    fs::write(
+        &dotslash_file,
+        format!(r#"{before}"providers": [{{"type": "exec", "name": "local"}}]{after}"#),
+    )?;
+
+    // Reads the destination from the request on stdin.
+    let bin_dir = test_env.config_file().with_file_name("bin");
+    fs::create_dir(&bin_dir)?;
+    let program = bin_dir.join("dotslash-provider-local");
+    fs::write(
+        &program,
+        "#!/bin/sh\n\
+         dest=$(sed -n 's/.*\"destination\":\"\\([^\"]*\\)\".*/\\1/p')\n\
+         printf 'contents\\n' > \"$dest\"\n\
+         echo '{}'\n",
+    )?;
+    fs::set_permissions(&program, fs::Permissions::from_mode(0o755))?;
+    let path = std::env::join_paths(std::iter::once(bin_dir).chain(std::env::split_paths(
+        &std::env::var_os("PATH").unwrap_or_default(),
+    )))?;
+
+    test_env
+        .dotslash_command()
+        .env("PATH", &path)
+        .arg("--")
+        .arg("fetch")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0)
+        .stderr_eq("");
+
+    // Without the program installed, there is nothing else to fall back on.
+    fs::remove_file(&program)?;
+    fs::remove_dir_all(test_env.dotslash_cache())?;
+    let assert = test_env
+        .dotslash_command()
+        .env("PATH", &path)
+        .arg("--")
+        .arg("fetch")
+        .arg(&dotslash_file)
+        .assert()
+        .code(1);
+    let stderr = str::from_utf8(&assert.get_output().stderr)?;
+    assert!(
+        stderr.contains("`dotslash-provider-local` was not found on PATH"),
+        "{stderr}",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn format_mismatch_is_reported_by_lint_and_unpack_errors() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    // Only the magic matters, not whether the rest is valid gzip.
+    let dotslash_file = test_env.local_dotslash_file("my_tool", b"\x1f\x8bnot gzip\n")?;
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0)
+        .stderr_eq("");
+
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("lint")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0);
+    let stdout = str::from_utf8(&assert.get_output().stdout)?;
+    assert!(
+        stdout.contains(
+            ": warning: the cached artifact is gzip-compressed, but has no `format` [format-mismatch]\n"
+        ),
+        "{stdout}",
+    );
+
+    // The mismatch is likely why unpacking fails.
+    let dotslash_file = test_env.local_dotslash_file("other_tool", b"\x28\xb5\x2f\xfd\n")?;
+    let contents = fs::read_to_string(&dotslash_file)?;
+    fs::write(
+        &dotslash_file,
+        contents.replace(
+            "\"hash\": \"blake3\",",
+            "\"hash\": \"blake3\",\n\"format\": \"tar.gz\",",
+        ),
+    )?;
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg(&dotslash_file)
+        .assert()
+        .code(1);
+    let stderr = str::from_utf8(&assert.get_output().stderr)?;
+    assert!(
+        stderr.contains("the artifact is zstd-compressed, but has `\"format\": \"tar.gz\"`\n"),
+        "{stderr}",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn fetch_many() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let tool_a = test_env.local_dotslash_file("tool_a", b"a\n")?;
+    let tool_b = test_env.local_dotslash_file("tool_b", b"b\n")?;
+    let fixtures_dir = tool_a.parent().unwrap();
+
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg("--recursive")
+        .arg(fixtures_dir)
+        .arg("--jobs")
+        .arg("2")
+        .assert()
+        .code(0)
+        .stderr_eq("2 DotSlash file(s): 2 fetched, 0 already cached, 0 failed\n");
+    let stdout = str::from_utf8(&assert.get_output().stdout)?;
+    let lines = stdout.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2, "{stdout}");
+    assert!(lines[0].starts_with(&format!("{}: ", tool_a.display())));
+    assert!(lines[1].starts_with(&format!("{}: ", tool_b.display())));
+    for line in lines {
+        let (_, executable) = line.split_once(": ").unwrap();
+        assert!(fs::metadata(executable)?.is_file());
+    }
+
+    let missing = fixtures_dir.join("missing");
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg(&tool_a)
+        .arg(&tool_b)
+        .arg(&missing)
+        .assert()
+        .code(1);
+    let stderr = str::from_utf8(&assert.get_output().stderr)?;
+    assert!(
+        stderr.contains(&format!("Failed to fetch `{}`: ", missing.display())),
+        "{stderr}",
+    );
+    assert!(
+        stderr.ends_with(
+            "\
+3 DotSlash file(s): 0 fetched, 2 already cached, 1 failed
+dotslash error: 'fetch' command failed
+caused by: failed to fetch 1 DotSlash file(s)
+"
+        ),
+        "{stderr}",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn fetch_other_platforms() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let dotslash_file = test_env.local_dotslash_file_for_platforms(
+        "my_tool",
+        &[
+            ("linux-x86_64", b"linux\n"),
+            ("macos-aarch64", b"macos\n"),
+            ("windows-x86_64", b"windows\n"),
+        ],
+    )?;
+
+    // A single platform behaves like a plain `fetch`.
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg("--platform")
+        .arg("macos-aarch64")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0)
+        .stderr_eq("");
+    let executable = str::from_utf8(&assert.get_output().stdout)?.trim_end();
+    assert_eq!(fs::read(executable)?, b"macos\n");
+
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg("--all-platforms")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0)
+        .stderr_eq("3 platform entries: 2 fetched, 1 already cached, 0 failed\n");
+    let stdout = str::from_utf8(&assert.get_output().stdout)?;
+    let platforms = stdout
+        .lines()
+        .map(|line| line.split(": ").nth(1).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        platforms,
+        vec!["linux-x86_64", "macos-aarch64", "windows-x86_64"]
+    );
+
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("get-extracted-cache-path")
+        .arg("--all-platforms")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0);
+    let stdout = str::from_utf8(&assert.get_output().stdout)?;
+    for line in stdout.lines() {
+        let (platform, executable) = line.split_once(": ").unwrap();
+        let expected = platform.split('-').next().unwrap();
+        assert_eq!(fs::read_to_string(executable)?, format!("{expected}\n"));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn fetch_output_dir() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let dotslash_file = test_env.local_dotslash_file_for_platforms(
+        "my_tool",
+        &[("linux-x86_64", b"linux\n"), ("macos-aarch64", b"macos\n")],
+    )?;
+    let output_dir = tempfile::tempdir()?;
+
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg("--all-platforms")
+        .arg("--output-dir")
+        .arg(output_dir.path())
+        .arg(&dotslash_file)
+        .assert()
+        .code(0)
+        .stderr_eq("2 platform entries: 2 fetched, 0 already cached, 0 failed\n");
+    assert_eq!(
+        fs::read(output_dir.path().join("linux-x86_64/my_tool"))?,
+        b"linux\n"
+    );
+    assert_eq!(
+        fs::read(output_dir.path().join("macos-aarch64/my_tool"))?,
+        b"macos\n"
+    );
+    // Nothing was unpacked into the cache.
+    assert!(
+        fs::read_dir(test_env.dotslash_cache())?
+            .flatten()
+            .all(|entry| entry.file_name() == "locks")
+    );
+
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg("--platform")
+        .arg("linux-x86_64")
+        .arg("--output-dir")
+        .arg(output_dir.path())
+        .arg(&dotslash_file)
+        .assert()
+        .code(0)
+        .stderr_eq("1 platform entries: 0 fetched, 1 already cached, 0 failed\n");
+
+    Ok(())
+}
+
+#[test]
+fn fetch_output_dir_rejects_escaping_platforms() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let dotslash_file = test_env.local_dotslash_file_for_platforms(
+        "my_tool",
+        &[("linux-x86_64", b"linux\n"), ("escaped", b"escaped\n")],
+    )?;
+    let dotslash_data = fs::read_to_string(&dotslash_file)?;
+    fs::write(
+        &dotslash_file,
+        dotslash_data.replace(r#""escaped": {"#, r#""../escaped": {"#),
+    )?;
+    let parent_dir = tempfile::tempdir()?;
+    let output_dir = parent_dir.path().join("output");
+
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg("--all-platforms")
+        .arg("--output-dir")
+        .arg(&output_dir)
+        .arg(&dotslash_file)
+        .assert()
+        .code(1)
+        .stderr_eq(
+            "\
+Failed to fetch `[..]`: ../escaped: cannot store `my_tool` in the output directory: the name and the platform must each be a single path component
+2 platform entries: 1 fetched, 0 already cached, 1 failed
+dotslash error: 'fetch' command failed
+caused by: failed to fetch 1 platform entries
+",
+        );
+    assert_eq!(
+        fs::read(output_dir.join("linux-x86_64/my_tool"))?,
+        b"linux\n"
+    );
+    assert!(!parent_dir.path().join("escaped").exists());
+
+    Ok(())
+}
+
+#[test]
+fn fetch_unknown_platform() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let dotslash_file = test_env.local_dotslash_file_for_platforms(
+        "my_tool",
+        &[("linux-x86_64", b"linux\n"), ("macos-aarch64", b"macos\n")],
+    )?;
+
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg("--platform")
+        .arg("freebsd-x86_64")
+        .arg(&dotslash_file)
+        .assert()
+        .code(1)
+        .stdout_eq("")
+        .stderr_eq(
+            "\
+dotslash error: 'fetch' command failed
+caused by: platform not supported
+caused by: expected platform `freebsd-x86_64` - but found `linux-x86_64`, `macos-aarch64`
+",
+        );
+
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg("--platform")
+        .arg("linux-x86_64")
+        .arg("--all-platforms")
+        .arg(&dotslash_file)
+        .assert()
+        .code(1)
+        .stdout_eq("")
+        .stderr_eq(
+            "\
+dotslash error: 'fetch' command failed
+caused by: `--platform` and `--all-platforms` cannot be combined
+",
+        );
+
+    Ok(())
+}
+
+#[test]
+fn fetch_no_args() {
+    DotslashTestEnv::try_new()
+        .unwrap()
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .assert()
+        .code(1)
+        .stdout_eq("")
+        .stderr_eq(
+            "\
+dotslash error: 'fetch' command failed
+caused by: expected at least one DotSlash file but received none
+",
+        );
+}
+
+#[test]
+fn fetch_records_manifest() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let dotslash_file = test_env.local_dotslash_file("my_tool", b"contents\n")?;
+
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0);
+    let artifact = str::from_utf8(&assert.get_output().stdout)?.trim_end();
+    let artifact_directory = Path::new(artifact).parent().unwrap();
+    let manifest_path = artifact_directory.with_extension("json");
+
+    let manifest: serde_json::Value = serde_json::from_str(&fs::read_to_string(manifest_path)?)?;
+    let origin = &manifest["origin"];
+    assert_eq!(origin["name"], "my_tool");
+    assert_eq!(origin["dotslash_file"], dotslash_file.to_str().unwrap());
+    assert_eq!(origin["hash"], "blake3");
+    assert_eq!(
+        origin["digest"],
+        blake3::hash(b"contents\n").to_hex().as_str()
+    );
+    assert_eq!(origin["format"], serde_json::Value::Null);
+    assert_eq!(origin["path"], "my_tool");
+    assert!(
+        origin["provider"]["url"]
+            .as_str()
+            .unwrap()
+            .starts_with("file://")
+    );
+    assert!(origin["unpacked_at"].as_u64().unwrap() > 0);
+    assert_eq!(manifest["entries"][0]["path"], "my_tool");
+
+    Ok(())
+}
+
+//
+// "fmt" Command
+//
+
+#[test]
+fn fmt_check_then_rewrite() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let dir = test_env.config_file().with_file_name("fmt");
+    fs::create_dir_all(&dir)?;
+    let formatted = r#"#!/usr/bin/env dotslash
+{
+  "name": "tool",
+  "platforms": {
+    "linux-x86_64": {
+      "size": 5,
+      "hash": "blake3",
+      "digest": "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+      "path": "tool", // Not `tool.exe`.
+      "providers": [
+        {
+          "url": "https://example.com/tool"
+        }
+      ]
+    }
+  }
+}
+"#;
+    let unformatted = r#"#!/usr/bin/env dotslash
+{"platforms": {"linux-x86_64": {
+  "path": "tool", // Not `tool.exe`.
+  "providers": [{"url": "https://example.com/tool"}],
+  "size": 5, "hash": "blake3",
+  "digest": "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"}},
+ "name": "tool"}
+"#;
+    let good = dir.join("good");
+    let bad = dir.join("bad");
+    fs::write(&good, formatted)?;
+    fs::write(&bad, unformatted)?;
+
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fmt")
+        .arg("--check")
+        .arg(&good)
+        .arg(&bad)
+        .assert()
+        .code(1)
+        .stdout_eq(format!("{}\n", bad.display()))
+        .stderr_eq(
+            "dotslash error: 'fmt' command failed\ncaused by: 1 DotSlash file(s) are not formatted\n",
+        );
+    assert_eq!(fs::read_to_string(&bad)?, unformatted);
+
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fmt")
+        .arg(&good)
+        .arg(&bad)
+        .assert()
+        .code(0)
+        .stdout_eq("")
+        .stderr_eq(format!("Formatted `{}`\n", bad.display()));
+    assert_eq!(fs::read_to_string(&bad)?, formatted);
+
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fmt")
+        .arg("--check")
+        .arg(&good)
+        .arg(&bad)
+        .assert()
+        .code(0);
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn fmt_keeps_permissions_and_symlinks() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let dotslash_file = test_env.local_dotslash_file("my_tool", b"contents\n")?;
+    let contents = fs::read_to_string(&dotslash_file)?;
+    let unformatted = contents.replace("\n  ", "\n    ");
+    fs::write(&dotslash_file, &unformatted)?;
+    fs::set_permissions(&dotslash_file, fs::Permissions::from_mode(0o750))?;
+    let link = dotslash_file.with_file_name("my_tool_link");
+    std::os::unix::fs::symlink(&dotslash_file, &link)?;
+
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fmt")
+        .arg(&link)
+        .assert()
+        .code(0);
+
+    assert!(fs::symlink_metadata(&link)?.is_symlink());
+    assert_ne!(fs::read_to_string(&dotslash_file)?, unformatted);
+    assert_eq!(
+        fs::metadata(&dotslash_file)?.permissions().mode() & 0o777,
+        0o750,
+    );
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fmt")
+        .arg("--check")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0);
+
+    Ok(())
+}
+
+//
+// "gc" Command
+//
+
+#[test]
+fn gc_command_empty_cache() {
+    DotslashTestEnv::try_new()
+        .unwrap()
+        .dotslash_command()
+        .arg("--")
+        .arg("gc")
+        .arg("--max-size")
+        .arg("1G")
+        .arg("--max-age")
+        .arg("30d")
+        .assert()
+        .code(0)
+        .stdout_eq("")
+        .stderr_eq("Removed 0 artifact(s); cache size 0 B -> 0 B (0 artifact(s) remaining)\n");
+}
+
+#[test]
+fn gc_command_no_budget() {
+    DotslashTestEnv::try_new()
+        .unwrap()
+        .dotslash_command()
+        .arg("--")
+        .arg("gc")
+        .assert()
+        .code(1)
+        .stdout_eq("")
+        .stderr_eq(
+            "\
+dotslash error: 'gc' command failed
+caused by: expected at least one of `--max-size` or `--max-age`
+",
+        );
+}
+
+#[test]
+fn gc_command_invalid_size() {
+    DotslashTestEnv::try_new()
+        .unwrap()
+        .dotslash_command()
+        .arg("--")
+        .arg("gc")
+        .arg("--max-size")
+        .arg("lots")
+        .assert()
+        .code(1)
+        .stdout_eq("")
+        .stderr_eq(
+            "\
+dotslash error: 'gc' command failed
+caused by: invalid size `lots` (expected a number of bytes with an optional K, M, G or T suffix)
+",
+        );
+}
+
+//
+// "get-extracted-cache-path" Command
+//
+
+#[test]
+fn get_extracted_cache_path_simple() -> anyhow::Result<()> {
+    let mut test_env = DotslashTestEnv::try_new()?;
+    test_env.path_redaction(
+        "[ARTIFACT_EXE]",
+        "[DOTSLASH_CACHE_DIR]/[PACK_TGZ_HTTP_ARCHIVE_CACHE_DIR]/subdir/[PRINT_ARGV_EXECUTABLE]",
+    );
+
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("get-extracted-cache-path")
+        .arg("tests/fixtures/http__tar_gz__print_argv")
+        .assert()
+        .code(0)
+        .stderr_eq("")
+        .stdout_eq("[ARTIFACT_EXE]\n");
+
+    Ok(())
+}
+
+#[test]
+fn get_extracted_cache_path_no_args() {
+    DotslashTestEnv::try_new()
+        .unwrap()
+        .dotslash_command()
+        .arg("--")
+        .arg("get-extracted-cache-path")
+        .assert()
+        .code(1)
+        .stdout_eq("")
+        .stderr_eq(
+            "\
+dotslash error: 'get-extracted-cache-path' command failed
+caused by: expected exactly one argument but received none
+",
+        );
+}
+
+#[test]
+fn get_extracted_cache_path_extra_args() {
+    DotslashTestEnv::try_new()
+        .unwrap()
+        .dotslash_command()
+        .arg("--")
+        .arg("get-extracted-cache-path")
+        .arg("tests/fixtures/http__tar_gz__print_argv")
+        .arg("extra_arg")
+        .assert()
+        .code(1)
+        .stdout_eq("")
+        .stderr_eq(
+            "\
+dotslash error: 'get-extracted-cache-path' command failed
+caused by: expected exactly one argument but received more
+",
+        );
+}
+
+#[test]
+fn get_extracted_cache_path_nonexistent_file() {
+    let env = DotslashTestEnv::try_new().unwrap();
+    let output = env
+        .dotslash_command()
+        .arg("--")
+        .arg("get-extracted-cache-path")
+        .arg("nonexistent_file")
+        .output()
+        .unwrap();
 
     assert!(!output.status.success());
     let stderr = String::from_utf8_lossy(&output.stderr);
@@ -1030,14 +2669,116 @@ fn get_extracted_cache_path_invalid_dotslash_file() -> anyhow::Result<()> {
     let output = env
         .dotslash_command()
         .arg("--")
-        .arg("get-extracted-cache-path")
-        .arg(invalid_file.path())
-        .output()?;
-
-    assert!(!output.status.success());
-    let stderr = String::from_utf8_lossy(&output.stderr);
-    assert!(stderr.contains("'get-extracted-cache-path' command failed"));
-    assert!(stderr.contains("failed to parse"));
+        .arg("get-extracted-cache-path")
+        .arg(invalid_file.path())
+        .output()?;
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("'get-extracted-cache-path' command failed"));
+    assert!(stderr.contains("failed to parse"));
+
+    Ok(())
+}
+
+//
+// "lint" Command
+//
+
+#[test]
+fn lint_directory() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let dir = test_env.config_file().with_file_name("lint");
+    fs::create_dir_all(dir.join("sub"))?;
+    let entry = |url: &str| {
+        format!(
+            r#"{{
+      "size": 5,
+      "hash": "blake3",
+      "digest": "{}",
+      "path": "tool",
+      "providers": [{{"url": "{url}"}}],
+    }}"#,
+            "a".repeat(64),
+        )
+    };
+    let platforms = [
+        "linux-aarch64",
+        "linux-x86_64",
+        "macos-aarch64",
+        "macos-x86_64",
+        "windows-x86_64",
+    ]
+    .map(|platform| format!("\"{platform}\": {}", entry("https://example.com/tool")))
+    .join(",\n    ");
+    fs::write(
+        dir.join("good"),
+        format!(
+            "#!/usr/bin/env dotslash\n{{\n  \"name\": \"good\",\n  \"platforms\": {{\n    {platforms}\n  }},\n}}\n"
+        ),
+    )?;
+    fs::write(
+        dir.join("sub").join("bad"),
+        format!(
+            "#!/usr/bin/env dotslash\n{{\n  \"name\": \"bad\",\n  \"platforms\": {{\n    \"linux-x86_64\": {},\n  }},\n  \"read_only\": false,\n}}\n",
+            entry("http://example.com/tool"),
+        ),
+    )?;
+    // Not a DotSlash file, so it is not linted.
+    fs::write(dir.join("README.md"), "# Tools\n")?;
+
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("lint")
+        .arg("--json")
+        .arg(&dir)
+        .assert()
+        .code(0)
+        .stderr_eq("2 DotSlash file(s): 0 error(s), 3 warning(s)\n");
+    let diagnostics =
+        serde_json::from_slice::<Vec<serde_json::Value>>(&assert.get_output().stdout)?;
+    assert_eq!(
+        diagnostics
+            .iter()
+            .map(|diagnostic| {
+                (
+                    diagnostic["line"].as_u64().unwrap(),
+                    diagnostic["column"].as_u64().unwrap(),
+                    diagnostic["severity"].as_str().unwrap(),
+                    diagnostic["code"].as_str().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>(),
+        [
+            (4, 3, "warning", "missing-platform"),
+            (10, 29, "warning", "insecure-url"),
+            (13, 3, "warning", "unknown-field"),
+        ],
+    );
+    assert_eq!(
+        diagnostics[2]["file"].as_str(),
+        dir.join("sub").join("bad").to_str(),
+    );
+
+    // Errors fail the command.
+    let bad = dir.join("sub").join("bad");
+    fs::write(
+        &bad,
+        fs::read_to_string(&bad)?.replace("\"tool\"", "\"../tool\""),
+    )?;
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("lint")
+        .arg(&bad)
+        .assert()
+        .code(1);
+    let stdout = str::from_utf8(&assert.get_output().stdout)?;
+    assert!(
+        stdout.contains(&format!("{}:9:15: error: invalid `path`: ", bad.display())),
+        "{stdout}",
+    );
 
     Ok(())
 }
@@ -1100,6 +2841,30 @@ caused by: [IO_ERROR_NOT_FOUND]
         );
 }
 
+//
+// "schema" Command
+//
+
+#[test]
+fn schema_is_json_schema() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("schema")
+        .assert()
+        .code(0)
+        .stderr_eq("");
+    let schema = serde_json::from_slice::<serde_json::Value>(&assert.get_output().stdout)?;
+    assert_eq!(
+        schema["$schema"],
+        "https://json-schema.org/draft/2020-12/schema",
+    );
+    assert_eq!(schema["required"], serde_json::json!(["name", "platforms"]));
+    assert!(schema["$defs"]["ArtifactEntry"].is_object());
+    Ok(())
+}
+
 //
 // "sha256" Command
 //
@@ -1121,3 +2886,444 @@ fn sha256_command_ok() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+//
+// "sign" Command
+//
+
+#[test]
+fn sign_command_then_verify_signature() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let dotslash_file = test_env.local_dotslash_file("my_tool", b"contents\n")?;
+    let key_file = dotslash_file.with_file_name("key");
+
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("sign")
+        .arg("--generate-key")
+        .arg(&key_file)
+        .assert()
+        .code(0);
+    let public_key = str::from_utf8(&assert.get_output().stdout)?
+        .trim()
+        .to_owned();
+    assert_eq!(public_key.len(), 64);
+
+    // Refuses to overwrite an existing key.
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("sign")
+        .arg("--generate-key")
+        .arg(&key_file)
+        .assert()
+        .code(1);
+
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("sign")
+        .arg("--key")
+        .arg(&key_file)
+        .arg(&dotslash_file)
+        .assert()
+        .code(0)
+        .stdout_eq("");
+
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("verify-signature")
+        .arg("--public-key")
+        .arg(&public_key)
+        .arg(&dotslash_file)
+        .assert()
+        .code(0)
+        .stderr_eq("");
+    let stdout = str::from_utf8(&assert.get_output().stdout)?;
+    assert!(stdout.contains(": signed by "), "{stdout}");
+
+    // Without `--public-key`, the trusted keys in the config are used.
+    let contents = fs::read_to_string(&dotslash_file)?;
+    fs::write(
+        &dotslash_file,
+        contents.replace("\"size\": 9", "\"size\": 10"),
+    )?;
+    fs::write(
+        test_env.config_file(),
+        serde_json::to_string(&serde_json::json!({"trusted_keys": [public_key]}))?,
+    )?;
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("verify-signature")
+        .arg(&dotslash_file)
+        .assert()
+        .code(1);
+    let stdout = str::from_utf8(&assert.get_output().stdout)?;
+    assert!(
+        stdout.contains("does not match the contents of the DotSlash file"),
+        "{stdout}",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn fetch_requires_signature() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let dotslash_file = test_env.local_dotslash_file("my_tool", b"contents\n")?;
+    let config_file = test_env.config_file();
+    fs::write(&config_file, r#"{"require_signature": true}"#)?;
+
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg(&dotslash_file)
+        .assert()
+        .code(1)
+        .stdout_eq("");
+    let stderr = str::from_utf8(&assert.get_output().stderr)?;
+    assert!(
+        stderr.contains(&format!(
+            "signature verification failed (signatures are required by `{}`)\n\
+             caused by: DotSlash file is not signed",
+            config_file.display(),
+        )),
+        "{stderr}",
+    );
+
+    Ok(())
+}
+
+//
+// "update" Command
+//
+
+/// Makes the URL of the `v1` DotSlash file at `dotslash_file` a template with
+/// a `version` var.
+fn make_version_template(dotslash_file: &Path) -> anyhow::Result<()> {
+    let dotslash_data = fs::read_to_string(dotslash_file)?;
+    let (header, json) = dotslash_data.split_once('\n').context("missing header")?;
+    let mut value = serde_json::from_str::<serde_json::Value>(json)?;
+    value["vars"] = serde_json::json!({"version": "v1"});
+    for entry in value["platforms"]
+        .as_object_mut()
+        .context("missing platforms")?
+        .values_mut()
+    {
+        let provider = &mut entry["providers"][0];
+        let url = provider["url"].as_str().context("missing url")?;
+        provider["template"] = serde_json::json!({"url": url.replace("/v1.", "/{version}.")});
+    }
+    fs::write(
+        dotslash_file,
+        format!("{header}\n{}\n", serde_json::to_string_pretty(&value)?),
+    )?;
+    Ok(())
+}
+
+#[test]
+fn update_refetches_templated_providers() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let dotslash_file = test_env.local_dotslash_file("v1", b"one\n")?;
+    test_env.local_dotslash_file("v2", b"two!\n")?;
+    make_version_template(&dotslash_file)?;
+
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("update")
+        .arg("--set")
+        .arg("version=v2")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0)
+        .stdout_eq("")
+        .stderr_eq("Updated `[..]` (5 B)\n");
+
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0);
+    let executable = str::from_utf8(&assert.get_output().stdout)?.trim_end();
+    assert_eq!(fs::read(executable)?, b"two!\n");
+
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("update")
+        .arg("--set")
+        .arg("release=v3")
+        .arg(&dotslash_file)
+        .assert()
+        .code(1)
+        .stderr_eq(
+            "dotslash error: 'update' command failed\n\
+             caused by: failed to update `[..]`\n\
+             caused by: unknown variable `release`; expected one of `version`\n",
+        );
+
+    Ok(())
+}
+
+#[test]
+fn update_respects_offline_mode_and_policies() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let dotslash_file = test_env.local_dotslash_file("v1", b"one\n")?;
+    test_env.local_dotslash_file("v2", b"two!\n")?;
+    make_version_template(&dotslash_file)?;
+    let original = fs::read_to_string(&dotslash_file)?;
+
+    test_env
+        .dotslash_command()
+        .env("DOTSLASH_OFFLINE", "1")
+        .arg("--")
+        .arg("update")
+        .arg("--set")
+        .arg("version=v2")
+        .arg(&dotslash_file)
+        .assert()
+        .code(1)
+        .stderr_eq(
+            "dotslash error: 'update' command failed\n\
+             caused by: failed to update `[..]`\n\
+             caused by: offline mode is enabled by `DOTSLASH_OFFLINE`, so the artifact for `[..]` cannot be fetched\n",
+        );
+
+    fs::write(
+        test_env.config_file(),
+        r#"{"policy": {"allow_provider_types": ["s3"]}}"#,
+    )?;
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("update")
+        .arg("--set")
+        .arg("version=v2")
+        .arg(&dotslash_file)
+        .assert()
+        .code(1);
+    let stderr = str::from_utf8(&assert.get_output().stderr)?;
+    assert!(
+        stderr.contains("rejected by policy:\nprovider type `http` is not allowed"),
+        "{stderr}",
+    );
+
+    assert_eq!(fs::read_to_string(&dotslash_file)?, original);
+
+    Ok(())
+}
+
+//
+// "verify" Command
+//
+
+#[test]
+fn verify_command_detects_and_repairs_drift() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let dotslash_file = test_env.local_dotslash_file("my_tool", b"original contents\n")?;
+
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0);
+    let artifact = str::from_utf8(&assert.get_output().stdout)?
+        .trim_end()
+        .to_owned();
+
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("verify")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0);
+    let stdout = str::from_utf8(&assert.get_output().stdout)?;
+    assert!(stdout.ends_with(": ok\n"), "{stdout}");
+
+    // Tamper with the cached artifact.
+    let mut perms = fs::metadata(&artifact)?.permissions();
+    #[expect(clippy::permissions_set_readonly_false)]
+    perms.set_readonly(false);
+    fs::set_permissions(&artifact, perms.clone())?;
+    fs::write(&artifact, "tampered contents\n")?;
+    perms.set_readonly(true);
+    fs::set_permissions(&artifact, perms)?;
+
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("verify")
+        .arg(&dotslash_file)
+        .assert()
+        .code(1)
+        .stderr_eq(
+            "\
+dotslash error: 'verify' command failed
+caused by: 1 cached artifact(s) failed verification
+",
+        );
+    let stdout = str::from_utf8(&assert.get_output().stdout)?;
+    assert!(
+        stdout.contains(": drift detected\n  `my_tool` has different contents\n"),
+        "{stdout}",
+    );
+
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("verify")
+        .arg("--repair")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0);
+    let stdout = str::from_utf8(&assert.get_output().stdout)?;
+    assert!(
+        stdout.contains(": repaired\n  `my_tool` has different contents\n"),
+        "{stdout}",
+    );
+    assert_eq!(fs::read_to_string(&artifact)?, "original contents\n");
+
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("verify")
+        .arg("--refetch")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0);
+
+    Ok(())
+}
+
+#[test]
+fn verify_command_offline_checks_metadata() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let dotslash_file = test_env.local_dotslash_file("my_tool", b"original contents\n")?;
+
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0);
+    let artifact = str::from_utf8(&assert.get_output().stdout)?
+        .trim_end()
+        .to_owned();
+
+    // The manifest has no hashes yet, and they cannot be fetched.
+    let assert = test_env
+        .dotslash_command()
+        .env("DOTSLASH_OFFLINE", "1")
+        .arg("--")
+        .arg("verify")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0);
+    let stdout = str::from_utf8(&assert.get_output().stdout)?;
+    assert!(stdout.ends_with(": ok (contents unverified)\n"), "{stdout}");
+
+    // Changes to the contents of the same size go unnoticed, but not others.
+    let mut perms = fs::metadata(&artifact)?.permissions();
+    #[expect(clippy::permissions_set_readonly_false)]
+    perms.set_readonly(false);
+    fs::set_permissions(&artifact, perms)?;
+    fs::write(&artifact, "longer tampered contents\n")?;
+
+    let assert = test_env
+        .dotslash_command()
+        .env("DOTSLASH_OFFLINE", "1")
+        .arg("--")
+        .arg("verify")
+        .arg(&dotslash_file)
+        .assert()
+        .code(1);
+    let stdout = str::from_utf8(&assert.get_output().stdout)?;
+    assert!(
+        stdout.contains(": drift detected\n  `my_tool` has different contents\n"),
+        "{stdout}",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn verify_command_requires_signature() -> anyhow::Result<()> {
+    let test_env = DotslashTestEnv::try_new()?;
+    let dotslash_file = test_env.local_dotslash_file("my_tool", b"contents\n")?;
+    fs::write(test_env.config_file(), r#"{"require_signature": true}"#)?;
+
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("verify")
+        .arg("--refetch")
+        .arg(&dotslash_file)
+        .assert()
+        .code(1)
+        .stdout_eq("");
+    let stderr = str::from_utf8(&assert.get_output().stderr)?;
+    assert!(
+        stderr.contains("caused by: DotSlash file is not signed"),
+        "{stderr}",
+    );
+    assert!(fs::read_dir(test_env.dotslash_cache())?.next().is_none());
+
+    // Without `--refetch` or `--repair`, what is in the cache is still
+    // checked, but nothing is fetched to fill in the hashes.
+    fs::remove_file(test_env.config_file())?;
+    test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("fetch")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0);
+    fs::write(test_env.config_file(), r#"{"require_signature": true}"#)?;
+    let assert = test_env
+        .dotslash_command()
+        .arg("--")
+        .arg("verify")
+        .arg(&dotslash_file)
+        .assert()
+        .code(0);
+    let output = assert.get_output();
+    let stdout = str::from_utf8(&output.stdout)?;
+    assert!(stdout.ends_with(": ok (contents unverified)\n"), "{stdout}");
+    let stderr = str::from_utf8(&output.stderr)?;
+    assert!(
+        stderr.starts_with("dotslash warning: only checking `")
+            && stderr.contains("` against the cache without fetching anything: "),
+        "{stderr}",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn verify_command_no_args() {
+    DotslashTestEnv::try_new()
+        .unwrap()
+        .dotslash_command()
+        .arg("--")
+        .arg("verify")
+        .assert()
+        .code(1)
+        .stdout_eq("")
+        .stderr_eq(
+            "\
+dotslash error: 'verify' command failed
+caused by: expected at least one argument but received none
+",
+        );
+}
@@ -0,0 +1,139 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is dual-licensed under either the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree or the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree. You may select, at your option, one of the
+ * above-listed licenses.
+ */
+
+//! Tests for using DotSlash as a library rather than through the binary.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::Context as _;
+use dotslash::ArtifactEntry;
+use dotslash::DotslashCache;
+use dotslash::DotslashFileInfo;
+use dotslash::FileLock;
+use dotslash::PlatformSelection;
+use dotslash::Provider;
+use dotslash::ProviderFactory;
+use serde_json::Value;
+
+/// Writes the `text` from its config rather than fetching anything.
+struct InlineProvider;
+
+impl Provider for InlineProvider {
+    fn fetch_artifact(
+        &self,
+        provider_config: &Value,
+        destination: &Path,
+        _fetch_lock: &FileLock,
+        _artifact_entry: &ArtifactEntry,
+    ) -> anyhow::Result<()> {
+        let text = provider_config["text"].as_str().context("missing `text`")?;
+        fs::write(destination, text)?;
+        Ok(())
+    }
+}
+
+struct InlineProviderFactory;
+
+impl ProviderFactory for InlineProviderFactory {
+    fn get_provider(&self, provider_type: &str) -> anyhow::Result<Box<dyn Provider>> {
+        match provider_type {
+            "inline" => Ok(Box::new(InlineProvider)),
+            _ => anyhow::bail!("unknown provider type: `{}`", provider_type),
+        }
+    }
+}
+
+fn inline_dotslash_file(text: &str) -> String {
+    let digest = blake3::hash(text.as_bytes());
+    format!(
+        r#"{header}
+{{
+  "name": "greeting",
+  "platforms": {{
+    "{platform}": {{
+      "size": {size},
+      "hash": "blake3",
+      "digest": "{digest}",
+      "path": "greeting.txt",
+      "providers": [{{"type": "inline", "text": "{text}"}}]
+    }}
+  }}
+}}
+"#,
+        header = dotslash::REQUIRED_HEADER,
+        platform = dotslash::SUPPORTED_PLATFORM,
+        size = text.len(),
+    )
+}
+
+#[test]
+fn locate_and_fetch_with_custom_provider() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let dotslash_cache = DotslashCache::new_in(temp_dir.path());
+    let dotslash_data = inline_dotslash_file("hello");
+
+    let (name, entries) =
+        dotslash::locate_artifacts(&dotslash_data, &dotslash_cache, &PlatformSelection::Host)?;
+    assert_eq!(name, "greeting");
+    let [entry] = entries.as_slice() else {
+        panic!("expected exactly one entry");
+    };
+    assert_eq!(entry.platform, dotslash::SUPPORTED_PLATFORM);
+    assert!(!entry.artifact_location.executable.exists());
+
+    dotslash::download_artifact(
+        &entry.artifact_entry,
+        &entry.artifact_location,
+        DotslashFileInfo {
+            name: &name,
+            path: Path::new("greeting"),
+        },
+        &InlineProviderFactory,
+    )?;
+    assert_eq!(
+        fs::read_to_string(&entry.artifact_location.executable)?,
+        "hello",
+    );
+
+    let cached = dotslash::list_artifacts(&dotslash_cache)?;
+    assert_eq!(cached.len(), 1);
+    assert_eq!(
+        cached[0].artifact_directory,
+        entry.artifact_location.artifact_directory,
+    );
+
+    Ok(())
+}
+
+#[test]
+fn fetch_rejects_content_that_does_not_match_digest() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let dotslash_cache = DotslashCache::new_in(temp_dir.path());
+    // Same size as "hello", so only the digest check can catch it.
+    let dotslash_data =
+        inline_dotslash_file("hello").replace(r#""text": "hello""#, r#""text": "jello""#);
+
+    let (name, entries) =
+        dotslash::locate_artifacts(&dotslash_data, &dotslash_cache, &PlatformSelection::Host)?;
+    let result = dotslash::download_artifact(
+        &entries[0].artifact_entry,
+        &entries[0].artifact_location,
+        DotslashFileInfo {
+            name: &name,
+            path: Path::new("greeting"),
+        },
+        &InlineProviderFactory,
+    );
+    assert!(result.is_err());
+    assert!(!entries[0].artifact_location.executable.exists());
+
+    Ok(())
+}